@@ -0,0 +1,55 @@
+use crate::address::PhysicalAddress;
+
+/// Physical devices.
+
+pub const DRAM_BASE: PhysicalAddress = PhysicalAddress::new(0x0000_0000);
+pub const DRAM_SIZE: usize = 0x3E00_0000;
+pub const DRAM_END: PhysicalAddress = DRAM_BASE + DRAM_SIZE;
+
+pub const PERIPHERALS_BASE: PhysicalAddress = PhysicalAddress::new(0x3F00_0000);
+pub const PERIPHERALS_SIZE: usize = 16 * 1024 * 1024;
+pub const PERIPHERALS_END: PhysicalAddress = PERIPHERALS_BASE + PERIPHERALS_SIZE;
+
+pub const GPIO_BASE: PhysicalAddress = PERIPHERALS_BASE + 0x20_0000usize;
+pub const GPIO_SIZE: usize = 0xA0;
+
+pub const PL011_UART_BASE: PhysicalAddress = PERIPHERALS_BASE + 0x20_1000usize;
+pub const PL011_UART_SIZE: usize = 0x48;
+
+// Local Peripheral Registers
+pub const LOCAL_REGISTERS_BASE: PhysicalAddress = PhysicalAddress::new(0x4000_0000);
+pub const LOCAL_REGISTERS_SIZE: usize = 0xFC;
+pub const LOCAL_REGISTERS_END: PhysicalAddress = LOCAL_REGISTERS_BASE + LOCAL_REGISTERS_SIZE;
+
+/// GIC-400 Distributor base. Replaces the BCM2837 legacy interrupt controller.
+pub const GICD_BASE: PhysicalAddress = PhysicalAddress::new(0x4000_1000);
+pub const GICD_SIZE: usize = 0x1000;
+
+/// GIC-400 CPU interface base.
+pub const GICC_BASE: PhysicalAddress = PhysicalAddress::new(0x4000_2000);
+pub const GICC_SIZE: usize = 0x1000;
+
+/// A physical MMIO region, registered once here and resolved to a virtual
+/// address via [`crate::vm::phy2virt`] (or, for one outside its static ranges
+/// such as the GIC's, [`crate::vm::map_mmio`]) wherever it's accessed, instead of
+/// every driver casting its own base constant straight to a pointer.
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceRegion {
+    pub base: PhysicalAddress,
+    pub size: usize,
+}
+
+pub const PL011_UART: DeviceRegion = DeviceRegion {
+    base: PL011_UART_BASE,
+    size: PL011_UART_SIZE,
+};
+
+pub const GICD: DeviceRegion = DeviceRegion {
+    base: GICD_BASE,
+    size: GICD_SIZE,
+};
+
+pub const GICC: DeviceRegion = DeviceRegion {
+    base: GICC_BASE,
+    size: GICC_SIZE,
+};
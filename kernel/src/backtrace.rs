@@ -0,0 +1,67 @@
+//! Best-effort AArch64 frame-pointer unwinder, used to print a list of return
+//! addresses from a panic or an unhandled CPU exception.
+//!
+//! AAPCS64 has every non-leaf function open its prologue with
+//! `stp x29, x30, [sp, #-16]!; mov x29, sp`, chaining `x29` (the frame
+//! pointer) back through every live call frame: `[x29]` holds the caller's
+//! `x29`, `[x29 + 8]` the caller's `x30` (return address). Walking that chain
+//! needs no debug info, just the current `(fp, lr)` pair.
+
+use crate::{
+    address::PhysicalAddress,
+    kimage::{kernel_phy_range, kernel_stack_range},
+    uart,
+};
+
+/// Hard cap on frames printed, guarding against a corrupted or cyclic FP
+/// chain walking off into memory that happens to look like a frame forever.
+const MAX_FRAMES: usize = 32;
+
+#[repr(C)]
+struct StackFrame {
+    fp: *const StackFrame,
+    lr: usize,
+}
+
+/// Whether `addr` falls inside the kernel's own image or stack -- the only
+/// places a legitimate frame pointer can point.
+fn fp_in_range(addr: usize) -> bool {
+    let paddr = PhysicalAddress::new(addr);
+    kernel_phy_range().contains(&paddr) || kernel_stack_range().contains(&paddr)
+}
+
+/// Prints up to [`MAX_FRAMES`] return addresses starting from `(fp, lr)` --
+/// `x29`/`x30` as captured by `#[exception_handler]`'s spill sequence, or read
+/// live off the registers at a panic site.
+///
+/// `lr` (frame #0, the innermost return address) is printed unconditionally --
+/// right after entry to a leaf function it may not reflect a real caller yet,
+/// but it isn't dereferenced, only printed. Every subsequent frame's `fp` is
+/// checked for a null value, 8-byte alignment, and falling within
+/// `kernel_phy_range()`/`kernel_stack_range()` before it's dereferenced, so a
+/// bogus chain stops the walk instead of faulting.
+pub(crate) fn backtrace(fp: usize, lr: usize) {
+    uart::print_blocking(format_args!("Backtrace:\n"));
+    uart::print_blocking(format_args!("  #0: {lr:#018x}\n"));
+
+    let mut fp = fp;
+    for i in 1..MAX_FRAMES {
+        if fp == 0 || fp % 8 != 0 || !fp_in_range(fp) {
+            break;
+        }
+
+        // Safety: `fp` was just validated as non-null, 8-byte aligned, and
+        // within the kernel's image or stack.
+        let frame = unsafe { &*(fp as *const StackFrame) };
+        let next_fp = frame.fp as usize;
+
+        uart::print_blocking(format_args!("  #{i}: {:#018x}\n", frame.lr));
+
+        if next_fp == fp {
+            // Self-referential frame -- following it further would spin
+            // forever without ever leaving `fp_in_range`.
+            break;
+        }
+        fp = next_fp;
+    }
+}
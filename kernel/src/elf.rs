@@ -0,0 +1,96 @@
+//! Minimal ELF64 program-header reader.
+//!
+//! Only the handful of fields needed to recover `PT_LOAD` segment geometry (VA,
+//! PA, size, R/W/X flags) are parsed here — this isn't a general-purpose ELF
+//! loader, just enough for [`crate::kimage::kernel_segments`] to read the kernel's
+//! own segment layout back out of its linked image.
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const PT_LOAD: u32 = 1;
+
+pub const PF_X: u32 = 1 << 0;
+pub const PF_W: u32 = 1 << 1;
+pub const PF_R: u32 = 1 << 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Elf64Ehdr {
+    e_ident: [u8; 16],
+    e_type: u16,
+    e_machine: u16,
+    e_version: u32,
+    e_entry: u64,
+    e_phoff: u64,
+    e_shoff: u64,
+    e_flags: u32,
+    e_ehsize: u16,
+    e_phentsize: u16,
+    e_phnum: u16,
+    e_shentsize: u16,
+    e_shnum: u16,
+    e_shstrndx: u16,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Elf64Phdr {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_paddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+    p_align: u64,
+}
+
+/// A single `PT_LOAD` segment's geometry and permissions, as recorded in the ELF
+/// program header table.
+#[derive(Debug, Clone, Copy)]
+pub struct Segment {
+    pub virt_addr: usize,
+    pub phys_addr: usize,
+    pub mem_size: usize,
+    pub flags: u32,
+}
+
+/// Reads every `PT_LOAD` segment out of the ELF64 header at `elf_base`, writing
+/// up to `out.len()` of them into `out` and returning how many were found.
+/// Returns `0` without touching `out` if `elf_base` doesn't point at a valid
+/// ELF64 header.
+///
+/// # Safety
+///
+/// `elf_base` must point to a readable ELF64 header immediately followed (at
+/// `e_phoff`) by its program header table, as the linker/loader is expected to
+/// lay out (see `kimage::kernel_segments`).
+pub unsafe fn load_segments(elf_base: *const u8, out: &mut [Option<Segment>]) -> usize {
+    let ehdr = &*(elf_base as *const Elf64Ehdr);
+    if ehdr.e_ident[0..4] != ELF_MAGIC {
+        return 0;
+    }
+
+    let phdr_base = elf_base.add(ehdr.e_phoff as usize) as *const Elf64Phdr;
+    let mut count = 0;
+
+    for i in 0..(ehdr.e_phnum as usize) {
+        if count == out.len() {
+            break;
+        }
+
+        let phdr = &*phdr_base.add(i);
+        if phdr.p_type != PT_LOAD {
+            continue;
+        }
+
+        out[count] = Some(Segment {
+            virt_addr: phdr.p_vaddr as usize,
+            phys_addr: phdr.p_paddr as usize,
+            mem_size: phdr.p_memsz as usize,
+            flags: phdr.p_flags,
+        });
+        count += 1;
+    }
+
+    count
+}
@@ -1,20 +1,28 @@
-use crate::address::PhysicalAddress;
+use crate::address::{PhysicalAddress, VirtualAddress};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Error {
-    BumpAllocatorOOM(usize),
     InvalidVirtualAddress(usize),
     PhysicalAddressNotStaticallyMapped(PhysicalAddress),
+    UnalignedVectorTableBase(usize),
+    VMMapExists(VirtualAddress),
+    MmioWindowExhausted,
+    PhysicalOOM,
+    ContigiousPhysicalRangeUnavailable(usize),
+    AddressSpaceTableFull,
+    MappingNotFound(usize),
+    DemandRegionTableFull,
+    SchedulerTableFull,
+    OperationNotPermitted,
+    ModuleFilterTableFull,
+    CowRegionTableFull,
+    CowFrameTableFull,
+    InvalidUserRange(usize, usize),
 }
 
 impl core::fmt::Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Error::BumpAllocatorOOM(max_size) => write!(
-                f,
-                "Static Bump Allocator OOM. Configured Size = {}",
-                max_size
-            ),
             Error::InvalidVirtualAddress(addr) => write!(f, "Invalid Virtual Address `{}`", addr),
             Error::PhysicalAddressNotStaticallyMapped(paddr) => write!(
                 f,
@@ -22,6 +30,55 @@ impl core::fmt::Display for Error {
             Peripheral, Kernel image addresses are statically mapped, for example",
                 paddr
             ),
+            Error::UnalignedVectorTableBase(addr) => write!(
+                f,
+                "Vector table base `0x{:X}` is not 2 KiB aligned (VBAR[10:0] must be 0)",
+                addr
+            ),
+            Error::VMMapExists(virt_addr) => write!(
+                f,
+                "Physical address is already remapped at `{}`",
+                virt_addr
+            ),
+            Error::MmioWindowExhausted => {
+                write!(f, "MMIO remap window exhausted, no VA space left to carve out")
+            }
+            Error::PhysicalOOM => write!(f, "Physical frame allocator is out of memory"),
+            Error::ContigiousPhysicalRangeUnavailable(num_pages) => write!(
+                f,
+                "No contiguous run of {} physical page(s) is available",
+                num_pages
+            ),
+            Error::AddressSpaceTableFull => {
+                write!(f, "Address space mapping table is full")
+            }
+            Error::MappingNotFound(addr) => {
+                write!(f, "No mapping covers virtual address `0x{:X}`", addr)
+            }
+            Error::DemandRegionTableFull => {
+                write!(f, "Demand-paged region table is full")
+            }
+            Error::SchedulerTableFull => {
+                write!(f, "Task table is full, no room for another spawned task")
+            }
+            Error::OperationNotPermitted => {
+                write!(f, "Calling task's profile does not permit this operation")
+            }
+            Error::ModuleFilterTableFull => {
+                write!(f, "Module log-level filter table is full")
+            }
+            Error::CowRegionTableFull => {
+                write!(f, "Copy-on-write region table is full")
+            }
+            Error::CowFrameTableFull => {
+                write!(f, "Copy-on-write frame refcount table is full")
+            }
+            Error::InvalidUserRange(ptr, len) => write!(
+                f,
+                "Syscall buffer `0x{:X}`..+{} isn't fully mapped readable in \
+                 the calling task's own address space",
+                ptr, len
+            ),
         }
     }
 }
@@ -7,18 +7,30 @@ use core::{
     fmt,
 };
 use macros::exception_handler;
+use spin::Mutex;
 use tock_registers::{
     interfaces::{Readable, Writeable},
     registers::InMemoryRegister,
 };
 
-use crate::{gic::dispatch_peripheral_irq, println};
+use crate::{
+    address::{Address, VirtualAddress},
+    error::{Error, Result},
+    gic::{dispatch_fiq, dispatch_peripheral_irq},
+    mmu, println, syscall, vm,
+};
+
+/// VBAR requires the vector table base to be aligned to 2 KiB (bits [10:0] == 0).
+const VECTOR_TABLE_ALIGNMENT: usize = 0x800;
 
 global_asm!(include_str!("../asm/rpi3/exception.s"));
 
 mod daifbits {
-    pub const IRQ_ENABLE: u8 = 0b0010;
-    pub const IRQ_DISABLE: u8 = 0b0000;
+    /// Bit position of each maskable exception in the 4-bit immediate `DAIFSet`/
+    /// `DAIFClr` take -- the same encoding the `DAIF` register itself uses for its
+    /// D/A/I/F mask bits.
+    pub const FIQ: u8 = 0b0001;
+    pub const IRQ: u8 = 0b0010;
 }
 
 /// .
@@ -29,7 +41,7 @@ mod daifbits {
 pub unsafe fn enable_irq() {
     asm!(
         "msr DAIFClr, {arg}",
-        arg = const daifbits::IRQ_ENABLE,
+        arg = const daifbits::IRQ,
         options(nomem, nostack, preserves_flags)
     );
 }
@@ -40,13 +52,71 @@ pub unsafe fn enable_irq() {
 ///
 /// Disables Asynchronous interrupts
 pub unsafe fn disable_irq() {
+    asm!(
+        "msr DAIFSet, {arg}",
+        arg = const daifbits::IRQ,
+        options(nomem, nostack, preserves_flags)
+    );
+}
+
+/// .
+///
+/// # Safety
+///
+/// Unmasks FIQ, letting interrupts promoted to Group 0 preempt even while IRQ is
+/// masked.
+pub unsafe fn enable_fiq() {
     asm!(
         "msr DAIFClr, {arg}",
-        arg = const daifbits::IRQ_DISABLE,
+        arg = const daifbits::FIQ,
         options(nomem, nostack, preserves_flags)
     );
 }
 
+/// RAII guard that masks IRQs for the duration of a critical section.
+///
+/// Unlike calling `disable_irq`/`enable_irq` directly, dropping a guard restores
+/// whatever mask state was in effect when it was created instead of
+/// unconditionally unmasking -- so a guard taken out while IRQs were already
+/// masked (e.g. nested inside `dispatch_peripheral_irq`, which runs with IRQs
+/// masked on exception entry) doesn't unmask them out from under its caller when
+/// it drops.
+pub struct IrqGuard {
+    was_masked: bool,
+}
+
+impl IrqGuard {
+    /// Masks IRQs, remembering the previous mask state so `Drop` can restore it.
+    pub fn new() -> Self {
+        let was_masked = DAIF.is_set(DAIF::I);
+
+        // Safety: masking IRQs for the guard's lifetime is exactly the invariant
+        // `Drop` relies on to restore the saved state correctly.
+        unsafe {
+            disable_irq();
+        }
+
+        Self { was_masked }
+    }
+}
+
+impl Default for IrqGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for IrqGuard {
+    fn drop(&mut self) {
+        if !self.was_masked {
+            // Safety: only unmasks IRQs that this guard itself masked.
+            unsafe {
+                enable_irq();
+            }
+        }
+    }
+}
+
 /// Initialized by ASM
 #[no_mangle]
 static VECTOR_TABLE_BASE_ADDR: u64 = 0;
@@ -62,6 +132,42 @@ pub unsafe fn handler_init() {
     VBAR_EL1.set(vt_base);
 }
 
+/// Installs an alternate EL1 vector table at `base`, replacing whatever
+/// `handler_init` (or a previous call to this function) set up. Lets recovery code
+/// or tests swap in a minimal table (e.g. a panic-only one) at runtime instead of
+/// being locked to the boot-time table.
+///
+/// # Safety
+///
+/// `base` must point to a valid, live vector table with the layout expected by the
+/// AArch64 exception model (16 entries of 128 bytes each).
+pub unsafe fn set_vector_table(base: VirtualAddress) -> Result<()> {
+    let addr = base.as_raw_ptr();
+    if addr % VECTOR_TABLE_ALIGNMENT != 0 {
+        return Err(Error::UnalignedVectorTableBase(addr));
+    }
+
+    VBAR_EL1.set(addr as u64);
+    Ok(())
+}
+
+/// EL2 counterpart of `set_vector_table`, for the brief window between reset and
+/// `switch_from_el2_to_el1` where exceptions are still routed through `VBAR_EL2`.
+///
+/// # Safety
+///
+/// Same requirements as `set_vector_table`, and must be called while still
+/// executing at EL2.
+pub unsafe fn set_vector_table_el2(base: VirtualAddress) -> Result<()> {
+    let addr = base.as_raw_ptr();
+    if addr % VECTOR_TABLE_ALIGNMENT != 0 {
+        return Err(Error::UnalignedVectorTableBase(addr));
+    }
+
+    VBAR_EL2.set(addr as u64);
+    Ok(())
+}
+
 /// Wrapper structs for memory copies of registers.
 #[repr(transparent)]
 struct SpsrEL1(InMemoryRegister<u64, SPSR_EL1::Register>);
@@ -85,10 +191,111 @@ pub(crate) struct ExceptionContext {
 
     /// Exception syndrome register.
     esr_el1: EsrEL1,
+
+    /// EL0's own stack pointer, banked separately from whichever `SP_ELx` this
+    /// context's own registers were pushed onto. Only meaningful for a context
+    /// that resumes at EL0 (see [`ExceptionContext::new_for_task_el0`]); for an
+    /// EL1h task it's saved/restored along with everything else but never read.
+    sp_el0: u64,
 }
 
 fn default_handler(funcname: &str, ec: &mut ExceptionContext) {
-    println!("Unhandled CPU Exception({funcname}): {ec}");
+    crate::panic::panic_with_context(funcname, ec);
+}
+
+mod dfsc {
+    /// Data/Instruction Fault Status Code, `ESR_EL1.ISS[5:0]`. The low 2 bits give
+    /// the translation-table level the fault happened at; the top 4 distinguish
+    /// the broad fault category -- translation fault is `0b0001LL`, permission
+    /// fault is `0b0011LL`.
+    pub const LEVEL_MASK: u64 = 0b00_0011;
+    pub const CATEGORY_MASK: u64 = 0b11_1100;
+    pub const ADDRESS_SIZE_FAULT: u64 = 0b00_0000;
+    pub const TRANSLATION_FAULT: u64 = 0b00_0100;
+    pub const ACCESS_FLAG_FAULT: u64 = 0b00_1000;
+    pub const PERMISSION_FAULT: u64 = 0b00_1100;
+    /// Doesn't follow the category+level scheme above -- a standalone code.
+    pub const ALIGNMENT_FAULT: u64 = 0b10_0001;
+}
+
+/// Describes a DFSC/IFSC value (they share the same encoding) as a fault category
+/// plus, for the categories that carry one, the translation-table level it
+/// happened at.
+fn describe_dfsc(dfsc: u64) -> (&'static str, Option<u8>) {
+    let level = (dfsc & dfsc::LEVEL_MASK) as u8;
+
+    match dfsc & dfsc::CATEGORY_MASK {
+        dfsc::ADDRESS_SIZE_FAULT => ("Address size fault", Some(level)),
+        dfsc::TRANSLATION_FAULT => ("Translation fault", Some(level)),
+        dfsc::ACCESS_FLAG_FAULT => ("Access flag fault", Some(level)),
+        dfsc::PERMISSION_FAULT => ("Permission fault", Some(level)),
+        _ if dfsc == dfsc::ALIGNMENT_FAULT => ("Alignment fault", None),
+        _ => ("Unrecognized fault status", None),
+    }
+}
+
+/// Tries to complete a translation fault by demand-paging in a frame for
+/// whichever region `vm::demand_region_for` recognizes `FAR_EL1` as belonging to.
+/// Leaves `elr_el1` untouched on success, so the handler's asm trampoline `eret`s
+/// straight back into the faulting instruction, which now succeeds against the
+/// freshly installed mapping. Returns `false` for permission faults and faults
+/// outside any known region, so the caller falls through to `default_handler`.
+fn try_recover_page_fault(ec: &ExceptionContext) -> bool {
+    if !ec.is_abort() || ec.esr_el1.dfsc() & dfsc::CATEGORY_MASK != dfsc::TRANSLATION_FAULT {
+        return false;
+    }
+
+    let Ok(fault_addr) = VirtualAddress::new(FAR_EL1.get() as usize) else {
+        return false;
+    };
+
+    let Some(perms) = vm::demand_region_for(fault_addr) else {
+        return false;
+    };
+
+    mmu::handle_demand_fault(fault_addr, perms).is_ok()
+}
+
+/// Tries to complete a write-caused permission fault by giving the faulting
+/// side its own private copy of a page `vm::fork` marked copy-on-write
+/// (`vm::cow_region_for`), via `mmu::handle_cow_fault`. Like
+/// `try_recover_page_fault`, leaves `elr_el1` untouched on success, so the
+/// trampoline `eret`s straight back into the faulting store, which now
+/// succeeds against the freshly copied, writable mapping.
+///
+/// Returns `false` for anything else -- a read-caused permission fault is
+/// never recoverable this way (COW pages are always left readable), and
+/// neither is a write fault outside any COW region; both fall through to
+/// `default_handler` as genuine protection violations.
+fn try_recover_cow_fault(ec: &ExceptionContext) -> bool {
+    if !ec.is_abort()
+        || ec.esr_el1.dfsc() & dfsc::CATEGORY_MASK != dfsc::PERMISSION_FAULT
+        || !ec.esr_el1.wnr()
+    {
+        return false;
+    }
+
+    let Ok(fault_addr) = VirtualAddress::new(FAR_EL1.get() as usize) else {
+        return false;
+    };
+
+    let Some(perms) = vm::cow_region_for(fault_addr) else {
+        return false;
+    };
+
+    mmu::handle_cow_fault(fault_addr, perms).is_ok()
+}
+
+/// Dispatches an EL0 `svc` trap to the kernel syscall table and writes the result
+/// back into `gpr[0]`. Returns normally -- `ELR_EL1` already points just past the
+/// `svc` instruction, so the handler's `eret` resumes EL0 right after the call
+/// site with no adjustment needed.
+fn handle_syscall(ec: &mut ExceptionContext) {
+    let number = ec.esr_el1.svc_imm();
+    let args = [
+        ec.gpr[0], ec.gpr[1], ec.gpr[2], ec.gpr[3], ec.gpr[4], ec.gpr[5],
+    ];
+    ec.gpr[0] = syscall::dispatch(ec, number, args);
 }
 
 #[exception_handler]
@@ -113,7 +320,7 @@ fn current_el_sp0_serror(ec: &mut ExceptionContext) {
 
 #[exception_handler]
 fn current_el_spn_sync(ec: &mut ExceptionContext) {
-    default_handler("current_el_spn_sync", ec);
+    dispatch_sync_exception("current_el_spn_sync", ec);
 }
 
 #[exception_handler]
@@ -125,7 +332,9 @@ fn current_el_spn_irq(ec: &mut ExceptionContext) {
 
 #[exception_handler]
 fn current_el_spn_fiq(ec: &mut ExceptionContext) {
-    default_handler("current_el_spn_fiq", ec);
+    if !dispatch_fiq(ec) {
+        default_handler("current_el_spn_fiq", ec);
+    }
 }
 
 #[exception_handler]
@@ -135,7 +344,7 @@ fn current_el_spn_serror(ec: &mut ExceptionContext) {
 
 #[exception_handler]
 fn lower_el_aarch64_sync(ec: &mut ExceptionContext) {
-    default_handler("lower_el_aarch64_sync", ec);
+    dispatch_sync_exception("lower_el_aarch64_sync", ec);
 }
 
 #[exception_handler]
@@ -147,7 +356,9 @@ fn lower_el_aarch64_irq(ec: &mut ExceptionContext) {
 
 #[exception_handler]
 fn lower_el_aarch64_fiq(ec: &mut ExceptionContext) {
-    default_handler("lower_el_aarch64_fiq", ec);
+    if !dispatch_fiq(ec) {
+        default_handler("lower_el_aarch64_fiq", ec);
+    }
 }
 
 #[exception_handler]
@@ -212,17 +423,163 @@ impl fmt::Display for SpsrEL1 {
     }
 }
 
+/// Simplified classification of `ESR_EL1.EC` decoded straight off the raw
+/// `ESR_EL1[31:26]` field rather than `aarch64_cpu`'s own `ESR_EL1::EC::Value`,
+/// so it covers classes (like `Brk`) that call site only cares to tell apart
+/// from "some other trap", collapsing the lower-EL/current-EL split the raw
+/// encoding carries -- a registered handler already knows which vector it's
+/// reachable from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExceptionClass {
+    DataAbort,
+    InstructionAbort,
+    SVCall,
+    Brk,
+    Unknown,
+}
+
+/// Raw `ESR_EL1.EC` values `ExceptionClass::decode` recognizes. Per ARM DDI
+/// 0487, `EC` is `ESR_EL1[31:26]`.
+mod ec {
+    pub const INSTR_ABORT_LOWER_EL: u64 = 0b100000;
+    pub const INSTR_ABORT_CURRENT_EL: u64 = 0b100001;
+    pub const DATA_ABORT_LOWER_EL: u64 = 0b100100;
+    pub const DATA_ABORT_CURRENT_EL: u64 = 0b100101;
+    pub const SVC64: u64 = 0b010101;
+    pub const BRK: u64 = 0b111100;
+}
+
+impl ExceptionClass {
+    const COUNT: usize = 5;
+
+    fn decode(raw_ec: u64) -> Self {
+        match raw_ec {
+            ec::DATA_ABORT_LOWER_EL | ec::DATA_ABORT_CURRENT_EL => ExceptionClass::DataAbort,
+            ec::INSTR_ABORT_LOWER_EL | ec::INSTR_ABORT_CURRENT_EL => {
+                ExceptionClass::InstructionAbort
+            }
+            ec::SVC64 => ExceptionClass::SVCall,
+            ec::BRK => ExceptionClass::Brk,
+            _ => ExceptionClass::Unknown,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            ExceptionClass::DataAbort => 0,
+            ExceptionClass::InstructionAbort => 1,
+            ExceptionClass::SVCall => 2,
+            ExceptionClass::Brk => 3,
+            ExceptionClass::Unknown => 4,
+        }
+    }
+}
+
+/// Handler a subsystem registers against an [`ExceptionClass`] via
+/// [`register_trap_handler`], the synchronous-exception counterpart of
+/// `gic::IRQHandler`.
+pub(crate) trait TrapHandler: Send + Sync {
+    /// Handles the trap, returning whether it was actually resolved. `false`
+    /// falls through to the next registered handler (there is only ever one
+    /// today) and finally to the unhandled-trap dump.
+    fn handle(&self, ec: &mut ExceptionContext) -> bool;
+}
+
+lazy_static! {
+    static ref REGISTERED_TRAP_HANDLERS: Mutex<[Option<&'static dyn TrapHandler>; ExceptionClass::COUNT]> =
+        Mutex::new([None; ExceptionClass::COUNT]);
+}
+
+/// Registers `handler` to run for every synchronous exception `dispatch_sync_exception`
+/// classifies as `class`.
+pub(crate) fn register_trap_handler(class: ExceptionClass, handler: &'static dyn TrapHandler) {
+    REGISTERED_TRAP_HANDLERS.lock()[class.index()] = Some(handler);
+}
+
+/// Routes a synchronous exception by its [`ExceptionClass`]: demand-paging
+/// recovery for aborts, the syscall table for `svc`, then whatever handler
+/// `register_trap_handler` installed for the class. Falls through to
+/// `default_handler`'s unhandled-trap dump (class, `FAR_EL1`, `ELR_EL1` all
+/// included via `ExceptionContext`'s `Display` impl) if nothing resolves it.
+fn dispatch_sync_exception(funcname: &str, ec: &mut ExceptionContext) {
+    let class = ec.class();
+
+    let resolved = match class {
+        ExceptionClass::DataAbort | ExceptionClass::InstructionAbort => {
+            try_recover_page_fault(ec) || try_recover_cow_fault(ec)
+        }
+        ExceptionClass::SVCall => {
+            handle_syscall(ec);
+            true
+        }
+        _ => false,
+    };
+
+    if resolved {
+        return;
+    }
+
+    let registered = REGISTERED_TRAP_HANDLERS.lock()[class.index()];
+    if let Some(handler) = registered {
+        if handler.handle(ec) {
+            return;
+        }
+    }
+
+    default_handler(funcname, ec);
+}
+
 impl EsrEL1 {
     #[inline(always)]
     fn exception_class(&self) -> Option<ESR_EL1::EC::Value> {
         self.0.read_as_enum(ESR_EL1::EC)
     }
+
+    #[inline(always)]
+    fn raw_ec(&self) -> u64 {
+        self.0.read(ESR_EL1::EC)
+    }
+
+    #[inline(always)]
+    fn dfsc(&self) -> u64 {
+        self.0.read(ESR_EL1::ISS) & 0b11_1111
+    }
+
+    /// The 16-bit immediate encoded in an `svc` instruction, carried in the low
+    /// 16 bits of the ISS for exception class `SVC64`.
+    #[inline(always)]
+    fn svc_imm(&self) -> u16 {
+        (self.0.read(ESR_EL1::ISS) & 0xFFFF) as u16
+    }
+
+    /// Write-not-Read, `ESR_EL1.ISS[6]`: set if a data abort was caused by a
+    /// write, clear if by a read. Meaningless for any other exception class.
+    #[inline(always)]
+    fn wnr(&self) -> bool {
+        self.0.read(ESR_EL1::ISS) & (1 << 6) != 0
+    }
+
+    /// Syndrome Access Size, `ESR_EL1.ISS[23:22]`, the width of the access that
+    /// faulted. Only meaningful when `ISV` (`ISS[24]`) is set -- the CPU leaves it
+    /// clear when the faulting instruction can't be described as a single
+    /// register load/store (e.g. a multi-register transfer).
+    #[inline(always)]
+    fn sas(&self) -> Option<u8> {
+        let iss = self.0.read(ESR_EL1::ISS);
+        if iss & (1 << 24) == 0 {
+            return None;
+        }
+
+        Some(((iss >> 22) & 0b11) as u8)
+    }
 }
 
 /// Human readable ESR_EL1.
 #[rustfmt::skip]
 impl fmt::Display for EsrEL1 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ESR_EL1::EC::Value::*;
+
         // Raw print of whole register.
         writeln!(f, "ESR_EL1: {:#010x}", self.0.get())?;
 
@@ -230,23 +587,143 @@ impl fmt::Display for EsrEL1 {
         write!(f, "      Exception Class         (EC) : {:#x}", self.0.read(ESR_EL1::EC))?;
 
         // Exception class.
-        let ec_translation = match self.exception_class() {
-            Some(ESR_EL1::EC::Value::DataAbortCurrentEL) => "Data Abort, current EL",
-            _ => "N/A",
+        let ec = self.exception_class();
+        let ec_translation = match ec {
+            Some(Unknown) => "Unknown reason",
+            Some(TrappedWFIorWFE) => "Trapped WFI/WFE",
+            Some(TrappedFPorASIMD) => "Trapped FP/ASIMD, unit disabled",
+            Some(IllegalExecutionState) => "Illegal Execution State",
+            Some(SVC64) => "SVC instruction",
+            Some(HVC64) => "HVC instruction",
+            Some(TrappedMsrMrs) => "Trapped MSR/MRS/system instruction",
+            Some(InstrAbortLowerEL) => "Instruction Abort, lower EL",
+            Some(InstrAbortCurrentEL) => "Instruction Abort, current EL",
+            Some(PCAlignmentFault) => "PC alignment fault",
+            Some(DataAbortLowerEL) => "Data Abort, lower EL",
+            Some(DataAbortCurrentEL) => "Data Abort, current EL",
+            Some(SPAlignmentFault) => "SP alignment fault",
+            Some(SError) => "SError interrupt",
+            Some(BreakpointLowerEL) => "Breakpoint, lower EL",
+            Some(BreakpointCurrentEL) => "Breakpoint, current EL",
+            Some(WatchpointLowerEL) => "Watchpoint, lower EL",
+            Some(WatchpointCurrentEL) => "Watchpoint, current EL",
+            None => "N/A",
         };
         writeln!(f, " - {ec_translation}")?;
 
         // Raw print of instruction specific syndrome.
-        write!(f, "      Instr Specific Syndrome (ISS): {:#x}", self.0.read(ESR_EL1::ISS))
+        writeln!(f, "      Instr Specific Syndrome (ISS): {:#x}", self.0.read(ESR_EL1::ISS))?;
+
+        // Structured ISS breakdown for the abort classes -- everything else keeps
+        // only the raw dump above, since the remaining bits mean nothing outside
+        // an abort.
+        let is_abort = matches!(
+            ec,
+            Some(InstrAbortLowerEL | InstrAbortCurrentEL | DataAbortLowerEL | DataAbortCurrentEL)
+        );
+        if !is_abort {
+            return Ok(());
+        }
+
+        let (fault_kind, level) = describe_dfsc(self.dfsc());
+        write!(f, "            Fault Status (DFSC/IFSC): {fault_kind}")?;
+        match level {
+            Some(level) => writeln!(f, ", level {level}")?,
+            None => writeln!(f)?,
+        }
+
+        let is_data_abort = matches!(ec, Some(DataAbortLowerEL | DataAbortCurrentEL));
+        if is_data_abort {
+            writeln!(f, "            Access Type          (WnR): {}", if self.wnr() { "Write" } else { "Read" })?;
+
+            let sas_meaning = match self.sas() {
+                Some(0b00) => "Byte",
+                Some(0b01) => "Halfword",
+                Some(0b10) => "Word",
+                Some(0b11) => "Doubleword",
+                _ => "Unknown (ISV clear)",
+            };
+            write!(f, "            Access Size          (SAS): {sas_meaning}")?;
+        }
+
+        Ok(())
     }
 }
 
 impl ExceptionContext {
+    /// Synthesizes the context a freshly spawned task "resumes into": `sched::Task`
+    /// writes this onto the task's own kernel stack so that the first time
+    /// `__sched_maybe_switch` points `sp` at it, the generic restore sequence in
+    /// `#[exception_handler]`'s asm (which can't tell a real preemption from a task
+    /// that's never run yet) lands the `eret` at `entry`, running at EL1 with `sp`
+    /// selected (`EL1h`) and interrupts unmasked, same as any other task.
+    pub(crate) fn new_for_task(entry: usize) -> Self {
+        let spsr_el1 = InMemoryRegister::new(0);
+        spsr_el1.write(
+            SPSR_EL1::D::Unmasked
+                + SPSR_EL1::A::Unmasked
+                + SPSR_EL1::I::Unmasked
+                + SPSR_EL1::F::Unmasked
+                + SPSR_EL1::M::EL1h,
+        );
+
+        Self::bare(entry as u64, spsr_el1, 0)
+    }
+
+    /// The EL0 counterpart of [`Self::new_for_task`]: resumes at `entry` running
+    /// at EL0 with `user_sp` as `SP_EL0`, interrupts unmasked, same as any other
+    /// task. `sched::spawn_user` writes this onto the new task's own *kernel*
+    /// stack -- the trap frame a `svc`/fault from EL0 lands in -- exactly like
+    /// `spawn` does for an EL1h task; only the saved `SPSR_EL1.M` and `SP_EL0`
+    /// differ.
+    pub(crate) fn new_for_task_el0(entry: usize, user_sp: usize) -> Self {
+        let spsr_el1 = InMemoryRegister::new(0);
+        spsr_el1.write(
+            SPSR_EL1::D::Unmasked
+                + SPSR_EL1::A::Unmasked
+                + SPSR_EL1::I::Unmasked
+                + SPSR_EL1::F::Unmasked
+                + SPSR_EL1::M::EL0t,
+        );
+
+        Self::bare(entry as u64, spsr_el1, user_sp as u64)
+    }
+
+    fn bare(
+        elr_el1: u64,
+        spsr_el1: InMemoryRegister<u64, SPSR_EL1::Register>,
+        sp_el0: u64,
+    ) -> Self {
+        Self {
+            gpr: [0; 30],
+            lr: 0,
+            elr_el1,
+            spsr_el1: SpsrEL1(spsr_el1),
+            esr_el1: EsrEL1(InMemoryRegister::new(0)),
+            sp_el0,
+        }
+    }
+
     #[inline(always)]
     fn exception_class(&self) -> Option<ESR_EL1::EC::Value> {
         self.esr_el1.exception_class()
     }
 
+    /// The simplified [`ExceptionClass`] `dispatch_sync_exception` and
+    /// `register_trap_handler` key off of.
+    #[inline(always)]
+    pub(crate) fn class(&self) -> ExceptionClass {
+        ExceptionClass::decode(self.esr_el1.raw_ec())
+    }
+
+    /// `(x29, lr)` as captured by the `#[exception_handler]` spill sequence --
+    /// the frame-pointer/link-register pair `backtrace::backtrace` starts
+    /// unwinding from.
+    #[inline(always)]
+    pub(crate) fn frame_pointer_and_lr(&self) -> (usize, usize) {
+        (self.gpr[29] as usize, self.lr as usize)
+    }
+
     #[inline(always)]
     fn fault_address_valid(&self) -> bool {
         use ESR_EL1::EC::Value::*;
@@ -265,6 +742,18 @@ impl ExceptionContext {
             ),
         }
     }
+
+    /// Whether this exception is a data or instruction abort, i.e. the classes
+    /// `dfsc()` is meaningful for.
+    #[inline(always)]
+    fn is_abort(&self) -> bool {
+        use ESR_EL1::EC::Value::*;
+
+        matches!(
+            self.exception_class(),
+            Some(InstrAbortLowerEL | InstrAbortCurrentEL | DataAbortLowerEL | DataAbortCurrentEL)
+        )
+    }
 }
 
 /// Human readable print of the exception context.
@@ -278,6 +767,7 @@ impl fmt::Display for ExceptionContext {
 
         writeln!(f, "{}", self.spsr_el1)?;
         writeln!(f, "ELR_EL1: {:#018x}", self.elr_el1)?;
+        writeln!(f, "SP_EL0:  {:#018x}", self.sp_el0)?;
         writeln!(f)?;
         writeln!(f, "General purpose register:")?;
 
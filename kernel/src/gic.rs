@@ -1,28 +1,92 @@
-use aarch64_cpu::registers::CNTP_CTL_EL0;
 use spin::mutex::Mutex;
-use tock_registers::interfaces::Readable;
+use tock_registers::interfaces::{Readable, Writeable};
+use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
+use tock_registers::{register_bitfields, register_structs};
 
 use crate::{
-    address::PhysicalAddress,
+    address::Address,
+    address_map::{GICC, GICD},
     error::Result,
     exception::ExceptionContext,
-    mimo::{CNTP_STATUS_EL0, MIMORW, PERIPHERAL_IC_BASE},
+    vm::map_mmio,
 };
 
-const IRQ_BASIC_PENDING: PhysicalAddress = PERIPHERAL_IC_BASE;
-const ENABLE_IRQS_1: PhysicalAddress = PERIPHERAL_IC_BASE + 0x10usize;
-const ENABLE_IRQS_2: PhysicalAddress = PERIPHERAL_IC_BASE + 0x14usize;
-const ENABLE_BASIC_IRQS: PhysicalAddress = PERIPHERAL_IC_BASE + 0x18usize;
-const DISABLE_IRQS_1: PhysicalAddress = PERIPHERAL_IC_BASE + 0x1Cusize;
-const DISABLE_IRQS_2: PhysicalAddress = PERIPHERAL_IC_BASE + 0x20usize;
-const DISABLE_BASIC_IRQS: PhysicalAddress = PERIPHERAL_IC_BASE + 0x24usize;
+register_structs! {
+    DistributorRegisters {
+        (0x000 => ctlr: ReadWrite<u32, GICD_CTLR::Register>),
+        (0x004 => typer: ReadOnly<u32>),
+        (0x008 => iidr: ReadOnly<u32>),
+        (0x00C => _reserved0),
+        (0x080 => igroupr: [ReadWrite<u32>; 8]),
+        (0x0A0 => _reserved0b),
+        (0x100 => isenabler: [ReadWrite<u32>; 32]),
+        (0x180 => icenabler: [ReadWrite<u32>; 32]),
+        (0x200 => _reserved1),
+        (0x400 => ipriorityr: [ReadWrite<u32>; 256]),
+        (0x800 => itargetsr: [ReadWrite<u32>; 256]),
+        (0xC00 => icfgr: [ReadWrite<u32>; 64]),
+        (0xD00 => _reserved2),
+        (0xF00 => sgir: WriteOnly<u32, GICD_SGIR::Register>),
+        (0xF04 => _reserved3),
+        (0x1000 => @END),
+    }
+}
+
+register_structs! {
+    CpuInterfaceRegisters {
+        (0x000 => ctlr: ReadWrite<u32, GICC_CTLR::Register>),
+        (0x004 => pmr: ReadWrite<u32, GICC_PMR::Register>),
+        (0x008 => _reserved0),
+        (0x00C => iar: ReadOnly<u32, GICC_IAR::Register>),
+        (0x010 => eoir: WriteOnly<u32, GICC_EOIR::Register>),
+        (0x014 => _reserved1),
+        (0x1000 => @END),
+    }
+}
+
+register_bitfields![u32,
+    GICD_CTLR [
+        ENABLE OFFSET(0) NUMBITS(1) [],
+    ],
+
+    GICD_SGIR [
+        TARGET_LIST_FILTER OFFSET(24) NUMBITS(2) [],
+        CPU_TARGET_LIST OFFSET(16) NUMBITS(8) [],
+        SGI_INT_ID OFFSET(0) NUMBITS(4) [],
+    ],
+
+    GICC_CTLR [
+        ENABLE OFFSET(0) NUMBITS(1) [],
+    ],
+
+    GICC_PMR [
+        PRIORITY OFFSET(0) NUMBITS(8) [],
+    ],
 
+    GICC_IAR [
+        INTERRUPT_ID OFFSET(0) NUMBITS(10) [],
+    ],
+
+    GICC_EOIR [
+        EOI_INT_ID OFFSET(0) NUMBITS(10) [],
+    ],
+];
+
+/// ID reported by `GICC_IAR`/consumed by `GICC_EOIR`. On GIC-400, IDs 0-15 are SGIs,
+/// 16-31 are PPIs (the per-core timer lands at 30 on rpi3's GIC-400), and 32+ are SPIs.
 pub(crate) type IRQNum = u32;
-const MAX_IRQ_NUM: u32 = 64;
+const MAX_IRQ_NUM: u32 = 256;
+/// `GICC_IAR` returns this sentinel `INTERRUPT_ID` when there is no pending interrupt.
+const SPURIOUS_INTERRUPT_ID: u32 = 1023;
+
+/// Default priority programmed into `GICD_IPRIORITYR` for newly enabled interrupts.
+/// Lower values are higher priority; `0xA0` leaves headroom above it for interrupts
+/// that are explicitly promoted to a higher priority.
+const DEFAULT_PRIORITY: u8 = 0xA0;
 
 /// Core Interrupt Request Handler Trait.
 pub(crate) trait IRQHandler: Send + Sync {
-    /// Return the interrupt request number associated with the handler
+    /// Return the GIC interrupt ID associated with the handler.
     fn get_irq_pending_bit_num(&self) -> IRQNum;
 
     /// Handle the interrput
@@ -42,63 +106,247 @@ impl<'a> IRQHandlerEntry<'a> {
 lazy_static! {
     static ref REGISTERED_IRQ_HANDLERS: Mutex<[IRQHandlerEntry<'static>; MAX_IRQ_NUM as usize]> =
         Mutex::new([IRQHandlerEntry::default(); MAX_IRQ_NUM as usize]);
+    static ref REGISTERED_FIQ_HANDLERS: Mutex<[IRQHandlerEntry<'static>; MAX_IRQ_NUM as usize]> =
+        Mutex::new([IRQHandlerEntry::default(); MAX_IRQ_NUM as usize]);
+}
+
+/// Priority given to interrupts promoted to FIQ (Group 0), so they preempt normal
+/// Group 1 (IRQ) sources even while IRQ remains masked.
+const FIQ_PRIORITY: u8 = 0x10;
+
+struct GicDistributor(&'static mut DistributorRegisters);
+struct GicCpuInterface(&'static mut CpuInterfaceRegisters);
+
+impl GicDistributor {
+    unsafe fn get() -> Self {
+        let virt = map_mmio(GICD.base, GICD.size).expect("GIC distributor region must be mappable");
+        Self(
+            (virt.as_mut_ptr() as *mut DistributorRegisters)
+                .as_mut()
+                .unwrap(),
+        )
+    }
+}
+
+impl GicCpuInterface {
+    unsafe fn get() -> Self {
+        let virt = map_mmio(GICC.base, GICC.size).expect("GIC CPU interface region must be mappable");
+        Self(
+            (virt.as_mut_ptr() as *mut CpuInterfaceRegisters)
+                .as_mut()
+                .unwrap(),
+        )
+    }
 }
 
 /// .
 ///
 /// # Safety
 ///
-/// Initialize BCM2537 Interrupt controller
+/// Initialize the GIC-400 Distributor and this core's CPU interface.
+///
+/// This crate only ever targets boards with a real GICv2 distributor/CPU
+/// interface (`GICD`/`GICC` in `address_map`, present on both the rpi3 target
+/// QEMU's `raspi3b` machine models and on rpi4) -- there is no BCM2837
+/// legacy-controller path left anywhere in `kernel` to select between at
+/// runtime. Per-target variation that previously would have gone through a
+/// board check lives in `libmei::arch::Current` instead (see
+/// `libmei/src/arch/mod.rs`), selected at compile time via the
+/// `arch.aarch64`/`arch.riscv64` Cargo features.
 pub unsafe fn init_gic() -> Result<()> {
-    DISABLE_IRQS_1.write_reg(0xffffffffu32)?;
-    DISABLE_IRQS_2.write_reg(0xffffffffu32)?;
-    DISABLE_BASIC_IRQS.write_reg(0xffffffffu32)?;
+    let gicd = GicDistributor::get();
+    let gicc = GicCpuInterface::get();
+
+    for reg in gicd.0.icenabler.iter() {
+        reg.set(0xffff_ffff);
+    }
+
+    gicc.0.pmr.write(GICC_PMR::PRIORITY.val(0xFF));
+    gicc.0.ctlr.write(GICC_CTLR::ENABLE::SET);
+    gicd.0.ctlr.write(GICD_CTLR::ENABLE::SET);
+
     Ok(())
 }
 
 pub(crate) fn register_interrupt_handler(irq_hand: &'static dyn IRQHandler) {
-    let irq_num = irq_hand.get_irq_pending_bit_num() as usize;
-    REGISTERED_IRQ_HANDLERS.lock()[irq_num] = IRQHandlerEntry::new(irq_hand);
+    let irq_num = irq_hand.get_irq_pending_bit_num();
+    REGISTERED_IRQ_HANDLERS.lock()[irq_num as usize] = IRQHandlerEntry::new(irq_hand);
 }
 
-fn is_timer_irq() -> Result<bool> {
-    Ok(unsafe {
-        CNTP_STATUS_EL0.read_reg::<u64>()? & (1 << 1) != 0
-            && CNTP_CTL_EL0.is_set(CNTP_CTL_EL0::ISTATUS)
-    })
+/// Registers `irq_hand` on the FIQ (Group 0) dispatch path. Callers must still
+/// promote the interrupt with `promote_to_fiq` so the distributor actually routes it
+/// as Group 0 rather than the default Group 1 (IRQ).
+pub(crate) fn register_fiq_handler(irq_hand: &'static dyn IRQHandler) {
+    let irq_num = irq_hand.get_irq_pending_bit_num();
+    REGISTERED_FIQ_HANDLERS.lock()[irq_num as usize] = IRQHandlerEntry::new(irq_hand);
 }
 
-pub(crate) fn dispatch_peripheral_irq(ec: &mut ExceptionContext) -> Result<bool> {
-    let irq_pending = unsafe { IRQ_BASIC_PENDING.read_reg::<u32>()? };
-    let mut handled = false;
+/// Programs `GICD_IGROUPR` to mark `irq_num` as Group 0 (signalled as FIQ instead of
+/// IRQ) and raises its priority above ordinary Group 1 interrupts.
+///
+/// # Safety
+///
+/// Must only be called after the GIC distributor has been initialized, and before
+/// `irq_num` is enabled.
+pub(crate) unsafe fn promote_to_fiq(irq_num: IRQNum) {
+    let mut gicd = GicDistributor::get();
+    let reg = &mut gicd.0.igroupr[(irq_num / 32) as usize];
+    reg.set(reg.get() & !(1u32 << (irq_num % 32)));
 
-    for i in 0..31 {
-        if (irq_pending & (1u32 << i)) != 0 {
-            if let Some(handler) = REGISTERED_IRQ_HANDLERS.lock()[i].0 {
-                handler.handle(ec);
-                handled = true;
-            }
-        }
-    }
+    set_irq_priority(irq_num, FIQ_PRIORITY);
+}
 
-    if is_timer_irq()? {
-        REGISTERED_IRQ_HANDLERS.lock()[0]
-            .0
-            .as_ref()
-            .unwrap()
-            .handle(ec);
-        handled = true
+/// Programs `GICD_IPRIORITYR`/`GICD_ITARGETSR` and enables `irq_num` at the
+/// distributor, keying the handler table by GIC interrupt ID rather than a
+/// legacy pending-bit position.
+pub(crate) unsafe fn enable_irq(irq_num: IRQNum) -> Result<()> {
+    set_irq_priority(irq_num, DEFAULT_PRIORITY);
+
+    // Route newly enabled SPIs to this core (core 0) by default.
+    if irq_num >= 32 {
+        set_irq_target_list(irq_num, 1 << 0);
     }
-    Ok(handled)
+
+    let mut gicd = GicDistributor::get();
+    let reg = &mut gicd.0.isenabler[(irq_num / 32) as usize];
+    reg.set(reg.get() | (1u32 << (irq_num % 32)));
+    Ok(())
 }
 
-pub(crate) unsafe fn enable_irq(irq_num: IRQNum) -> Result<()> {
-    if irq_num < 8 {
-        return ENABLE_BASIC_IRQS.write_reg(1u32 << irq_num);
-    } else if irq_num < 32 {
-        return ENABLE_IRQS_1.write_reg(1u32 << irq_num);
+/// Programs `GICD_ITARGETSR` for SPI `irq_num` with `core_mask` (core N delivered to
+/// iff bit N is set). Only meaningful for SPIs (`irq_num >= 32`); PPIs/SGIs are
+/// banked per-core and always target the requesting core.
+///
+/// # Safety
+///
+/// Must only be called after the GIC distributor has been initialized.
+pub(crate) unsafe fn set_irq_target_list(irq_num: IRQNum, core_mask: u8) {
+    let mut gicd = GicDistributor::get();
+    let byte = irq_num as usize % 4;
+    let targetsr = &mut gicd.0.itargetsr[irq_num as usize / 4];
+    let mask = !(0xffu32 << (byte * 8));
+    targetsr.set((targetsr.get() & mask) | ((core_mask as u32) << (byte * 8)));
+}
+
+/// Edge- vs level-triggered configuration for an SPI/PPI, as programmed into
+/// `GICD_ICFGR` (2 bits per interrupt; the low bit is reserved on GICv2, the
+/// high bit selects edge- over level-sensitive).
+#[derive(Clone, Copy)]
+pub(crate) enum TriggerMode {
+    /// Stays pending until the peripheral deasserts its line. The default for
+    /// every interrupt this crate currently registers (PL011 UART, timer).
+    LevelSensitive,
+    /// Latched on the rising edge and cleared once acknowledged, independent
+    /// of the line's level afterwards -- needed for GPIO-style sources.
+    EdgeTriggered,
+}
+
+/// Programs `GICD_ICFGR` for `irq_num`. Must run before the interrupt is
+/// enabled, and only affects SPIs/PPIs -- SGIs (`irq_num < 16`) are always
+/// edge-triggered per the GICv2 spec and ignore this field.
+///
+/// # Safety
+///
+/// Must only be called after the GIC distributor has been initialized.
+pub(crate) unsafe fn set_irq_trigger_mode(irq_num: IRQNum, mode: TriggerMode) {
+    let mut gicd = GicDistributor::get();
+    let reg = &mut gicd.0.icfgr[(irq_num / 16) as usize];
+    let shift = (irq_num % 16) * 2;
+    let edge_bit = match mode {
+        TriggerMode::LevelSensitive => 0,
+        TriggerMode::EdgeTriggered => 1,
+    };
+
+    let mask = !(0b11u32 << shift);
+    reg.set((reg.get() & mask) | (edge_bit << (shift + 1)));
+}
+
+/// Sets the 8-bit priority (lower value = higher priority) of `irq_num` in
+/// `GICD_IPRIORITYR`.
+pub(crate) unsafe fn set_irq_priority(irq_num: IRQNum, priority: u8) {
+    let mut gicd = GicDistributor::get();
+    let byte = irq_num as usize % 4;
+    let ipriorityr = &mut gicd.0.ipriorityr[irq_num as usize / 4];
+    let mask = !(0xffu32 << (byte * 8));
+    ipriorityr.set((ipriorityr.get() & mask) | ((priority as u32) << (byte * 8)));
+}
+
+/// Selects which cores a software-generated interrupt is delivered to, mirroring the
+/// `GICD_SGIR` target-list filter (bits [25:24]).
+pub(crate) enum SgiTarget {
+    /// Deliver to the cores named in the bitmask (bit N == core N), via
+    /// `GICD_SGIR::CPU_TARGET_LIST`.
+    SpecificCores(u8),
+    /// Deliver to every core in the system except the sender.
+    AllOther,
+    /// Deliver back to the sending core only.
+    Self_,
+}
+
+/// Sends a software-generated interrupt (SGI ID 0..=15) to the given target cores by
+/// writing `GICD_SGIR`. Acknowledged SGIs are routed through the same
+/// `REGISTERED_IRQ_HANDLERS` table as PPIs/SPIs, so a handler for `sgi_id` must have
+/// been installed with `register_interrupt_handler` beforehand.
+///
+/// # Safety
+///
+/// Must only be called after `init_gic` has run on this core.
+pub(crate) unsafe fn send_sgi(sgi_id: u8, target: SgiTarget) {
+    assert!(sgi_id < 16, "SGI ID must be in 0..=15, got {sgi_id}");
+
+    let mut gicd = GicDistributor::get();
+    let (filter, cpu_target_list) = match target {
+        SgiTarget::SpecificCores(mask) => (0b00u32, mask as u32),
+        SgiTarget::AllOther => (0b01u32, 0u32),
+        SgiTarget::Self_ => (0b10u32, 0u32),
+    };
+
+    gicd.0.sgir.write(
+        GICD_SGIR::TARGET_LIST_FILTER.val(filter)
+            + GICD_SGIR::CPU_TARGET_LIST.val(cpu_target_list)
+            + GICD_SGIR::SGI_INT_ID.val(sgi_id as u32),
+    );
+}
+
+/// Acknowledges the highest priority pending interrupt via `GICC_IAR`, dispatches it
+/// to its registered handler, and signals end-of-interrupt via `GICC_EOIR`.
+pub(crate) fn dispatch_peripheral_irq(ec: &mut ExceptionContext) -> bool {
+    let gicc = unsafe { GicCpuInterface::get() };
+    let irq_id = gicc.0.iar.read(GICC_IAR::INTERRUPT_ID);
+
+    if irq_id == SPURIOUS_INTERRUPT_ID {
+        return false;
+    }
+
+    let handled = if let Some(handler) = REGISTERED_IRQ_HANDLERS.lock()[irq_id as usize].0 {
+        handler.handle(ec);
+        true
     } else {
-        let irq_num = irq_num - 32;
-        return ENABLE_IRQS_2.write_reg(1u32 << irq_num);
+        false
+    };
+
+    gicc.0.eoir.write(GICC_EOIR::EOI_INT_ID.val(irq_id));
+    handled
+}
+
+/// Equivalent of `dispatch_peripheral_irq` for the FIQ path: acknowledges via
+/// `GICC_IAR`, dispatches to a handler registered with `register_fiq_handler`, and
+/// signals end-of-interrupt.
+pub(crate) fn dispatch_fiq(ec: &mut ExceptionContext) -> bool {
+    let gicc = unsafe { GicCpuInterface::get() };
+    let irq_id = gicc.0.iar.read(GICC_IAR::INTERRUPT_ID);
+
+    if irq_id == SPURIOUS_INTERRUPT_ID {
+        return false;
     }
+
+    let handled = if let Some(handler) = REGISTERED_FIQ_HANDLERS.lock()[irq_id as usize].0 {
+        handler.handle(ec);
+        true
+    } else {
+        false
+    };
+
+    gicc.0.eoir.write(GICC_EOIR::EOI_INT_ID.val(irq_id));
+    handled
 }
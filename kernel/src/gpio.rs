@@ -1,5 +1,17 @@
-pub(crate) const GPIO_BASE_ADDR: u64 = 0x3f200000;
-pub(crate) const UART_BASE_ADDR: u64 = GPIO_BASE_ADDR + 0x1000;
+//! rpi3 (BCM2837) GPIO controller, plus a bit-banged I2C master layered on top of
+//! two GPIO pins.
+
+use tock_registers::interfaces::Writeable;
+use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
+use tock_registers::{register_bitfields, register_structs};
+
+use crate::address::Address;
+use crate::address_map::GPIO_BASE;
+use crate::timer::busy_wait;
+use crate::vm::phy2virt;
+use core::time::Duration;
+
+pub(crate) const UART_BASE_ADDR: u64 = 0x3f201000;
 
 /// .
 ///
@@ -18,3 +30,339 @@ pub(crate) unsafe fn write_mmio_reg<T: Sized + Copy>(addr: u64, val: T) {
 pub(crate) unsafe fn read_mmio_reg<T: Sized + Copy>(addr: u64) -> T {
     core::ptr::read_volatile(addr as *mut T)
 }
+
+register_structs! {
+    GpioRegisters {
+        (0x00 => gpfsel: [ReadWrite<u32, FunctionSelect::Register>; 6]),
+        (0x18 => _reserved0),
+        (0x1C => gpset: [WriteOnly<u32>; 2]),
+        (0x24 => _reserved1),
+        (0x28 => gpclr: [WriteOnly<u32>; 2]),
+        (0x30 => _reserved2),
+        (0x34 => gplev: [ReadOnly<u32>; 2]),
+        (0x3C => _reserved3),
+        (0x94 => gppud: ReadWrite<u32, PullUpDown::Register>),
+        (0x98 => gppudclk: [ReadWrite<u32>; 2]),
+        (0xA0 => @END),
+    }
+}
+
+register_bitfields! [u32,
+    FunctionSelect [
+        FSEL0 OFFSET(0) NUMBITS(3) [
+            Input = 0b000,
+            Output = 0b001,
+            Alt0 = 0b100,
+            Alt1 = 0b101,
+            Alt2 = 0b110,
+            Alt3 = 0b111,
+            Alt4 = 0b011,
+            Alt5 = 0b010,
+        ],
+    ],
+    PullUpDown [
+        PUD OFFSET(0) NUMBITS(2) [
+            Off = 0b00,
+            PullDown = 0b01,
+            PullUp = 0b10,
+        ],
+    ],
+];
+
+/// GPIO pin function, mirroring the `GPFSELn.FSELx` encoding.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Function {
+    Input,
+    Output,
+    Alt0,
+    Alt1,
+    Alt2,
+    Alt3,
+    Alt4,
+    Alt5,
+}
+
+/// Pull resistor state for an input pin, mirroring `GPPUD`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pull {
+    Off,
+    Down,
+    Up,
+}
+
+struct Gpio(&'static mut GpioRegisters);
+
+impl Default for Gpio {
+    fn default() -> Self {
+        let virt = phy2virt(GPIO_BASE).expect("GPIO region must be mapped");
+
+        unsafe { Self((virt.as_mut_ptr() as *mut GpioRegisters).as_mut().unwrap()) }
+    }
+}
+
+impl Gpio {
+    fn set_function(&mut self, pin: u32, function: Function) {
+        use FunctionSelect::FSEL0::Value::*;
+
+        let value = match function {
+            Function::Input => Input,
+            Function::Output => Output,
+            Function::Alt0 => Alt0,
+            Function::Alt1 => Alt1,
+            Function::Alt2 => Alt2,
+            Function::Alt3 => Alt3,
+            Function::Alt4 => Alt4,
+            Function::Alt5 => Alt5,
+        };
+
+        let bank = (pin / 10) as usize;
+        let shift = (pin % 10) * 3;
+        let reg = &mut self.0.gpfsel[bank];
+        reg.set((reg.get() & !(0b111 << shift)) | ((value as u32) << shift));
+    }
+
+    fn set_high(&mut self, pin: u32) {
+        self.0.gpset[(pin / 32) as usize].set(1 << (pin % 32));
+    }
+
+    fn set_low(&mut self, pin: u32) {
+        self.0.gpclr[(pin / 32) as usize].set(1 << (pin % 32));
+    }
+
+    fn read_level(&self, pin: u32) -> bool {
+        self.0.gplev[(pin / 32) as usize].get() & (1 << (pin % 32)) != 0
+    }
+
+    /// Configures the pull-up/pull-down resistor for `pin`, following the BCM2837's
+    /// two-step `GPPUD`/`GPPUDCLK` handshake: stage the desired state in `GPPUD`,
+    /// clock it into the target pin via `GPPUDCLKn`, then clear both.
+    fn set_pull(&mut self, pin: u32, pull: Pull) {
+        use PullUpDown::PUD::Value::*;
+
+        let value = match pull {
+            Pull::Off => Off,
+            Pull::Down => PullDown,
+            Pull::Up => PullUp,
+        };
+
+        self.0.gppud.write(PullUpDown::PUD.val(value as u32));
+        busy_wait(Duration::from_micros(1));
+
+        self.0.gppudclk[(pin / 32) as usize].set(1 << (pin % 32));
+        busy_wait(Duration::from_micros(1));
+
+        self.0.gppud.write(PullUpDown::PUD.val(Off as u32));
+        self.0.gppudclk[(pin / 32) as usize].set(0);
+    }
+}
+
+pub(crate) mod i2c {
+    //! Bit-banged I2C master over two open-drain GPIO pins.
+    //!
+    //! SCL/SDA are never driven high: "releasing" a line switches it to an input so
+    //! the external pull-up (configured via `Pull::Up`) brings it high, matching how
+    //! real open-drain I2C buses behave and letting a slave stretch the clock by
+    //! holding SCL low after it's released.
+
+    use super::{Function, Gpio, Pull};
+    use crate::timer::busy_wait;
+    use core::time::Duration;
+
+    /// Half-period of the bit-banged clock; ~100kHz (standard mode) overall.
+    const HALF_PERIOD: Duration = Duration::from_micros(5);
+
+    /// Number of half-periods to wait for a stretching slave to release SCL before
+    /// giving up.
+    const CLOCK_STRETCH_TIMEOUT_ITERS: u32 = 10_000;
+
+    pub(crate) struct I2cMaster {
+        gpio: Gpio,
+        scl: u32,
+        sda: u32,
+    }
+
+    impl I2cMaster {
+        pub(crate) fn new(scl: u32, sda: u32) -> Self {
+            let mut gpio = Gpio::default();
+            for pin in [scl, sda] {
+                gpio.set_pull(pin, Pull::Up);
+                gpio.set_function(pin, Function::Input);
+            }
+            Self { gpio, scl, sda }
+        }
+
+        fn release_scl(&mut self) {
+            self.gpio.set_function(self.scl, Function::Input);
+        }
+
+        fn drive_scl_low(&mut self) {
+            self.gpio.set_low(self.scl);
+            self.gpio.set_function(self.scl, Function::Output);
+        }
+
+        fn release_sda(&mut self) {
+            self.gpio.set_function(self.sda, Function::Input);
+        }
+
+        fn drive_sda_low(&mut self) {
+            self.gpio.set_low(self.sda);
+            self.gpio.set_function(self.sda, Function::Output);
+        }
+
+        fn read_sda(&self) -> bool {
+            self.gpio.read_level(self.sda)
+        }
+
+        /// Releases SCL and busy-waits for it to actually read high, giving a slave
+        /// doing clock stretching time to finish before the next bit is clocked.
+        fn clock_high(&mut self) {
+            self.release_scl();
+            busy_wait(HALF_PERIOD);
+
+            let mut iters = 0;
+            while !self.gpio.read_level(self.scl) && iters < CLOCK_STRETCH_TIMEOUT_ITERS {
+                busy_wait(HALF_PERIOD);
+                iters += 1;
+            }
+        }
+
+        fn clock_low(&mut self) {
+            self.drive_scl_low();
+            busy_wait(HALF_PERIOD);
+        }
+
+        /// SDA transitions high-to-low while SCL is high.
+        fn start(&mut self) {
+            self.release_sda();
+            self.clock_high();
+            self.drive_sda_low();
+            busy_wait(HALF_PERIOD);
+            self.drive_scl_low();
+        }
+
+        /// A repeated start is just a start issued without a preceding stop.
+        fn repeated_start(&mut self) {
+            self.release_scl();
+            self.release_sda();
+            busy_wait(HALF_PERIOD);
+            self.start();
+        }
+
+        /// SDA transitions low-to-high while SCL is high.
+        fn stop(&mut self) {
+            self.drive_sda_low();
+            self.clock_high();
+            self.release_sda();
+            busy_wait(HALF_PERIOD);
+        }
+
+        fn write_bit(&mut self, bit: bool) {
+            if bit {
+                self.release_sda();
+            } else {
+                self.drive_sda_low();
+            }
+            self.clock_high();
+            self.clock_low();
+        }
+
+        fn read_bit(&mut self) -> bool {
+            self.release_sda();
+            self.clock_high();
+            let bit = self.read_sda();
+            self.clock_low();
+            bit
+        }
+
+        /// Writes `byte` MSB-first and returns whether the slave ACKed (pulled SDA
+        /// low during the 9th clock).
+        fn write_byte(&mut self, byte: u8) -> bool {
+            for i in (0..8).rev() {
+                self.write_bit((byte >> i) & 1 != 0);
+            }
+            !self.read_bit()
+        }
+
+        /// Reads a byte MSB-first, then drives the ACK/NACK bit for the 9th clock.
+        fn read_byte(&mut self, ack: bool) -> u8 {
+            let mut byte = 0u8;
+            for _ in 0..8 {
+                byte = (byte << 1) | self.read_bit() as u8;
+            }
+            self.write_bit(!ack);
+            byte
+        }
+
+        /// Writes `data` to 7-bit address `addr`. Returns `false` if the slave NACKed
+        /// the address or any data byte.
+        pub(crate) fn write(&mut self, addr: u8, data: &[u8]) -> bool {
+            self.start();
+            if !self.write_byte((addr << 1) | 0) {
+                self.stop();
+                return false;
+            }
+            for &byte in data {
+                if !self.write_byte(byte) {
+                    self.stop();
+                    return false;
+                }
+            }
+            self.stop();
+            true
+        }
+
+        /// Reads `buf.len()` bytes from 7-bit address `addr`, ACKing every byte but
+        /// the last. Returns `false` if the slave NACKed the address.
+        pub(crate) fn read(&mut self, addr: u8, buf: &mut [u8]) -> bool {
+            self.start();
+            if !self.write_byte((addr << 1) | 1) {
+                self.stop();
+                return false;
+            }
+            for (i, byte) in buf.iter_mut().enumerate() {
+                *byte = self.read_byte(i + 1 < buf.len());
+            }
+            self.stop();
+            true
+        }
+
+        /// Sequential-read from a byte-addressed I2C EEPROM: writes the single-byte
+        /// memory address, issues a repeated start, then reads `buf.len()` bytes.
+        pub(crate) fn eeprom_read(&mut self, addr: u8, mem_addr: u8, buf: &mut [u8]) -> bool {
+            self.start();
+            if !self.write_byte((addr << 1) | 0) || !self.write_byte(mem_addr) {
+                self.stop();
+                return false;
+            }
+
+            self.repeated_start();
+            if !self.write_byte((addr << 1) | 1) {
+                self.stop();
+                return false;
+            }
+            for (i, byte) in buf.iter_mut().enumerate() {
+                *byte = self.read_byte(i + 1 < buf.len());
+            }
+            self.stop();
+            true
+        }
+
+        /// Byte-addressed I2C EEPROM write: memory address byte followed by
+        /// sequential data, all in a single write transaction.
+        pub(crate) fn eeprom_write(&mut self, addr: u8, mem_addr: u8, data: &[u8]) -> bool {
+            self.start();
+            if !self.write_byte((addr << 1) | 0) || !self.write_byte(mem_addr) {
+                self.stop();
+                return false;
+            }
+            for &byte in data {
+                if !self.write_byte(byte) {
+                    self.stop();
+                    return false;
+                }
+            }
+            self.stop();
+            true
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Binary buddy allocator backing `#[global_allocator]`.
+//!
+//! Blocks are tracked in power-of-two runs, the same scheme `crate::phys_alloc`
+//! uses for physical frames: free list `k` holds aligned `MIN_BLOCK * 2^k`-sized
+//! blocks. Allocating rounds a request's size (and alignment) up to the smallest
+//! block order that fits, splits a larger free block down to that order (handing
+//! the unused buddy halves back to the lower-order lists), and freeing merges a
+//! block with its buddy (`index XOR block_size`) whenever that buddy is free at
+//! the same order, propagating the merge upward. This gives the kernel a real,
+//! reclaiming heap behind `alloc::boxed::Box`/`Vec`/etc., instead of one that
+//! could only ever grow.
+
+use core::alloc::{GlobalAlloc, Layout};
+
+use spin::Mutex;
+
+/// Total size of the heap region, a power of two so every order up to
+/// `MAX_ORDER` evenly divides it.
+const HEAP_REGION_LEN: usize = 1024 * 1024;
+
+/// Smallest block the allocator will ever hand out or free -- large enough to
+/// always hold a free-list link written into its own first bytes.
+const MIN_BLOCK: usize = 16;
+
+/// Number of `MIN_BLOCK`-sized slots the region holds.
+const NUM_BLOCKS: usize = HEAP_REGION_LEN / MIN_BLOCK;
+
+/// `NUM_BLOCKS` is a power of two, so this is its log2: the largest order a
+/// single free list can hold (one block spanning the whole region).
+const MAX_ORDER: usize = NUM_BLOCKS.trailing_zeros() as usize;
+
+/// Sentinel meaning "no block", used instead of `Option<usize>` so the
+/// free-list link can be written directly into the block's own backing bytes.
+const NONE_BLOCK: usize = usize::MAX;
+
+struct FreeLists {
+    /// `heads[order]` is the block index at the head of that order's free
+    /// list, or `NONE_BLOCK` if empty.
+    heads: [usize; MAX_ORDER + 1],
+    initialized: bool,
+}
+
+/// `region`'s backing array is naturally only byte-aligned, but every
+/// order's blocks land at `region_base() + k * MIN_BLOCK` -- an offset that's
+/// a multiple of the block's own size by the buddy-index invariant, but only an
+/// aligned *address* if `region_base()` itself is. Forcing 16-byte alignment
+/// here, the same way `smp::SecondaryStack` does for its boot stacks, is what
+/// lets callers like `sched::spawn` request a 16-byte-aligned task stack (the
+/// AAPCS64 `sp` requirement) straight out of this allocator.
+#[repr(align(16))]
+struct AlignedRegion([u8; HEAP_REGION_LEN]);
+
+pub struct HeapAllocator {
+    region: AlignedRegion,
+    free_lists: Mutex<FreeLists>,
+}
+
+impl HeapAllocator {
+    const fn new() -> Self {
+        Self {
+            region: AlignedRegion([0; HEAP_REGION_LEN]),
+            free_lists: Mutex::new(FreeLists {
+                heads: [NONE_BLOCK; MAX_ORDER + 1],
+                initialized: false,
+            }),
+        }
+    }
+
+    fn region_base(&self) -> usize {
+        self.region.0.as_ptr() as usize
+    }
+
+    fn block_to_ptr(&self, block: usize) -> *mut u8 {
+        (self.region_base() + block * MIN_BLOCK) as *mut u8
+    }
+
+    fn ptr_to_block(&self, ptr: *mut u8) -> usize {
+        (ptr as usize - self.region_base()) / MIN_BLOCK
+    }
+
+    /// Reads the free-list link stored in a free block's first bytes.
+    fn read_link(&self, block: usize) -> usize {
+        let ptr = self.block_to_ptr(block) as *const usize;
+        unsafe { ptr.read() }
+    }
+
+    fn write_link(&self, block: usize, next: usize) {
+        let ptr = self.block_to_ptr(block) as *mut usize;
+        unsafe { ptr.write(next) };
+    }
+
+    /// The first lock holder lazily seeds `heads[MAX_ORDER]` with one block
+    /// spanning the whole region, since a `HeapAllocator` has to be
+    /// `const`-constructible to be used as a `#[global_allocator]`.
+    fn ensure_init(&self, lists: &mut FreeLists) {
+        if lists.initialized {
+            return;
+        }
+
+        self.write_link(0, NONE_BLOCK);
+        lists.heads[MAX_ORDER] = 0;
+        lists.initialized = true;
+    }
+
+    fn push_free(&self, lists: &mut FreeLists, order: usize, block: usize) {
+        self.write_link(block, lists.heads[order]);
+        lists.heads[order] = block;
+    }
+
+    fn pop_free(&self, lists: &mut FreeLists, order: usize) -> Option<usize> {
+        let block = lists.heads[order];
+        if block == NONE_BLOCK {
+            return None;
+        }
+
+        lists.heads[order] = self.read_link(block);
+        Some(block)
+    }
+
+    /// Removes `block` from order `order`'s free list if it's present there,
+    /// reporting whether it was found.
+    fn remove_free(&self, lists: &mut FreeLists, order: usize, block: usize) -> bool {
+        let mut curr = lists.heads[order];
+        let mut prev = None;
+
+        while curr != NONE_BLOCK {
+            let next = self.read_link(curr);
+
+            if curr == block {
+                match prev {
+                    Some(p) => self.write_link(p, next),
+                    None => lists.heads[order] = next,
+                }
+                return true;
+            }
+
+            prev = Some(curr);
+            curr = next;
+        }
+
+        false
+    }
+
+    fn alloc_buddy(&self, layout: Layout) -> *mut u8 {
+        let order = order_for(layout);
+        if order > MAX_ORDER {
+            return core::ptr::null_mut();
+        }
+
+        let mut lists = self.free_lists.lock();
+        self.ensure_init(&mut lists);
+
+        let Some(source_order) = (order..=MAX_ORDER).find(|&o| lists.heads[o] != NONE_BLOCK)
+        else {
+            return core::ptr::null_mut();
+        };
+
+        let block = self.pop_free(&mut lists, source_order).unwrap();
+
+        for split_order in (order..source_order).rev() {
+            let buddy = block ^ (1 << split_order);
+            self.push_free(&mut lists, split_order, buddy);
+        }
+
+        self.block_to_ptr(block)
+    }
+
+    fn dealloc_buddy(&self, ptr: *mut u8, layout: Layout) {
+        let mut order = order_for(layout);
+        let mut block = self.ptr_to_block(ptr);
+
+        let mut lists = self.free_lists.lock();
+
+        while order < MAX_ORDER {
+            let buddy = block ^ (1 << order);
+
+            if !self.remove_free(&mut lists, order, buddy) {
+                break;
+            }
+
+            block = block.min(buddy);
+            order += 1;
+        }
+
+        self.push_free(&mut lists, order, block);
+    }
+}
+
+impl Default for HeapAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for HeapAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.alloc_buddy(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.dealloc_buddy(ptr, layout)
+    }
+}
+
+/// Smallest order `k` such that a `MIN_BLOCK * 2^k`-sized, -aligned block
+/// satisfies `layout`.
+fn order_for(layout: Layout) -> usize {
+    let required = layout.size().max(layout.align()).max(MIN_BLOCK);
+    let blocks = required.next_power_of_two() / MIN_BLOCK;
+
+    blocks.trailing_zeros() as usize
+}
+
+#[global_allocator]
+static HEAP_ALLOCATOR: HeapAllocator = HeapAllocator::new();
@@ -1,4 +1,10 @@
-use core::cell::UnsafeCell;
+use core::{cell::UnsafeCell, ops::Range};
+
+use crate::{
+    address::{Address, PhysicalAddress},
+    elf,
+    vm::{MemoryMap, Permissions},
+};
 
 #[allow(improper_ctypes)]
 extern "C" {
@@ -6,8 +12,20 @@ extern "C" {
     static __kernel_start_marker: UnsafeCell<()>;
     static __kernel_end_marker: UnsafeCell<()>;
     static __kernel_stack_start_marker: UnsafeCell<()>;
+    /// Provided by Linker: the kernel's own ELF64 header and program header
+    /// table, kept around in the linked image so the kernel can read its segment
+    /// layout back out of itself (see `kernel_segments`).
+    static __kernel_elf_header_start: UnsafeCell<()>;
 }
 
+/// Largest number of `PT_LOAD` segments `kernel_segments` will report (text,
+/// rodata, data, bss is comfortably under this).
+const MAX_SEGMENTS: usize = 8;
+
+/// Stack reservations aren't `PT_LOAD` segments, so unlike `kernel_phy_range` this
+/// still comes from the linker-provided marker, not the ELF header.
+const KERNEL_STACK_SIZE: usize = 16 * 1024;
+
 pub fn kernel_image_size() -> usize {
     let kstart = unsafe { __kernel_start_marker.get() as usize };
     let kend = unsafe { __kernel_end_marker.get() as usize };
@@ -18,3 +36,49 @@ pub fn kernel_image_size() -> usize {
 pub fn kernel_stack_base() -> usize {
     unsafe { __kernel_stack_start_marker.get() as usize }
 }
+
+/// Discovers the kernel's loadable ELF segments from the program header table
+/// the linker places at `__kernel_elf_header_start`, deriving a `MemoryMap` per
+/// segment with permissions taken directly from its R/W/X flags (RO+PX for
+/// `.text`, RW+PXN for data, and so on). This is the source of truth
+/// `kernel_phy_range` is built from, instead of a hand-maintained constant that
+/// can silently drift from the actual linked image.
+pub fn kernel_segments() -> impl Iterator<Item = MemoryMap> {
+    let mut segments: [Option<elf::Segment>; MAX_SEGMENTS] = [None; MAX_SEGMENTS];
+    let elf_base = unsafe { __kernel_elf_header_start.get() as *const u8 };
+    let count = unsafe { elf::load_segments(elf_base, &mut segments) };
+
+    segments.into_iter().take(count).flatten().map(|seg| MemoryMap {
+        phys: PhysicalAddress::new(seg.phys_addr),
+        size: seg.mem_size,
+        perms: Permissions::from_elf_flags(seg.flags),
+    })
+}
+
+/// Physical range spanning every loadable segment of the kernel image, derived
+/// from the ELF program headers rather than a hand-maintained constant.
+pub fn kernel_phy_range() -> Range<PhysicalAddress> {
+    let mut start = usize::MAX;
+    let mut end = 0;
+
+    for seg in kernel_segments() {
+        let seg_start = seg.phys.as_raw_ptr();
+        let seg_end = seg_start + seg.size;
+        start = start.min(seg_start);
+        end = end.max(seg_end);
+    }
+
+    if start > end {
+        // No (or no valid) ELF header was found at `__kernel_elf_header_start` --
+        // fall back to the linker-provided image bounds.
+        start = unsafe { __kernel_start_marker.get() as usize };
+        end = unsafe { __kernel_end_marker.get() as usize };
+    }
+
+    PhysicalAddress::new(start)..PhysicalAddress::new(end)
+}
+
+pub fn kernel_stack_range() -> Range<PhysicalAddress> {
+    let start = kernel_stack_base();
+    PhysicalAddress::new(start)..PhysicalAddress::new(start + KERNEL_STACK_SIZE)
+}
@@ -0,0 +1,150 @@
+//! A [`log::Log`] backend over [`uart`], so the kernel and drivers can use
+//! `log::{info, warn, error, ...}` instead of calling `println!` directly.
+//!
+//! Every record is serialized behind [`LOCK`] (on top of whatever serialization
+//! `uart::_print` already does for individual writes) so two cores logging at
+//! once can't interleave one record's bytes with another's, then written as a
+//! single line prefixed with the current `timer` tick and level, e.g.:
+//!
+//! ```text
+//! [      412][INFO ] vm: mapped 0x40000000..0x40001000
+//! ```
+//!
+//! Filtering happens in two layers: a global [`LevelFilter`] ([`set_max_level`])
+//! and an optional per-module override ([`set_module_level`]) for silencing a
+//! specific subsystem (e.g. `gic`, `vm`) without dropping the global floor for
+//! everything else. [`init`] registers the logger with the `log` facade; call
+//! it once, early in boot, before any `info!`/`warn!`/`error!` call.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spin::Mutex;
+
+use crate::{
+    error::{Error, Result},
+    println, timer, uart,
+};
+
+/// Upper bound on concurrently-overridden module filters. There's no dynamic
+/// subsystem registration to size this against, so a handful of slots -- one
+/// per subsystem anyone's actually silenced -- is plenty; `set_module_level`
+/// returns `Error::ModuleFilterTableFull` rather than growing it.
+const MAX_MODULE_FILTERS: usize = 8;
+
+struct Filters {
+    global: LevelFilter,
+    modules: [Option<(&'static str, LevelFilter)>; MAX_MODULE_FILTERS],
+}
+
+static FILTERS: Mutex<Filters> = Mutex::new(Filters {
+    global: LevelFilter::Info,
+    modules: [None; MAX_MODULE_FILTERS],
+});
+
+/// Serializes one log record's tick-prefix-through-newline write against every
+/// other core's, so concurrent log calls don't interleave mid-line. Separate
+/// from whatever locking `uart`'s own TX ring does internally, which only
+/// guarantees individual `enqueue` calls don't tear -- not that two enqueues
+/// from two cores stay in record order relative to each other.
+static LOCK: Mutex<()> = Mutex::new(());
+
+struct KernelLogger;
+
+static LOGGER: KernelLogger = KernelLogger;
+
+impl KernelLogger {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        let filters = FILTERS.lock();
+
+        let best = filters
+            .modules
+            .iter()
+            .flatten()
+            .filter(|(module, _)| target.starts_with(module))
+            .max_by_key(|(module, _)| module.len());
+
+        best.map_or(filters.global, |(_, level)| *level)
+    }
+}
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let _guard = LOCK.lock();
+        println!(
+            "[{:>9}][{:<5}] {}",
+            timer::ticks(),
+            record.level(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        uart::flush();
+    }
+}
+
+/// Registers [`KernelLogger`] with the `log` facade. Must run once, before the
+/// first `info!`/`warn!`/`error!` call -- `log`'s own facade silently drops
+/// every record logged before a logger is installed.
+///
+/// # Panics
+///
+/// Panics if a logger is already registered; `log::set_logger` only ever
+/// succeeds once per process, and this kernel never needs to swap loggers.
+pub fn init() {
+    log::set_logger(&LOGGER).expect("klog::init must only be called once");
+    log::set_max_level(LevelFilter::Trace);
+}
+
+/// Sets the floor every target falls back to when it has no
+/// [`set_module_level`] override of its own.
+pub fn set_max_level(level: LevelFilter) {
+    FILTERS.lock().global = level;
+}
+
+/// Overrides the level filter for every target whose module path starts with
+/// `module` (e.g. `"kernel::gic"`), independent of [`set_max_level`]'s global
+/// floor. Silence a noisy subsystem with `LevelFilter::Warn` (or `Off`)
+/// without raising or lowering anything else. Re-calling with the same
+/// `module` replaces its existing entry rather than consuming a new slot.
+pub fn set_module_level(module: &'static str, level: LevelFilter) -> Result<()> {
+    let mut filters = FILTERS.lock();
+
+    if let Some(slot) = filters
+        .modules
+        .iter_mut()
+        .find(|slot| matches!(slot, Some((existing, _)) if *existing == module))
+    {
+        *slot = Some((module, level));
+        return Ok(());
+    }
+
+    let free = filters.modules.iter_mut().find(|slot| slot.is_none());
+    match free {
+        Some(slot) => {
+            *slot = Some((module, level));
+            Ok(())
+        }
+        None => Err(Error::ModuleFilterTableFull),
+    }
+}
+
+/// Writes one line in the same `[tick][LEVEL] message` shape [`KernelLogger`]
+/// uses, but straight over `uart::print_blocking` instead of through the `log`
+/// facade. `panic` calls this instead of `log::error!` so its diagnostics read
+/// like every other log line, while keeping `print_blocking`'s guarantee that
+/// the write survives a wedged TX ring or IRQ -- a guarantee `KernelLogger::log`
+/// can't give, since it goes through the same ring-buffered `println!` every
+/// other caller does. Ignores both filters: a panic is always worth printing.
+pub fn panic_line(level: Level, args: core::fmt::Arguments) {
+    uart::print_blocking(format_args!("[{:>9}][{:<5}] ", timer::ticks(), level));
+    uart::print_blocking(args);
+    uart::print_blocking(format_args!("\n"));
+}
@@ -5,17 +5,27 @@
 #![feature(const_trait_impl)]
 #[macro_use]
 extern crate lazy_static;
+extern crate alloc;
 
 pub mod address;
 pub mod address_map;
+pub mod backtrace;
 pub mod boot;
+pub mod elf;
 pub mod error;
 pub mod exception;
 pub mod gic;
+pub mod gpio;
+pub mod heap;
 pub mod kimage;
+pub mod klog;
 pub mod mimo;
+pub mod mmu;
 pub mod panic;
-pub mod static_bump_alloc;
+pub mod phys_alloc;
+pub mod sched;
+pub mod smp;
+pub mod syscall;
 pub mod timer;
 pub mod uart;
 pub mod vm;
@@ -9,7 +9,7 @@ use libmei::{
     exception,
     kimage::{kernel_image_size, kernel_stack_base},
     mmu::setup_mmu,
-    println, timer, uart,
+    println, syscall, timer, uart,
 };
 use tock_registers::interfaces::Readable;
 
@@ -34,10 +34,10 @@ const EL0_STACK_SIZE: usize = 8192;
 static EL0_STACK: [u8; EL0_STACK_SIZE] = [0; EL0_STACK_SIZE];
 
 /// Entry point for EL0 (user space)
-#[naked]
-unsafe extern "C" fn el0_main() -> ! {
-    // Infinite Loop
-    core::arch::asm!("1: b 1b", options(noreturn));
+extern "C" fn el0_main() -> ! {
+    let msg = b"Hello from EL0!\n";
+    syscall::write(1, msg.as_ptr(), msg.len() as u64);
+    syscall::exit(0);
 }
 
 #[no_mangle]
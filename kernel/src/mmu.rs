@@ -0,0 +1,615 @@
+//! Per-process address spaces, built on the "low half is user, high half is
+//! kernel" split that `vm`'s layout comment already documents for AArch64 (VA bit
+//! [63] selects TTBR0 vs TTBR1) and that holds just as well for RISC-V Sv39
+//! (kernel mappings conventionally live in the sign-extended upper half too).
+//!
+//! The actual "given a VA, walk/populate levels and install a PA with these
+//! attributes" work is architecture-specific, so it's abstracted behind the
+//! [`TranslationRegime`] trait. [`Aarch64Regime`] is the current 4KB/4-level
+//! AArch64 scheme; [`Sv39Regime`] is RISC-V's three-level Sv39 scheme. `vm` and
+//! `AddressSpace` only ever talk to `ActiveRegime`, so the same kernel logic
+//! builds for both `aarch64-unknown-none` and `riscv64imac-unknown-none-elf`.
+//!
+//! The paths that walk and populate a real level-3 table are
+//! `Aarch64Regime::map`/`remap` and `aarch64_regime::handle_demand_fault` and
+//! `handle_cow_fault`, the last two called from the sync exception handlers to
+//! resolve a translation fault against a demand-paged region (`vm::map_lazy`)
+//! or a permission fault against a copy-on-write one (`vm::fork`),
+//! respectively. `Sv39Regime::map`/`remap` and every regime's `unmap` only
+//! bookkeep which mappings were requested, the same stopgap `vm::map_mmio`
+//! uses for the MMIO remap window: they install or clear no descriptor.
+
+use spin::Mutex;
+
+use crate::{
+    address::{Address, PhysicalAddress, TTBR, VirtualAddress},
+    error::{Error, Result},
+    phys_alloc,
+    vm::{Permissions, PAGE_SIZE},
+};
+
+/// Maximum number of distinct ranges a single mapping table (one `AddressSpace`'s
+/// regime, or the shared kernel regime) can hold.
+const MAX_MAPPINGS: usize = 32;
+
+/// A requested virtual-to-physical mapping, carrying the `MemoryMap`-style
+/// permissions a `TranslationRegime` needs to encode its leaf entries. Stands in
+/// for the richer descriptor the real per-level table walker will consume once
+/// it exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    pub virt: VirtualAddress,
+    pub phys: PhysicalAddress,
+    pub num_pages: usize,
+    pub perms: Permissions,
+}
+
+struct MappingTable {
+    mappings: [Option<Mapping>; MAX_MAPPINGS],
+    count: usize,
+}
+
+impl MappingTable {
+    const fn new() -> Self {
+        Self {
+            mappings: [None; MAX_MAPPINGS],
+            count: 0,
+        }
+    }
+
+    fn insert(&mut self, mapping: Mapping) -> Result<()> {
+        if self.count == MAX_MAPPINGS {
+            return Err(Error::AddressSpaceTableFull);
+        }
+
+        self.mappings[self.count] = Some(mapping);
+        self.count += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, virt: VirtualAddress) -> Result<()> {
+        let idx = self.mappings[..self.count]
+            .iter()
+            .position(|m| matches!(m, Some(m) if m.virt == virt))
+            .ok_or(Error::MappingNotFound(virt.as_raw_ptr()))?;
+
+        self.count -= 1;
+        self.mappings[idx] = self.mappings[self.count];
+        self.mappings[self.count] = None;
+        Ok(())
+    }
+
+    fn active(&self) -> &[Option<Mapping>] {
+        &self.mappings[..self.count]
+    }
+
+    /// Overwrites the existing entry whose start address is `mapping.virt` in
+    /// place, or inserts a new one if there is none yet. Used by `vm::fork` to flip a page
+    /// read-only for COW sharing without leaving the pre-fork record behind
+    /// as a stale duplicate for the same VA.
+    fn replace(&mut self, mapping: Mapping) -> Result<()> {
+        if let Some(slot) = self.mappings[..self.count]
+            .iter_mut()
+            .find(|m| matches!(m, Some(m) if m.virt == mapping.virt))
+        {
+            *slot = Some(mapping);
+            return Ok(());
+        }
+
+        self.insert(mapping)
+    }
+}
+
+/// Abstracts the architecture-specific half of address-space management: owning
+/// a root table, recording mappings into it, and installing it into hardware.
+/// `AddressSpace` and `vm` are written entirely in terms of this trait so they
+/// don't need to know whether `ActiveRegime` is AArch64 or RISC-V.
+pub trait TranslationRegime: Sized {
+    /// Page size this regime's leaf entries describe. 4KiB on both the current
+    /// AArch64 scheme and RISC-V Sv39.
+    const PAGE_SIZE: usize;
+
+    /// Allocates a fresh, zeroed root table page and returns a regime rooted at
+    /// it.
+    fn new_root() -> Result<Self>;
+
+    /// Records `mapping`, walking/populating whatever intermediate levels this
+    /// regime's format requires.
+    fn map(&mut self, mapping: Mapping) -> Result<()>;
+
+    /// Removes the mapping whose start address is `virt`; a `virt` inside a
+    /// mapping but past its start matches nothing.
+    fn unmap(&mut self, virt: VirtualAddress) -> Result<()>;
+
+    /// Re-installs the mapping whose start address is `mapping.virt`, updating
+    /// the existing bookkeeping record in place rather than recording a
+    /// second one for the same VA. Used by `vm::fork` to flip an
+    /// already-mapped page read-only for COW sharing.
+    fn remap(&mut self, mapping: Mapping) -> Result<()>;
+
+    /// Every mapping currently recorded for this regime, for `vm::fork`'s
+    /// benefit.
+    fn mappings(&self) -> &[Option<Mapping>];
+
+    /// The value to load into this architecture's root-table control register
+    /// (`TTBR0_EL1` on AArch64, `satp` on RISC-V) to make this regime active.
+    fn root_register_value(&self) -> u64;
+
+    /// Installs `self` as the active translation regime and invalidates any
+    /// stale TLB state left over from whatever was active before.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not rely on whatever regime was active before this call
+    /// still being active afterwards, and `self` must outlive every subsequent
+    /// access through one of its virtual addresses.
+    unsafe fn activate(&self);
+}
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64_regime {
+    use aarch64_cpu::registers::TTBR0_EL1;
+    use core::arch::asm;
+    use tock_registers::interfaces::{Readable, Writeable};
+
+    use super::{MappingTable, TranslationRegime};
+    use crate::{
+        address::{Address, PhysicalAddress, TTBR},
+        error::{Error, Result},
+        mmu::Mapping,
+        phys_alloc,
+        vm::{self, Permissions, VirtualAddress, PAGE_SIZE},
+    };
+
+    /// Stage-1 table/page descriptor bits (4KB granule, VMSAv8-64). Plain `const`
+    /// bit values, matching the style `Sv39Regime` below uses for its own PTE
+    /// format rather than a `register_bitfields!` block, since these are only ever
+    /// OR'd together positionally.
+    const DESC_VALID: u64 = 1 << 0;
+    /// Set on every table descriptor (levels 0-2) and every page descriptor
+    /// (level 3); this regime never installs a level 1/2 block descriptor, so bit
+    /// 1 being clear never needs to be distinguished from "not present".
+    const DESC_TABLE_OR_PAGE: u64 = 1 << 1;
+    /// Access flag. Must be set on every leaf or the next access to it takes an
+    /// access-flag fault instead of succeeding.
+    const DESC_AF: u64 = 1 << 10;
+    /// AP[2:1] (bits [7:6]): EL0 R/W when writable, EL0 read-only otherwise. Both
+    /// grant EL1 the same or broader access.
+    const DESC_AP_RW_EL0: u64 = 0b01 << 6;
+    const DESC_AP_RO_EL0: u64 = 0b11 << 6;
+    const DESC_UXN: u64 = 1 << 54;
+    const DESC_PXN: u64 = 1 << 53;
+    const OUTPUT_ADDR_MASK: u64 = 0x0000_FFFF_FFFF_F000;
+
+    fn leaf_descriptor(phys: PhysicalAddress, perms: Permissions) -> u64 {
+        let mut desc = phys.as_raw_ptr() as u64 & OUTPUT_ADDR_MASK;
+        desc |= DESC_VALID | DESC_TABLE_OR_PAGE | DESC_AF;
+        desc |= if perms.write {
+            DESC_AP_RW_EL0
+        } else {
+            DESC_AP_RO_EL0
+        };
+        if !perms.execute {
+            desc |= DESC_UXN | DESC_PXN;
+        }
+        desc
+    }
+
+    /// Reads `table`'s entry at `index`, returning the output address it
+    /// points at if the entry is valid.
+    ///
+    /// # Safety
+    ///
+    /// `table` must be a live, zero-initialized-or-populated translation table
+    /// page that this regime owns.
+    unsafe fn read_valid_entry(table: PhysicalAddress, index: usize) -> Option<PhysicalAddress> {
+        let slot = (table.as_raw_ptr() as *mut u64).add(index);
+        let existing = slot.read_volatile();
+
+        (existing & DESC_VALID != 0)
+            .then(|| PhysicalAddress::new((existing & OUTPUT_ADDR_MASK) as usize))
+    }
+
+    /// Returns the physical address of the next-level table referenced by
+    /// `table`'s entry at `index`, allocating and zeroing a fresh table if that
+    /// entry isn't installed yet.
+    ///
+    /// # Safety
+    ///
+    /// `table` must be a live, zero-initialized-or-populated translation table
+    /// page that this regime owns.
+    unsafe fn next_table(table: PhysicalAddress, index: usize) -> Result<PhysicalAddress> {
+        if let Some(existing) = read_valid_entry(table, index) {
+            return Ok(existing);
+        }
+
+        let next = phys_alloc::alloc_pages(1)?;
+        (next.as_raw_ptr() as *mut u8).write_bytes(0, PAGE_SIZE);
+        let slot = (table.as_raw_ptr() as *mut u64).add(index);
+        slot.write_volatile(next.as_raw_ptr() as u64 | DESC_VALID | DESC_TABLE_OR_PAGE);
+        Ok(next)
+    }
+
+    /// Walks from `root` down to the level-3 leaf covering `virt` without
+    /// installing anything along the way, for `handle_cow_fault`'s benefit --
+    /// it needs the frame a COW mapping already points at before it can copy
+    /// it. Fails with `Error::MappingNotFound` if any level isn't already
+    /// populated, the same error `AddressSpace::unmap` reports for a VA with
+    /// no mapping at all.
+    ///
+    /// # Safety
+    ///
+    /// `root` must be a live, fully populated translation table this regime
+    /// owns down to `virt`'s leaf.
+    unsafe fn leaf_physical(
+        root: PhysicalAddress,
+        virt: VirtualAddress,
+    ) -> Result<PhysicalAddress> {
+        let not_found = || Error::MappingNotFound(virt.as_raw_ptr());
+
+        let l1 = read_valid_entry(root, virt.get_level0_ind()).ok_or_else(not_found)?;
+        let l2 = read_valid_entry(l1, virt.get_level1_ind()).ok_or_else(not_found)?;
+        let l3 = read_valid_entry(l2, virt.get_level2_ind()).ok_or_else(not_found)?;
+        read_valid_entry(l3, virt.get_level3_ind()).ok_or_else(not_found)
+    }
+
+    /// Walks from `root` down to the level-3 table covering `virt`, allocating
+    /// intermediate levels on demand, installs `phys` as its leaf with `perms`,
+    /// and issues the barrier/invalidate sequence the architecture requires
+    /// before the new mapping is safe to use.
+    ///
+    /// # Safety
+    ///
+    /// `root` must be the physical address a live `TTBR0_EL1` (or a root about to
+    /// be activated into it) points at, and `phys` must be a frame this regime now
+    /// owns exclusively.
+    unsafe fn install_leaf(
+        root: PhysicalAddress,
+        virt: VirtualAddress,
+        phys: PhysicalAddress,
+        perms: Permissions,
+    ) -> Result<()> {
+        let l1 = next_table(root, virt.get_level0_ind())?;
+        let l2 = next_table(l1, virt.get_level1_ind())?;
+        let l3 = next_table(l2, virt.get_level2_ind())?;
+
+        let entry = (l3.as_raw_ptr() as *mut u64).add(virt.get_level3_ind());
+        entry.write_volatile(leaf_descriptor(phys, perms));
+
+        asm!(
+            "dsb ishst",
+            "tlbi vae1is, {va}",
+            "dsb ish",
+            "isb",
+            va = in(reg) (virt.as_raw_ptr() >> 12) as u64,
+            options(nostack)
+        );
+
+        Ok(())
+    }
+
+    /// Completes a translation fault against a demand-paged region: allocates and
+    /// zeroes a fresh frame and installs it as a level-3 leaf in whichever user
+    /// address space's root table `TTBR0_EL1` currently points at. `virt` must
+    /// select `TTBR::Zero` -- the TTBR1 kernel half's `KERNEL_MAPPINGS` is still
+    /// bookkeeping-only and has no root table of its own to install into yet.
+    pub fn handle_demand_fault(virt: VirtualAddress, perms: Permissions) -> Result<()> {
+        if !matches!(virt.get_ttbr_select(), TTBR::Zero) {
+            return Err(Error::MappingNotFound(virt.as_raw_ptr()));
+        }
+
+        let phys = phys_alloc::alloc_pages(1)?;
+        unsafe {
+            (phys.as_raw_ptr() as *mut u8).write_bytes(0, PAGE_SIZE);
+        }
+
+        // CnP (bit 0) is never set by `activate`, so the raw value is the root
+        // table's physical address as-is.
+        let root = PhysicalAddress::new(TTBR0_EL1.get() as usize);
+        unsafe { install_leaf(root, virt, phys, perms) }
+    }
+
+    /// Completes a permission fault against a page `vm::fork` marked
+    /// copy-on-write: copies the frame currently mapped at `virt` onto a fresh
+    /// one and installs that one with `perms` (the full, writable permissions
+    /// the page should have going forward), then releases the faulting side's
+    /// claim on the original via `vm::cow_release`, freeing it once every
+    /// other sharer has done the same.
+    ///
+    /// Always copies rather than first checking whether this is already the
+    /// last sharer (which could skip the copy and just flip permissions in
+    /// place) -- simpler to reason about, and one extra frame copy on the
+    /// first write after a fork isn't worth optimizing away yet.
+    pub fn handle_cow_fault(virt: VirtualAddress, perms: Permissions) -> Result<()> {
+        if !matches!(virt.get_ttbr_select(), TTBR::Zero) {
+            return Err(Error::MappingNotFound(virt.as_raw_ptr()));
+        }
+
+        // CnP (bit 0) is never set by `activate`, so the raw value is the root
+        // table's physical address as-is.
+        let root = PhysicalAddress::new(TTBR0_EL1.get() as usize);
+        let old_phys = unsafe { leaf_physical(root, virt)? };
+
+        let new_phys = phys_alloc::alloc_pages(1)?;
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                old_phys.as_ptr::<u8>(),
+                new_phys.as_mut_ptr::<u8>(),
+                PAGE_SIZE,
+            );
+            install_leaf(root, virt, new_phys, perms)?;
+        }
+
+        if vm::cow_release(old_phys) {
+            phys_alloc::free_pages(old_phys, 1);
+        }
+
+        Ok(())
+    }
+
+    /// The kernel's current 4KB-granule, 4-level AArch64 translation scheme.
+    pub struct Aarch64Regime {
+        root_table: PhysicalAddress,
+        mappings: MappingTable,
+    }
+
+    impl TranslationRegime for Aarch64Regime {
+        const PAGE_SIZE: usize = PAGE_SIZE;
+
+        fn new_root() -> Result<Self> {
+            let root_table = phys_alloc::alloc_pages(1)?;
+            unsafe {
+                (root_table.as_raw_ptr() as *mut u8).write_bytes(0, PAGE_SIZE);
+            }
+
+            Ok(Self {
+                root_table,
+                mappings: MappingTable::new(),
+            })
+        }
+
+        /// Unlike `Sv39Regime::map`, this actually walks/populates the real
+        /// level-3 table straight away -- `root_table` is this regime's own page,
+        /// never the active `TTBR0_EL1` (that's only true once `activate` runs),
+        /// so there's no live-kernel-fetch hazard in installing leaves into it
+        /// ahead of time, the same assumption `handle_demand_fault` already makes
+        /// about the table it installs into.
+        fn map(&mut self, mapping: Mapping) -> Result<()> {
+            for page in 0..mapping.num_pages {
+                let virt = mapping.virt + (page * PAGE_SIZE) as isize;
+                let phys = PhysicalAddress::new(mapping.phys.as_raw_ptr() + page * PAGE_SIZE);
+                unsafe {
+                    install_leaf(self.root_table, virt, phys, mapping.perms)?;
+                }
+            }
+
+            self.mappings.insert(mapping)
+        }
+
+        fn unmap(&mut self, virt: VirtualAddress) -> Result<()> {
+            self.mappings.remove(virt)
+        }
+
+        fn remap(&mut self, mapping: Mapping) -> Result<()> {
+            for page in 0..mapping.num_pages {
+                let virt = mapping.virt + (page * PAGE_SIZE) as isize;
+                let phys = PhysicalAddress::new(mapping.phys.as_raw_ptr() + page * PAGE_SIZE);
+                unsafe {
+                    install_leaf(self.root_table, virt, phys, mapping.perms)?;
+                }
+            }
+
+            self.mappings.replace(mapping)
+        }
+
+        fn mappings(&self) -> &[Option<Mapping>] {
+            self.mappings.active()
+        }
+
+        fn root_register_value(&self) -> u64 {
+            self.root_table.as_raw_ptr() as u64
+        }
+
+        unsafe fn activate(&self) {
+            TTBR0_EL1.set(self.root_register_value());
+
+            asm!(
+                "dsb ishst",
+                "tlbi vmalle1is",
+                "dsb ish",
+                "isb",
+                options(nomem, nostack)
+            );
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+pub use aarch64_regime::{handle_cow_fault, handle_demand_fault, Aarch64Regime};
+
+#[cfg(target_arch = "riscv64")]
+mod sv39_regime {
+    use super::{MappingTable, TranslationRegime};
+    use crate::{
+        address::{Address, PhysicalAddress, VirtualAddress},
+        error::Result,
+        mmu::Mapping,
+        phys_alloc,
+        vm::Permissions,
+    };
+
+    /// Sv39 has 9-bit VPN indices at each of 3 levels and a 12-bit page offset.
+    pub const PAGE_OFFSET_BITS: u32 = 12;
+    pub const VPN_BITS: u32 = 9;
+    pub const LEVELS: usize = 3;
+
+    /// PTE permission/state bits (RISC-V privileged spec, `satp`/Sv39 PTE format).
+    pub const PTE_V: u64 = 1 << 0;
+    pub const PTE_R: u64 = 1 << 1;
+    pub const PTE_W: u64 = 1 << 2;
+    pub const PTE_X: u64 = 1 << 3;
+    pub const PTE_U: u64 = 1 << 4;
+    pub const PTE_G: u64 = 1 << 5;
+    pub const PTE_A: u64 = 1 << 6;
+    pub const PTE_D: u64 = 1 << 7;
+    const PPN_SHIFT: u32 = 10;
+
+    /// `satp.MODE` field selecting Sv39.
+    const SATP_MODE_SV39: u64 = 8 << 60;
+    const SATP_ASID_SHIFT: u32 = 44;
+
+    /// Encodes a leaf PTE for `phys`/`perms` in the Sv39 format: PPN in bits
+    /// [53:10], followed by the D/A/G/U/X/W/R/V flag bits.
+    pub fn encode_leaf_pte(phys: PhysicalAddress, perms: Permissions) -> u64 {
+        let ppn = phys.as_raw_ptr() as u64 >> PAGE_OFFSET_BITS;
+
+        let mut pte = (ppn << PPN_SHIFT) | PTE_V | PTE_A | PTE_D;
+        if perms.read {
+            pte |= PTE_R;
+        }
+        if perms.write {
+            pte |= PTE_W;
+        }
+        if perms.execute {
+            pte |= PTE_X;
+        }
+
+        pte
+    }
+
+    /// RISC-V's Sv39 translation scheme: 3 levels, 4KiB pages.
+    pub struct Sv39Regime {
+        root_table: PhysicalAddress,
+        mappings: MappingTable,
+        asid: u16,
+    }
+
+    impl TranslationRegime for Sv39Regime {
+        const PAGE_SIZE: usize = 1 << PAGE_OFFSET_BITS;
+
+        fn new_root() -> Result<Self> {
+            let root_table = phys_alloc::alloc_pages(1)?;
+            unsafe {
+                (root_table.as_raw_ptr() as *mut u8).write_bytes(0, Self::PAGE_SIZE);
+            }
+
+            Ok(Self {
+                root_table,
+                mappings: MappingTable::new(),
+                asid: 0,
+            })
+        }
+
+        fn map(&mut self, mapping: Mapping) -> Result<()> {
+            // Real descriptor installation awaits the per-level table walker;
+            // `encode_leaf_pte` is the format that walker will write.
+            let _ = encode_leaf_pte(mapping.phys, mapping.perms);
+            self.mappings.insert(mapping)
+        }
+
+        fn unmap(&mut self, virt: VirtualAddress) -> Result<()> {
+            self.mappings.remove(virt)
+        }
+
+        fn remap(&mut self, mapping: Mapping) -> Result<()> {
+            // Real descriptor installation awaits the per-level table walker;
+            // see `map`'s comment above.
+            let _ = encode_leaf_pte(mapping.phys, mapping.perms);
+            self.mappings.replace(mapping)
+        }
+
+        fn mappings(&self) -> &[Option<Mapping>] {
+            self.mappings.active()
+        }
+
+        fn root_register_value(&self) -> u64 {
+            let root_ppn = self.root_table.as_raw_ptr() as u64 >> PAGE_OFFSET_BITS;
+            SATP_MODE_SV39 | ((self.asid as u64) << SATP_ASID_SHIFT) | root_ppn
+        }
+
+        unsafe fn activate(&self) {
+            let satp = self.root_register_value();
+            core::arch::asm!(
+                "csrw satp, {satp}",
+                "sfence.vma",
+                satp = in(reg) satp,
+                options(nostack)
+            );
+        }
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+pub use sv39_regime::Sv39Regime;
+
+#[cfg(target_arch = "aarch64")]
+pub type ActiveRegime = Aarch64Regime;
+
+#[cfg(target_arch = "riscv64")]
+pub type ActiveRegime = Sv39Regime;
+
+lazy_static! {
+    /// Bookkeeping for the kernel (high) half of the address space, shared by
+    /// every `AddressSpace` since it's never swapped on a context switch.
+    static ref KERNEL_MAPPINGS: Mutex<MappingTable> = Mutex::new(MappingTable::new());
+}
+
+/// A user process's private half of the address space.
+pub struct AddressSpace {
+    regime: ActiveRegime,
+}
+
+impl AddressSpace {
+    /// Allocates a fresh, zeroed root table for a new user address space.
+    pub fn new_user_space() -> Result<Self> {
+        Ok(Self {
+            regime: ActiveRegime::new_root()?,
+        })
+    }
+
+    /// Records `mapping`, routing it to this address space's own regime or the
+    /// shared kernel mapping table depending on which one `mapping.virt` selects.
+    pub fn map(&mut self, mapping: Mapping) -> Result<()> {
+        match mapping.virt.get_ttbr_select() {
+            TTBR::Zero => self.regime.map(mapping),
+            TTBR::One => KERNEL_MAPPINGS.lock().insert(mapping),
+        }
+    }
+
+    /// Removes the mapping whose start address is `virt`, routed the same way
+    /// as `map`.
+    pub fn unmap(&mut self, virt: VirtualAddress) -> Result<()> {
+        match virt.get_ttbr_select() {
+            TTBR::Zero => self.regime.unmap(virt),
+            TTBR::One => KERNEL_MAPPINGS.lock().remove(virt),
+        }
+    }
+
+    /// Re-records `mapping`, routed the same way as `map` but updating an
+    /// existing entry for the same VA in place instead of adding a duplicate.
+    /// Used by `vm::fork` to flip a page read-only for COW sharing.
+    pub(crate) fn remap(&mut self, mapping: Mapping) -> Result<()> {
+        match mapping.virt.get_ttbr_select() {
+            TTBR::Zero => self.regime.remap(mapping),
+            TTBR::One => KERNEL_MAPPINGS.lock().replace(mapping),
+        }
+    }
+
+    /// Every mapping currently recorded in this address space's own (TTBR0)
+    /// half -- the kernel (TTBR1) half lives in the shared `KERNEL_MAPPINGS`
+    /// table instead, which `vm::fork` has no reason to touch since every
+    /// address space already shares it.
+    pub(crate) fn user_mappings(&self) -> impl Iterator<Item = Mapping> + '_ {
+        self.regime.mappings().iter().flatten().copied()
+    }
+
+    /// Makes this the active user address space. See
+    /// `TranslationRegime::activate` for the safety contract.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as `TranslationRegime::activate`.
+    pub unsafe fn switch_to(&self) {
+        self.regime.activate();
+    }
+}
@@ -0,0 +1,74 @@
+//! Panic handling and fault reporting.
+//!
+//! Both entry points here print through `klog::panic_line`, not `log::error!`
+//! or the regular `println!` macro: a panic or an unhandled exception may
+//! itself be the reason the interrupt-driven TX ring is stuck, so diagnostics
+//! need a path that doesn't depend on IRQs still working. `panic_line` writes
+//! the same `[tick][LEVEL] message` shape every other log line does, straight
+//! over `uart::print_blocking` rather than through the ring.
+
+use core::{arch::asm, panic::PanicInfo};
+use log::Level;
+
+use crate::{backtrace, exception::ExceptionContext, klog, uart};
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    klog::panic_line(Level::Error, format_args!("*** KERNEL PANIC ***"));
+
+    if let Some(location) = info.location() {
+        klog::panic_line(
+            Level::Error,
+            format_args!(
+                "at {}:{}:{}",
+                location.file(),
+                location.line(),
+                location.column()
+            ),
+        );
+    }
+
+    klog::panic_line(Level::Error, format_args!("{}", info.message()));
+
+    let (fp, lr): (usize, usize);
+    // Safety: reads `x29`/`lr` without touching memory or the stack pointer.
+    unsafe {
+        asm!(
+            "mov {fp}, x29",
+            "mov {lr}, lr",
+            fp = out(reg) fp,
+            lr = out(reg) lr,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    backtrace::backtrace(fp, lr);
+
+    uart::flush();
+
+    halt()
+}
+
+/// Reports an unhandled CPU exception as a register dump -- general purpose
+/// registers, `ELR_EL1`, `SPSR_EL1`, `ESR_EL1`, and the faulting address when the
+/// exception class carries one -- then halts. Called from `exception::default_handler`
+/// for exceptions with no installed handler, mirroring `panic` but driven from a
+/// saved `ExceptionContext` rather than a `PanicInfo`.
+pub(crate) fn panic_with_context(funcname: &str, ec: &ExceptionContext) -> ! {
+    klog::panic_line(
+        Level::Error,
+        format_args!("*** UNHANDLED CPU EXCEPTION ({funcname}) ***\n{ec}"),
+    );
+
+    let (fp, lr) = ec.frame_pointer_and_lr();
+    backtrace::backtrace(fp, lr);
+
+    uart::flush();
+
+    halt()
+}
+
+fn halt() -> ! {
+    loop {
+        aarch64_cpu::asm::wfe();
+    }
+}
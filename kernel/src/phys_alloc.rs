@@ -0,0 +1,192 @@
+//! Binary buddy allocator for physical page frames.
+//!
+//! Pages are tracked in power-of-two runs: free list `k` holds aligned blocks of
+//! `2^k` frames. Allocating `num_pages` rounds up to the smallest order that fits,
+//! splits a larger free block down to that order (handing the unused buddy halves
+//! back to the lower-order lists), and frees merge a block with its buddy
+//! (`index XOR block_size`) whenever that buddy is free at the same order,
+//! propagating the merge upward. This gives `mmu`/`vm` a real source of physically
+//! contiguous pages for translation tables and DMA buffers.
+
+use spin::Mutex;
+
+use crate::{
+    address::PhysicalAddress,
+    error::{Error, Result},
+    vm::PAGE_SIZE,
+};
+
+/// Number of 4KiB frames the allocator manages (a 4MiB pool).
+const NUM_FRAMES: usize = 1024;
+
+/// `NUM_FRAMES` is a power of two, so this is its log2: the largest order a single
+/// free list can hold (one block spanning the whole pool).
+const MAX_ORDER: usize = 10;
+
+/// Sentinel meaning "no frame", used instead of `Option<usize>` so the free-list
+/// link can be written directly into the frame's own backing bytes.
+const NONE_FRAME: usize = usize::MAX;
+
+struct FreeLists {
+    /// `heads[order]` is the frame index at the head of that order's free list, or
+    /// `NONE_FRAME` if empty.
+    heads: [usize; MAX_ORDER + 1],
+    initialized: bool,
+}
+
+pub struct PhysicalFrameAllocator {
+    pool: [u8; NUM_FRAMES * PAGE_SIZE],
+    free_lists: Mutex<FreeLists>,
+}
+
+unsafe impl Sync for PhysicalFrameAllocator {}
+
+impl PhysicalFrameAllocator {
+    const fn new() -> Self {
+        Self {
+            pool: [0; NUM_FRAMES * PAGE_SIZE],
+            free_lists: Mutex::new(FreeLists {
+                heads: [NONE_FRAME; MAX_ORDER + 1],
+                initialized: false,
+            }),
+        }
+    }
+
+    fn pool_base(&self) -> usize {
+        self.pool.as_ptr() as usize
+    }
+
+    fn frame_to_paddr(&self, frame: usize) -> PhysicalAddress {
+        PhysicalAddress::new(self.pool_base() + frame * PAGE_SIZE)
+    }
+
+    fn paddr_to_frame(&self, paddr: PhysicalAddress) -> usize {
+        (paddr.as_raw_ptr() - self.pool_base()) / PAGE_SIZE
+    }
+
+    /// Reads the free-list link stored in a free frame's first bytes.
+    fn read_link(&self, frame: usize) -> usize {
+        let ptr = (self.pool_base() + frame * PAGE_SIZE) as *const usize;
+        unsafe { ptr.read() }
+    }
+
+    fn write_link(&self, frame: usize, next: usize) {
+        let ptr = (self.pool_base() + frame * PAGE_SIZE) as *mut usize;
+        unsafe { ptr.write(next) };
+    }
+
+    fn ensure_init(&self, lists: &mut FreeLists) {
+        if lists.initialized {
+            return;
+        }
+
+        self.write_link(0, NONE_FRAME);
+        lists.heads[MAX_ORDER] = 0;
+        lists.initialized = true;
+    }
+
+    fn push_free(&self, lists: &mut FreeLists, order: usize, frame: usize) {
+        self.write_link(frame, lists.heads[order]);
+        lists.heads[order] = frame;
+    }
+
+    fn pop_free(&self, lists: &mut FreeLists, order: usize) -> Option<usize> {
+        let frame = lists.heads[order];
+        if frame == NONE_FRAME {
+            return None;
+        }
+
+        lists.heads[order] = self.read_link(frame);
+        Some(frame)
+    }
+
+    /// Removes `frame` from order `order`'s free list if it's present there,
+    /// reporting whether it was found.
+    fn remove_free(&self, lists: &mut FreeLists, order: usize, frame: usize) -> bool {
+        let mut curr = lists.heads[order];
+        let mut prev = None;
+
+        while curr != NONE_FRAME {
+            let next = self.read_link(curr);
+
+            if curr == frame {
+                match prev {
+                    Some(p) => self.write_link(p, next),
+                    None => lists.heads[order] = next,
+                }
+                return true;
+            }
+
+            prev = Some(curr);
+            curr = next;
+        }
+
+        false
+    }
+
+    /// Allocates a physically contiguous run of at least `num_pages` 4KiB pages.
+    pub fn alloc(&self, num_pages: usize) -> Result<PhysicalAddress> {
+        let order = order_for(num_pages);
+        if order > MAX_ORDER {
+            return Err(Error::ContigiousPhysicalRangeUnavailable(num_pages));
+        }
+
+        let mut lists = self.free_lists.lock();
+        self.ensure_init(&mut lists);
+
+        let source_order = (order..=MAX_ORDER)
+            .find(|&o| lists.heads[o] != NONE_FRAME)
+            .ok_or(Error::PhysicalOOM)?;
+
+        let frame = self.pop_free(&mut lists, source_order).unwrap();
+
+        for split_order in (order..source_order).rev() {
+            let buddy = frame ^ (1 << split_order);
+            self.push_free(&mut lists, split_order, buddy);
+        }
+
+        Ok(self.frame_to_paddr(frame))
+    }
+
+    /// Returns a run of `num_pages` pages previously returned by `alloc` back to
+    /// the pool, coalescing with its buddy at each order while the buddy is free.
+    pub fn free(&self, paddr: PhysicalAddress, num_pages: usize) {
+        let mut order = order_for(num_pages);
+        let mut frame = self.paddr_to_frame(paddr);
+
+        let mut lists = self.free_lists.lock();
+
+        while order < MAX_ORDER {
+            let buddy = frame ^ (1 << order);
+
+            if !self.remove_free(&mut lists, order, buddy) {
+                break;
+            }
+
+            frame = frame.min(buddy);
+            order += 1;
+        }
+
+        self.push_free(&mut lists, order, frame);
+    }
+}
+
+/// Smallest order `k` such that `2^k >= num_pages`.
+fn order_for(num_pages: usize) -> usize {
+    let num_pages = num_pages.max(1);
+    (usize::BITS - (num_pages - 1).leading_zeros()) as usize
+}
+
+lazy_static! {
+    static ref PHYSICAL_FRAME_ALLOCATOR: PhysicalFrameAllocator = PhysicalFrameAllocator::new();
+}
+
+/// Allocates a physically contiguous run of `num_pages` 4KiB pages.
+pub fn alloc_pages(num_pages: usize) -> Result<PhysicalAddress> {
+    PHYSICAL_FRAME_ALLOCATOR.alloc(num_pages)
+}
+
+/// Frees a run of `num_pages` pages previously returned by `alloc_pages`.
+pub fn free_pages(paddr: PhysicalAddress, num_pages: usize) {
+    PHYSICAL_FRAME_ALLOCATOR.free(paddr, num_pages)
+}
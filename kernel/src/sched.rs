@@ -0,0 +1,442 @@
+//! Preemptive round-robin scheduler built on `timer`'s virtual-timer tick,
+//! `gic`'s IRQ dispatch, and `exception`'s `#[exception_handler]` trampoline.
+//!
+//! A `Task` is nothing more than a kernel stack plus wherever on it the last
+//! `ExceptionContext` was saved -- exactly the struct `#[exception_handler]`
+//! already pushes onto `sp` for every trap. `schedule` (called from the virtual
+//! timer's `IRQHandler::handle` and from `sys_yield`) records the interrupted
+//! task's own `ec` address as its new `saved_sp`, picks the next `Runnable` task,
+//! and publishes its `saved_sp` through `NEXT_SP`. `__sched_maybe_switch`, spliced
+//! into the shared asm trampoline right after the handler call, moves `sp` there
+//! before the trampoline's restore sequence runs -- so the very next `ldp`/`ldr`
+//! it executes reads the next task's registers instead of the interrupted one's,
+//! and its `eret` resumes the next task rather than the one that just trapped.
+//!
+//! A task that has never run yet "resumes into" a context synthesized by
+//! `ExceptionContext::new_for_task`, so the first switch to it looks, from the
+//! trampoline's point of view, identical to resuming a previously preempted one.
+//!
+//! `spawn_user` is the EL0 counterpart of `spawn`: it resumes into
+//! `ExceptionContext::new_for_task_el0` instead, gives the task its own
+//! `mmu::AddressSpace`, and attaches a [`Profile`] -- the sandbox-style
+//! syscall allow-list `syscall::dispatch` checks a task's `svc`s against.
+//! `spawn`'s kernel tasks get [`Profile::ALL`] instead, since they're as
+//! trusted as the kernel itself.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use spin::Mutex;
+
+use crate::{
+    address::{Address, PhysicalAddress, VirtualAddress},
+    error::{Error, Result},
+    exception::ExceptionContext,
+    mmu, phys_alloc, smp, syscall,
+    vm::{Permissions, PAGE_SIZE},
+};
+
+/// Per-task kernel stack size, matching `kimage`'s reservation for the boot
+/// stack.
+const TASK_STACK_SIZE: usize = 16 * 1024;
+
+/// Upper bound on live tasks, the same fixed-capacity-table style
+/// `gic::REGISTERED_IRQ_HANDLERS`/`exception::REGISTERED_TRAP_HANDLERS` use.
+const MAX_TASKS: usize = 16;
+
+/// Fixed entry/stack placement for every `spawn_user` task. All of TTBR0's
+/// low-half range is equally available to a brand-new `AddressSpace` (its
+/// table starts out empty), so one arbitrary pair of addresses below the MMIO
+/// remap window (`vm::MMIO_WINDOW_END`) is as good as any other until a real
+/// ELF loader picks per-task layouts.
+const USER_ENTRY_VIRT: usize = 0x0040_0000;
+const USER_STACK_TOP_VIRT: usize = 0x0080_0000;
+
+/// Sandbox-style allow-list of syscalls a task may make, checked by
+/// `syscall::dispatch` against the calling task's [`Profile`] before a handler
+/// runs -- the "explicit per-task profile" chunk10-3 models `Operation` after.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    Write,
+    Exit,
+    Yield,
+}
+
+impl Operation {
+    const COUNT: usize = 3;
+
+    const fn index(self) -> usize {
+        match self {
+            Operation::Write => 0,
+            Operation::Exit => 1,
+            Operation::Yield => 2,
+        }
+    }
+}
+
+/// A task's allow-list: which [`Operation`]s its syscalls may invoke.
+/// `spawn`'s kernel tasks run under [`Profile::ALL`] -- they're as trusted as
+/// the kernel itself, the same way `spawn` gives them no `AddressSpace` of
+/// their own and just runs them against the kernel's existing mappings.
+/// `spawn_user` tasks get whatever allow-list the caller builds with
+/// [`Profile::NONE`]/[`Profile::allow`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Profile([bool; Operation::COUNT]);
+
+impl Profile {
+    pub(crate) const ALL: Self = Self([true; Operation::COUNT]);
+    pub(crate) const NONE: Self = Self([false; Operation::COUNT]);
+
+    pub(crate) const fn allow(mut self, op: Operation) -> Self {
+        self.0[op.index()] = true;
+        self
+    }
+
+    pub(crate) fn permits(&self, op: Operation) -> bool {
+        self.0[op.index()]
+    }
+}
+
+/// Holds the `saved_sp` `schedule` should switch to next, or `0` for "no switch
+/// pending" -- read and cleared by `__sched_check_switch`, which runs on every
+/// exception return via `__sched_maybe_switch`.
+static NEXT_SP: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TaskState {
+    Runnable,
+    Blocked,
+}
+
+struct Task {
+    /// Backing storage for the task's kernel stack. Never read directly once the
+    /// task has run once -- `saved_sp` is what everything else uses -- but it
+    /// must stay alive for the task's lifetime, so it's kept here rather than
+    /// dropped after `spawn` builds the initial context.
+    _stack: Vec<u8>,
+    state: TaskState,
+    /// Address within `_stack` of this task's last-saved `ExceptionContext`.
+    saved_sp: usize,
+    /// Syscall allow-list this task's `svc` traps are checked against.
+    profile: Profile,
+    /// This task's own TTBR0 page table, for an EL0 task spawned via
+    /// `spawn_user`. `None` for a `spawn`ned kernel task, which runs against
+    /// the kernel's existing mappings the same way the boot path does.
+    ///
+    /// Not yet activated on context switch: the kernel currently lives in
+    /// TTBR0's numeric range (`vm`'s `KERNEL_VIRT_ADDRESS_BASE` is `0x0`)
+    /// instead of TTBR1, so swapping TTBR0 away from the kernel's own mappings
+    /// mid-`schedule()` would fault the very next kernel instruction fetch.
+    /// Wiring `address_space.switch_to()` into `schedule` is follow-on work
+    /// that depends on the kernel completing that TTBR1 migration first.
+    address_space: Option<mmu::AddressSpace>,
+}
+
+struct Scheduler {
+    tasks: [Option<Task>; MAX_TASKS],
+    /// Index into `tasks` of the task each core is currently running, or `None`
+    /// before that core has taken its first scheduling tick.
+    current: [Option<usize>; smp::NUM_CORES],
+}
+
+impl Scheduler {
+    const fn new() -> Self {
+        Self {
+            tasks: [const { None }; MAX_TASKS],
+            current: [None; smp::NUM_CORES],
+        }
+    }
+
+    /// The first tick a core ever takes has nothing to switch away from -- adopt
+    /// whatever was already running on `ec`'s own stack (the boot path, or
+    /// whichever task last ran here) as that core's current task.
+    fn ensure_current(&mut self, core: usize, ec: &mut ExceptionContext) -> Option<usize> {
+        if let Some(idx) = self.current[core] {
+            return Some(idx);
+        }
+
+        let idx = self.tasks.iter().position(Option::is_none)?;
+        self.tasks[idx] = Some(Task {
+            _stack: Vec::new(),
+            state: TaskState::Runnable,
+            saved_sp: ec as *mut ExceptionContext as usize,
+            profile: Profile::ALL,
+            address_space: None,
+        });
+        self.current[core] = Some(idx);
+        Some(idx)
+    }
+
+    /// Next `Runnable` task after `current`, scanning cyclically. The scan's
+    /// last offset (`MAX_TASKS`) wraps back to `current` itself, so this
+    /// naturally returns `current` if nothing else is runnable, or `None` if
+    /// even `current` isn't (e.g. it just called `block`).
+    fn pick_next(&self, current: usize) -> Option<usize> {
+        (1..=MAX_TASKS)
+            .map(|offset| (current + offset) % MAX_TASKS)
+            .find(|&idx| matches!(&self.tasks[idx], Some(t) if t.state == TaskState::Runnable))
+    }
+}
+
+static SCHEDULER: Mutex<Scheduler> = Mutex::new(Scheduler::new());
+
+/// Spawns a new task that starts executing at `entry` the next time it's
+/// scheduled. `entry` never returns, the same convention `smp::secondary_main`
+/// and `main::el0_main` use for a thread with no caller to return to.
+pub(crate) fn spawn(entry: fn() -> !) -> Result<()> {
+    let mut stack = alloc::vec![0u8; TASK_STACK_SIZE];
+    let stack_top = stack.as_mut_ptr() as usize + TASK_STACK_SIZE;
+    let saved_sp = stack_top - core::mem::size_of::<ExceptionContext>();
+
+    unsafe {
+        (saved_sp as *mut ExceptionContext).write(ExceptionContext::new_for_task(entry as usize));
+    }
+
+    let mut sched = SCHEDULER.lock();
+    let idx = sched
+        .tasks
+        .iter()
+        .position(Option::is_none)
+        .ok_or(Error::SchedulerTableFull)?;
+
+    sched.tasks[idx] = Some(Task {
+        _stack: stack,
+        state: TaskState::Runnable,
+        saved_sp,
+        profile: Profile::ALL,
+        address_space: None,
+    });
+
+    Ok(())
+}
+
+/// Spawns a new EL0 task running under `profile`'s syscall allow-list, with its
+/// own `AddressSpace`: `phys_entry` is eagerly mapped read+execute at
+/// `USER_ENTRY_VIRT`, and a freshly allocated frame is mapped read+write as its
+/// stack, topping out at `USER_STACK_TOP_VIRT`. Like `spawn`, the task starts
+/// running the next time it's scheduled.
+///
+/// `phys_entry` must already hold the task's code (e.g. a `PT_LOAD` segment
+/// `elf::load_segments` read out of a loaded binary) -- there's no loader here,
+/// just the mapping of an already-prepared page.
+pub(crate) fn spawn_user(phys_entry: PhysicalAddress, profile: Profile) -> Result<()> {
+    let mut address_space = mmu::AddressSpace::new_user_space()?;
+
+    let entry_virt = VirtualAddress::new(USER_ENTRY_VIRT).expect("USER_ENTRY_VIRT is a valid VA");
+    address_space.map(mmu::Mapping {
+        virt: entry_virt,
+        phys: phys_entry,
+        num_pages: 1,
+        perms: Permissions {
+            read: true,
+            write: false,
+            execute: true,
+        },
+    })?;
+
+    let stack_phys = phys_alloc::alloc_pages(1)?;
+    let stack_virt = VirtualAddress::new(USER_STACK_TOP_VIRT - PAGE_SIZE)
+        .expect("USER_STACK_TOP_VIRT - PAGE_SIZE is a valid VA");
+    address_space.map(mmu::Mapping {
+        virt: stack_virt,
+        phys: stack_phys,
+        num_pages: 1,
+        perms: Permissions {
+            read: true,
+            write: true,
+            execute: false,
+        },
+    })?;
+
+    let mut stack = alloc::vec![0u8; TASK_STACK_SIZE];
+    let stack_top = stack.as_mut_ptr() as usize + TASK_STACK_SIZE;
+    let saved_sp = stack_top - core::mem::size_of::<ExceptionContext>();
+
+    unsafe {
+        (saved_sp as *mut ExceptionContext).write(ExceptionContext::new_for_task_el0(
+            entry_virt.as_raw_ptr(),
+            USER_STACK_TOP_VIRT,
+        ));
+    }
+
+    let mut sched = SCHEDULER.lock();
+    let idx = sched
+        .tasks
+        .iter()
+        .position(Option::is_none)
+        .ok_or(Error::SchedulerTableFull)?;
+
+    sched.tasks[idx] = Some(Task {
+        _stack: stack,
+        state: TaskState::Runnable,
+        saved_sp,
+        profile,
+        address_space: Some(address_space),
+    });
+
+    Ok(())
+}
+
+/// Checks whether the task currently running on this core is allowed to
+/// perform `op`, per its [`Profile`]. Used by `syscall::dispatch` to gate each
+/// handler before it runs, the sandbox-profile enforcement point chunk10-3
+/// asks for. Adopts the caller as this core's current task first if `schedule`
+/// hasn't run yet, the same bootstrapping `schedule` itself relies on via
+/// `ensure_current`; fails closed (denies) on the otherwise-unreachable case
+/// where the task table has no room left to record that adoption.
+pub(crate) fn current_task_permits(ec: &mut ExceptionContext, op: Operation) -> bool {
+    let core = smp::current_core_id();
+    let mut sched = SCHEDULER.lock();
+
+    let Some(idx) = sched.ensure_current(core, ec) else {
+        return false;
+    };
+
+    sched.tasks[idx].as_ref().unwrap().profile.permits(op)
+}
+
+/// Checks that every page in `[ptr, ptr + len)` is covered by one of the
+/// calling task's own readable TTBR0 mappings, so a syscall like
+/// `sys_write` can't be handed an arbitrary kernel address/length and echo
+/// that memory back out -- the same call-site enforcement
+/// `current_task_permits` already does for *which* syscalls a task's
+/// `Profile` allows, just for *what memory* an allowed one may touch. A
+/// `spawn`ned kernel task (`address_space: None`) is as trusted as the
+/// kernel itself, the same way it already runs under `Profile::ALL`, so
+/// it's exempt.
+pub(crate) fn current_task_validate_read(
+    ec: &mut ExceptionContext,
+    ptr: usize,
+    len: usize,
+) -> bool {
+    if len == 0 {
+        return true;
+    }
+
+    let Some(end) = ptr.checked_add(len) else {
+        return false;
+    };
+
+    let core = smp::current_core_id();
+    let mut sched = SCHEDULER.lock();
+
+    let Some(idx) = sched.ensure_current(core, ec) else {
+        return false;
+    };
+
+    let Some(address_space) = sched.tasks[idx].as_ref().unwrap().address_space.as_ref() else {
+        return true;
+    };
+
+    let mut page = ptr & !(PAGE_SIZE - 1);
+    while page < end {
+        let Ok(virt) = VirtualAddress::new(page) else {
+            return false;
+        };
+
+        let covered = address_space.user_mappings().any(|m| {
+            m.perms.read
+                && m.virt.as_raw_ptr() <= virt.as_raw_ptr()
+                && virt.as_raw_ptr() < m.virt.as_raw_ptr() + m.num_pages * PAGE_SIZE
+        });
+
+        if !covered {
+            return false;
+        }
+
+        page += PAGE_SIZE;
+    }
+
+    true
+}
+
+/// Saves the currently running task's context (wherever `ec` already is, on its
+/// own stack), picks the next `Runnable` task, and -- if it's a different task --
+/// arranges for `__sched_maybe_switch` to move `sp` there before this exception
+/// returns. Called from the virtual-timer tick for preemption and from
+/// `sys_yield` for cooperative yielding; both already run with `ec` pointing at
+/// the calling task's freshly saved context.
+pub(crate) fn schedule(ec: &mut ExceptionContext) {
+    let core = smp::current_core_id();
+    let mut sched = SCHEDULER.lock();
+
+    let Some(current) = sched.ensure_current(core, ec) else {
+        return;
+    };
+    sched.tasks[current].as_mut().unwrap().saved_sp = ec as *mut ExceptionContext as usize;
+
+    let Some(next) = sched.pick_next(current) else {
+        return;
+    };
+    if next == current {
+        return;
+    }
+
+    sched.current[core] = Some(next);
+    NEXT_SP.store(sched.tasks[next].as_ref().unwrap().saved_sp, Ordering::Release);
+}
+
+/// Cooperatively hands the core to another `Runnable` task via the same `svc`
+/// trap EL0's `libmei::syscall::yield_now` uses, landing in `sys_yield` ->
+/// `schedule`.
+pub(crate) fn yield_now() {
+    syscall::yield_now();
+}
+
+/// Marks the calling task `Blocked` and immediately yields, so `schedule` won't
+/// pick it again until a matching `wake`.
+pub(crate) fn block() {
+    let core = smp::current_core_id();
+    {
+        let mut sched = SCHEDULER.lock();
+        if let Some(current) = sched.current[core] {
+            sched.tasks[current].as_mut().unwrap().state = TaskState::Blocked;
+        }
+    }
+
+    yield_now();
+}
+
+/// Marks task index `task` `Runnable` again, making it eligible the next time
+/// `schedule` runs. There's no task-handle type yet to spell this as `wake(id)`
+/// against -- `spawn`'s callers don't have one to hold onto -- so this takes the
+/// raw table index `ensure_current`/`spawn` hand out internally.
+pub(crate) fn wake(task: usize) {
+    if let Some(slot) = SCHEDULER.lock().tasks.get_mut(task).and_then(Option::as_mut) {
+        slot.state = TaskState::Runnable;
+    }
+}
+
+/// Consulted by `__sched_maybe_switch` right after every exception handler
+/// returns: hands back the next task's `saved_sp` if `schedule` queued a switch,
+/// clearing it in the same step so it only ever fires once.
+#[no_mangle]
+extern "C" fn __sched_check_switch() -> usize {
+    NEXT_SP.swap(0, Ordering::Acquire)
+}
+
+/// Spliced into `#[exception_handler]`'s generated asm right after the handler
+/// call, on every one of the 16 exception vectors. Every register the
+/// trampoline's restore sequence needs is reloaded fresh from `[sp, ...]`
+/// immediately afterwards, so the only value to carry across the call to
+/// `__sched_check_switch` is this function's own return address, kept in `x19`
+/// -- the only job is to move `sp` itself if `schedule` queued a switch.
+///
+/// # Safety
+///
+/// Must only ever be reached via the `bl` the macro emits, with `sp` pointing at
+/// a valid `ExceptionContext` belonging to the task now being suspended.
+#[no_mangle]
+#[naked]
+unsafe extern "C" fn __sched_maybe_switch() {
+    core::arch::asm!(
+        "mov x19, x30",
+        "bl {check}",
+        "cbz x0, 1f",
+        "mov sp, x0",
+        "1:",
+        "ret x19",
+        check = sym __sched_check_switch,
+        options(noreturn)
+    );
+}
@@ -0,0 +1,87 @@
+//! Secondary-core (SMP) bring-up for the rpi3's four Cortex-A53 cores.
+//!
+//! Core 0 runs the regular boot path through `boot.s`; cores 1-3 are parked by the
+//! firmware spinning on a per-core release address in the low-memory spin table
+//! (`0xE0`, `0xE8`, `0xF0`). Writing the entry point there and signalling an event (`sev`) releases
+//! the core into `secondary_entry`.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use aarch64_cpu::registers::MPIDR_EL1;
+use tock_registers::interfaces::Readable;
+
+use crate::{boot::switch_from_el2_to_el1, gic};
+
+/// Number of cores on the rpi3.
+pub const NUM_CORES: usize = 4;
+
+/// rpi3 spin-table release addresses for cores 1-3 (core 0 never parks here).
+const CORE_RELEASE_ADDR: [usize; NUM_CORES] = [0, 0xE0, 0xE8, 0xF0];
+
+/// Per-core stack size handed to each secondary core.
+const SECONDARY_STACK_SIZE: usize = 16 * 1024;
+
+#[repr(align(16))]
+struct SecondaryStack([u8; SECONDARY_STACK_SIZE]);
+
+static SECONDARY_STACKS: [SecondaryStack; NUM_CORES - 1] =
+    [const { SecondaryStack([0; SECONDARY_STACK_SIZE]) }; NUM_CORES - 1];
+
+/// Tracks how many secondary cores have confirmed they reached Rust, so
+/// `release_secondary_cores` can wait for bring-up before returning.
+static CORES_ONLINE: AtomicUsize = AtomicUsize::new(1);
+
+/// .
+///
+/// # Safety
+///
+/// Must be called exactly once, from core 0, after the GIC has been initialized.
+pub unsafe fn release_secondary_cores() {
+    for core in 1..NUM_CORES {
+        let release_addr = CORE_RELEASE_ADDR[core] as *mut usize;
+        core::ptr::write_volatile(release_addr, secondary_entry as usize);
+        core::arch::asm!("sev");
+    }
+
+    while CORES_ONLINE.load(Ordering::Acquire) != NUM_CORES {
+        core::hint::spin_loop();
+    }
+}
+
+/// Entry point for secondary cores, reached after `release_secondary_cores` pokes
+/// the spin-table and they branch out of the firmware-provided wait loop.
+unsafe extern "C" fn secondary_entry() -> ! {
+    CORES_ONLINE.fetch_add(1, Ordering::AcqRel);
+
+    let stack_top = SECONDARY_STACKS[current_core_id() - 1].0.as_ptr() as u64
+        + SECONDARY_STACK_SIZE as u64;
+    switch_from_el2_to_el1(stack_top, secondary_main as *const ());
+    aarch64_cpu::asm::eret()
+}
+
+/// Returns the 0-based core index from `MPIDR_EL1.Aff0`.
+pub fn current_core_id() -> usize {
+    (MPIDR_EL1.get() & 0b11) as usize
+}
+
+fn secondary_main() -> ! {
+    unsafe {
+        gic::init_gic().ok();
+        crate::exception::handler_init();
+        crate::exception::enable_irq();
+    }
+
+    loop {
+        aarch64_cpu::asm::wfe();
+    }
+}
+
+/// Programs `GICD_ITARGETSR` so `irq` is only ever delivered to the cores named in
+/// `core_mask` (bit N set == core N may receive it).
+///
+/// # Safety
+///
+/// Must only be called after the GIC distributor has been initialized.
+pub unsafe fn set_irq_affinity(irq: gic::IRQNum, core_mask: u8) {
+    gic::set_irq_target_list(irq, core_mask);
+}
@@ -0,0 +1,150 @@
+//! The EL1 side of the SVC-based syscall ABI. `libmei::syscall` is the EL0 side:
+//! typed wrappers around `svc #imm` that agree with [`dispatch`] on numbering and
+//! calling convention (`gpr[0..=5]` as arguments, return value written back into
+//! `gpr[0]`). `exception::handle_syscall` is the only caller -- it decodes the
+//! trap and hands the syscall number and argument registers straight through,
+//! whether the `svc` came from an EL0 task or, via [`yield_now`], from EL1
+//! itself.
+//!
+//! Every number maps to a required `sched::Operation`
+//! ([`required_operation`]), checked against the calling task's
+//! `sched::Profile` before the matching handler runs -- a `spawn_user` task
+//! whose profile doesn't list the operation gets `Error::OperationNotPermitted`
+//! instead. That only gates *which* syscalls a task may call; any syscall that
+//! also takes a caller-supplied pointer (currently just [`nr::WRITE`]) additionally
+//! runs it through `sched::current_task_validate_read` in [`checked_dispatch`]
+//! first, so an allowed call still can't be pointed at memory outside the
+//! caller's own mappings.
+
+use crate::{
+    error::{Error, Result},
+    exception::ExceptionContext,
+    print, println,
+    sched::{self, Operation},
+    uart,
+};
+
+/// Syscall numbers, matching the `svc` immediate `libmei::syscall`'s wrappers
+/// encode and `EsrEL1::svc_imm`, the SVC64 ISS decode, extracts.
+mod nr {
+    pub const WRITE: u16 = 0;
+    pub const EXIT: u16 = 1;
+    pub const YIELD: u16 = 2;
+}
+
+/// EL1-side trigger for [`nr::YIELD`], used by `sched::yield_now` so a kernel
+/// task can cooperatively yield the same way `libmei::syscall::yield_now` does
+/// from EL0 -- both land in `sys_yield` via the identical `svc` trap path.
+pub(crate) fn yield_now() {
+    unsafe {
+        core::arch::asm!("svc {nr}", nr = const nr::YIELD, options(nostack));
+    }
+}
+
+/// Status an EL0 task reports through the `exit` syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum ExitCode {
+    Success = 0,
+    Failure = 1,
+}
+
+/// The [`Operation`] a syscall number requires its caller's [`sched::Profile`]
+/// to permit, checked against `sched::current_task_permits` before the
+/// handler runs. `None` for numbers `dispatch` doesn't recognize -- they fall
+/// through to the "unknown syscall" `u64::MAX` unconditionally, the same as
+/// before any profile existed.
+fn required_operation(number: u16) -> Option<Operation> {
+    match number {
+        nr::WRITE => Some(Operation::Write),
+        nr::EXIT => Some(Operation::Exit),
+        nr::YIELD => Some(Operation::Yield),
+        _ => None,
+    }
+}
+
+/// Dispatches one `svc` trap to its handler and returns the value to write back
+/// into `gpr[0]`. Unknown syscall numbers, and calls the caller's profile
+/// doesn't permit, both return `u64::MAX` -- the latter logged via its
+/// `Error::OperationNotPermitted` first, so a denied call is distinguishable in
+/// the console from a handler that legitimately returns `u64::MAX` only by
+/// that log line, same as any other best-effort kernel diagnostic. Takes `ec`
+/// (rather than just the decoded `number`/`args`) for `sys_yield`'s benefit and
+/// to look up the calling task's profile -- every other handler ignores it.
+pub(crate) fn dispatch(ec: &mut ExceptionContext, number: u16, args: [u64; 6]) -> u64 {
+    match checked_dispatch(ec, number, args) {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Rejected syscall {number}: {e}");
+            u64::MAX
+        }
+    }
+}
+
+fn checked_dispatch(ec: &mut ExceptionContext, number: u16, args: [u64; 6]) -> Result<u64> {
+    if let Some(op) = required_operation(number) {
+        if !sched::current_task_permits(ec, op) {
+            return Err(Error::OperationNotPermitted);
+        }
+    }
+
+    if number == nr::WRITE {
+        let (buf, len) = (args[1] as usize, args[2] as usize);
+        if !sched::current_task_validate_read(ec, buf, len) {
+            return Err(Error::InvalidUserRange(buf, len));
+        }
+    }
+
+    Ok(match number {
+        nr::WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        nr::EXIT => sys_exit(args[0]),
+        nr::YIELD => sys_yield(ec),
+        _ => u64::MAX,
+    })
+}
+
+/// Writes the `len` bytes at `buf` to the console. `fd` is accepted but ignored --
+/// there's only one console until a real file-descriptor table exists.
+///
+/// `checked_dispatch` has already run `buf`/`len` through
+/// `sched::current_task_validate_read` before this is called, so by the time
+/// `from_raw_parts` runs, the whole range is known to fall inside the calling
+/// task's own readable mappings -- an EL0 caller can't point this at
+/// arbitrary kernel memory.
+fn sys_write(_fd: u64, buf: *const u8, len: u64) -> u64 {
+    let bytes = unsafe { core::slice::from_raw_parts(buf, len as usize) };
+    match core::str::from_utf8(bytes) {
+        Ok(s) => {
+            print!("{s}");
+            len
+        }
+        Err(_) => u64::MAX,
+    }
+}
+
+/// Terminates the calling EL0 task. There is no process table yet to remove an
+/// entry from, so this reports the exit and halts the core, the same recovery-less
+/// ending `panic::halt` gives an unhandled exception.
+fn sys_exit(code: u64) -> u64 {
+    let exit_code = if code == ExitCode::Success as u64 {
+        ExitCode::Success
+    } else {
+        ExitCode::Failure
+    };
+
+    println!("\nEL0 task exited with {exit_code:?}");
+    uart::flush();
+
+    loop {
+        aarch64_cpu::asm::wfe();
+    }
+}
+
+/// Hands the core to `sched`, which saves the caller's context (already sitting
+/// at `ec`, wherever its own stack happens to be), picks the next `Runnable`
+/// task, and queues the switch for the trampoline to carry out on the way back
+/// out of this trap.
+fn sys_yield(ec: &mut ExceptionContext) -> u64 {
+    sched::schedule(ec);
+    0
+}
@@ -1,10 +1,28 @@
 use core::time::Duration;
 
-use aarch64_cpu::registers::{CNTP_CTL_EL0, CNTP_TVAL_EL0};
+use aarch64_cpu::registers::{CNTP_CTL_EL0, CNTP_TVAL_EL0, CNTPCT_EL0, CNTV_CTL_EL0, CNTV_TVAL_EL0};
 use spin::Mutex;
 use tock_registers::interfaces::{Readable, Writeable};
 
-use crate::{exception::ExceptionContext, println};
+use crate::{
+    exception::{enable_fiq, ExceptionContext},
+    gic::{
+        enable_irq, promote_to_fiq, register_fiq_handler, register_interrupt_handler, IRQHandler,
+        IRQNum,
+    },
+    println, sched,
+};
+
+/// GIC-400 PPI interrupt ID for the non-secure physical timer (PPI 14, i.e.
+/// GIC ID 16 + 14).
+const TIMER_IRQ_NUM: IRQNum = 30;
+
+/// GIC-400 PPI interrupt ID for the non-secure virtual timer (PPI 11, i.e.
+/// GIC ID 16 + 11). Kept on the ordinary Group 1 (IRQ) path, unlike
+/// `TIMER_IRQ_NUM` -- `sched::schedule` runs with IRQs masked on entry like any
+/// other handler and has no reason to preempt a FIQ, so there's nothing to gain
+/// from the physical timer's promotion here.
+const VIRTUAL_TIMER_IRQ_NUM: IRQNum = 27;
 
 /// Will be initialized by ASM (boot.s)
 #[no_mangle]
@@ -23,49 +41,122 @@ fn compute_timer_counter_value(duration: core::time::Duration) -> u64 {
     (freq * duration.as_secs_f64()) as u64
 }
 
+/// Busy-waits for `duration` by polling the physical counter (`CNTPCT_EL0`) against
+/// `TIMER_FREQ`. For short, sub-interrupt-tick delays (e.g. bit-banged bus clock
+/// half-periods) where blocking on the 10ms `TIMER_INTERVAL` tick isn't precise
+/// enough.
+pub(crate) fn busy_wait(duration: Duration) {
+    let cycles = compute_timer_counter_value(duration);
+    let start = CNTPCT_EL0.get();
+    while CNTPCT_EL0.get().wrapping_sub(start) < cycles {
+        core::hint::spin_loop();
+    }
+}
+
 fn set_timer_interval_count() {
     let timer = *TIMER_INTERVAL_CNT;
     // Set timer interval
     CNTP_TVAL_EL0.set(timer);
 }
 
+static TICKS: Mutex<u64> = Mutex::new(0);
+
+/// The physical timer's tick count since boot, one per `TIMER_INTERVAL` (10ms).
+/// `klog` prefixes every log line with this, giving a coarse, monotonically
+/// increasing timestamp without reading `CNTPCT_EL0` (and its raw frequency)
+/// directly.
+pub(crate) fn ticks() -> u64 {
+    *TICKS.lock()
+}
+
+#[derive(Default)]
+struct TimerInterruptHandler;
+
+impl IRQHandler for TimerInterruptHandler {
+    fn get_irq_pending_bit_num(&self) -> IRQNum {
+        TIMER_IRQ_NUM
+    }
+
+    fn handle(&self, _ec: &mut ExceptionContext) {
+        let tick_count;
+        {
+            let mut ticks = TICKS.lock();
+            tick_count = *ticks;
+            *ticks += 1;
+        }
+        if tick_count % TICKS_PER_SECOND == 0 {
+            println!(
+                "Time Elapsed Since Boot = {} s",
+                tick_count / TICKS_PER_SECOND
+            );
+        }
+        set_timer_interval_count();
+    }
+}
+
+lazy_static! {
+    static ref IRQ_HANDLER: TimerInterruptHandler = TimerInterruptHandler::default();
+}
+
+/// Ticks the scheduler: programs the next interval and hands `ec` to
+/// `sched::schedule`, which saves the preempted task's context, picks the next
+/// `Runnable` one, and queues the switch for the trampoline to carry out.
+#[derive(Default)]
+struct VirtualTimerInterruptHandler;
+
+impl IRQHandler for VirtualTimerInterruptHandler {
+    fn get_irq_pending_bit_num(&self) -> IRQNum {
+        VIRTUAL_TIMER_IRQ_NUM
+    }
+
+    fn handle(&self, ec: &mut ExceptionContext) {
+        CNTV_TVAL_EL0.set(*TIMER_INTERVAL_CNT);
+        sched::schedule(ec);
+    }
+}
+
+lazy_static! {
+    static ref VIRTUAL_TIMER_IRQ_HANDLER: VirtualTimerInterruptHandler =
+        VirtualTimerInterruptHandler::default();
+}
+
 /// .
 ///
 /// # Safety
 ///
-/// Init Timer module
+/// Init Timer module.
+///
+/// The timer is promoted to Group 0 (FIQ) rather than left on the regular IRQ
+/// path: it is the one source in the system that must keep ticking even while a
+/// driver has IRQs masked (e.g. the UART's TX ring under lock), so it gets the
+/// GIC's higher-priority, separately-maskable interrupt class.
 pub unsafe fn init_timer() {
     set_timer_interval_count();
 
     // Enable timer and timer interrupt
     CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::CLEAR);
 
-    let cntp_el0 = 0x40000040 as *mut u64;
-    core::ptr::write_volatile(cntp_el0, 1 << 1);
-}
-
-pub(crate) fn is_timer_irq() -> bool {
-    let cntp_status_el0 = 0x40000060 as *mut u64;
-    unsafe {
-        core::ptr::read_volatile(cntp_status_el0) & (1 << 1) != 0
-            && CNTP_CTL_EL0.is_set(CNTP_CTL_EL0::ISTATUS)
-    }
+    register_fiq_handler(&*IRQ_HANDLER);
+    promote_to_fiq(TIMER_IRQ_NUM);
+    enable_fiq();
 }
 
-static TICKS: Mutex<u64> = Mutex::new(0);
+/// .
+///
+/// # Safety
+///
+/// Init the scheduler's preemption tick. Must run after `gic::init_gic`, and
+/// after `exception::handler_init`/`exception::enable_irq` so the IRQ it
+/// schedules has a vector table and an unmasked IRQ line to land on.
+///
+/// Left as a regular Group 1 IRQ rather than promoted to FIQ like
+/// `init_timer`'s physical timer: a missed reschedule tick just means the
+/// current task runs a little longer, which is harmless, unlike a UART TX ring
+/// stalling because its own tick got masked out.
+pub unsafe fn init_scheduler_timer() {
+    CNTV_TVAL_EL0.set(*TIMER_INTERVAL_CNT);
+    CNTV_CTL_EL0.write(CNTV_CTL_EL0::ENABLE::SET + CNTV_CTL_EL0::IMASK::CLEAR);
 
-pub(crate) fn handle_timer_irq(_ec: &mut ExceptionContext) {
-    let tick_count;
-    {
-        let mut ticks = TICKS.lock();
-        tick_count = *ticks;
-        *ticks += 1;
-    }
-    if tick_count % TICKS_PER_SECOND == 0 {
-        println!(
-            "Time Elapsed Since Boot = {} s",
-            tick_count / TICKS_PER_SECOND
-        );
-    }
-    set_timer_interval_count();
+    register_interrupt_handler(&*VIRTUAL_TIMER_IRQ_HANDLER);
+    enable_irq(VIRTUAL_TIMER_IRQ_NUM).expect("enabling the scheduler tick IRQ must not fail");
 }
@@ -3,9 +3,12 @@ use tock_registers::interfaces::{Readable, Writeable};
 use tock_registers::registers::{ReadOnly, ReadWrite, WriteOnly};
 use tock_registers::{register_bitfields, register_structs};
 
+use crate::address::Address;
+use crate::address_map::PL011_UART;
 use crate::exception::ExceptionContext;
 use crate::gic::{enable_irq, IRQHandler};
 use crate::gic::{register_interrupt_handler, IRQNum};
+use crate::vm::phy2virt;
 
 register_structs! {
     Registers {
@@ -37,50 +40,192 @@ struct Pl011Uart(&'static mut Registers);
 
 impl Default for Pl011Uart {
     fn default() -> Self {
-        unsafe {
-            Self(
-                (crate::mimo::PL011_UART_BASE as *mut Registers)
-                    .as_mut()
-                    .unwrap(),
-            )
+        let virt = phy2virt(PL011_UART.base).expect("PL011 UART region must be mapped");
+
+        unsafe { Self((virt.as_mut_ptr() as *mut Registers).as_mut().unwrap()) }
+    }
+}
+
+/// GIC-400 SPI interrupt ID for PL011 UART0 (SPI 121, i.e. GIC ID 32 + 121).
+const UART_IRQ_NUM: IRQNum = 153;
+
+/// PL011 UART reference clock on rpi3 (also matches QEMU's `raspi3b` machine).
+const UART_CLK_HZ: u32 = 48_000_000;
+/// Default console baud rate.
+const UART_BAUD: u32 = 115_200;
+
+/// Size of the software TX ring buffer feeding the PL011 TX FIFO.
+const TX_BUF_LEN: usize = 256;
+
+/// Fixed-size byte ring buffer used to decouple `print!`/`println!` callers from the
+/// PL011 TX FIFO, so they don't busy-wait for the UART to drain.
+struct TxRing {
+    buf: [u8; TX_BUF_LEN],
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl Default for TxRing {
+    fn default() -> Self {
+        Self {
+            buf: [0; TX_BUF_LEN],
+            head: 0,
+            tail: 0,
+            len: 0,
         }
     }
 }
 
-const UART_IRQ_NUM: IRQNum = 57;
-const UART_IRQ_PENDING_BIT_NUM: IRQNum = 19;
+impl TxRing {
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == TX_BUF_LEN
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[self.tail] = byte;
+        self.tail = (self.tail + 1) % TX_BUF_LEN;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % TX_BUF_LEN;
+        self.len -= 1;
+        Some(byte)
+    }
+}
 
 impl Pl011Uart {
     fn init(&mut self) {
         self.0.cr.set(0);
-        self.0.ibrd.set(26);
-        self.0.fbrd.set(0);
+        self.set_baud(UART_CLK_HZ, UART_BAUD);
 
-        self.0.lcr.write(LineControl::WLEN.val(2));
+        self.0.lcr.write(LineControl::WLEN.val(2) + LineControl::FEN::SET);
         self.0.imsc.write(InterruptMaskSetClear::RXIM::SET);
         self.0
             .cr
             .write(Control::ENABLE::SET + Control::RXE::SET + Control::TXE::SET);
     }
 
+    /// Computes and latches the PL011 baud-rate divisors for the given UART reference
+    /// clock. Must be called before the line-control write so the new divisors take
+    /// effect (the PL011 only samples `IBRD`/`FBRD` on an `LCR_H` write).
+    fn set_baud(&mut self, uart_clk_hz: u32, baud: u32) {
+        let divider = (uart_clk_hz as u64 * 4) / baud as u64;
+        let ibrd = (divider >> 6) as u32;
+        let fbrd = (divider & 0x3f) as u32;
+
+        self.0.ibrd.write(IntegerBaudRate::BAUD_DIVINT.val(ibrd));
+        self.0.fbrd.write(FractionalBaudRate::BAUD_DIVFRAC.val(fbrd));
+    }
+
     fn read_byte(&mut self) -> u8 {
         self.0.dr.get() as u8
     }
 
-    fn write_byte(&mut self, byte: u8) {
+    /// Busy-waits until the FIFO can accept a byte and writes it directly,
+    /// bypassing the TX ring. Used by the panic handler and other diagnostics
+    /// that must survive a wedged IRQ path.
+    fn write_byte_blocking(&mut self, byte: u8) {
         while self.0.fr.is_set(Flag::BUSY) {}
         self.0.dr.set(byte as u32)
     }
 
-    fn write_str(&mut self, s: &str) {
+    fn write_str_blocking(&mut self, s: &str) {
         for char in s.as_bytes() {
-            self.write_byte(*char);
+            self.write_byte_blocking(*char);
         }
     }
 
     fn has_recv_irq(&self) -> bool {
         self.0.mis.is_set(MaskedInterruptStatus::RXMIS)
     }
+
+    fn has_xmit_irq(&self) -> bool {
+        self.0.mis.is_set(MaskedInterruptStatus::TXMIS)
+    }
+
+    fn tx_fifo_full(&self) -> bool {
+        self.0.fr.is_set(Flag::TXFF)
+    }
+
+    fn rx_fifo_empty(&self) -> bool {
+        self.0.fr.is_set(Flag::RXFE)
+    }
+
+    fn enable_txim(&mut self) {
+        self.0.imsc.write(InterruptMaskSetClear::RXIM::SET + InterruptMaskSetClear::TXIM::SET);
+    }
+
+    fn disable_txim(&mut self) {
+        self.0.imsc.write(InterruptMaskSetClear::RXIM::SET);
+    }
+}
+
+/// Size of the software RX ring buffer the RX interrupt handler drains the
+/// PL011 RX FIFO into.
+const RX_BUF_LEN: usize = 256;
+
+/// Fixed-size byte ring buffer used to decouple `getchar`/`try_getchar` callers
+/// from the PL011 RX FIFO, so they don't busy-wait on `UART0_FR`. Mirrors `TxRing`.
+struct RxRing {
+    buf: [u8; RX_BUF_LEN],
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl Default for RxRing {
+    fn default() -> Self {
+        Self {
+            buf: [0; RX_BUF_LEN],
+            head: 0,
+            tail: 0,
+            len: 0,
+        }
+    }
+}
+
+impl RxRing {
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn is_full(&self) -> bool {
+        self.len == RX_BUF_LEN
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.buf[self.tail] = byte;
+        self.tail = (self.tail + 1) % RX_BUF_LEN;
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        let byte = self.buf[self.head];
+        self.head = (self.head + 1) % RX_BUF_LEN;
+        self.len -= 1;
+        Some(byte)
+    }
 }
 
 lazy_static! {
@@ -89,6 +234,8 @@ lazy_static! {
 
 struct UARTAccessor {
     uart: spin::Mutex<Pl011Uart>,
+    tx_ring: spin::Mutex<TxRing>,
+    rx_ring: spin::Mutex<RxRing>,
 }
 
 impl Default for UARTAccessor {
@@ -98,29 +245,99 @@ impl Default for UARTAccessor {
 
         Self {
             uart: spin::Mutex::new(uart),
+            tx_ring: spin::Mutex::new(TxRing::default()),
+            rx_ring: spin::Mutex::new(RxRing::default()),
+        }
+    }
+}
+
+impl UARTAccessor {
+    /// Enqueues `s` onto the TX ring and kicks off interrupt-driven draining,
+    /// returning without waiting for the FIFO to actually send the bytes.
+    fn enqueue(&self, s: &str) {
+        let mut uart = self.uart.lock();
+        let mut ring = self.tx_ring.lock();
+
+        for byte in s.as_bytes() {
+            // Ring is full: drain a byte straight into the FIFO to make room rather
+            // than blocking the caller on the whole string.
+            while ring.is_full() {
+                if !uart.tx_fifo_full() {
+                    if let Some(byte) = ring.pop() {
+                        uart.0.dr.set(byte as u32);
+                    }
+                } else {
+                    break;
+                }
+            }
+            ring.push(*byte);
+        }
+
+        uart.enable_txim();
+    }
+
+    /// Pops the next received byte off the RX ring, if any is buffered.
+    fn try_getchar(&self) -> Option<u8> {
+        self.rx_ring.lock().pop()
+    }
+
+    /// Blocks until every byte currently queued has been handed to the FIFO.
+    /// Used by the panic handler to guarantee diagnostics are flushed.
+    fn flush(&self) {
+        loop {
+            let mut uart = self.uart.lock();
+            let mut ring = self.tx_ring.lock();
+            if ring.is_empty() {
+                return;
+            }
+            if !uart.tx_fifo_full() {
+                if let Some(byte) = ring.pop() {
+                    uart.0.dr.set(byte as u32);
+                }
+            }
         }
     }
 }
 
 impl IRQHandler for UARTAccessor {
     fn get_irq_pending_bit_num(&self) -> IRQNum {
-        UART_IRQ_PENDING_BIT_NUM
+        UART_IRQ_NUM
     }
 
     fn handle(&self, _ec: &mut ExceptionContext) {
         let mut uart = self.uart.lock();
-        if !uart.has_recv_irq() {
-            return;
-        }
-        let char = uart.read_byte();
-        if char == b'\r' {
-            uart.write_byte(b'\n');
-        } else {
-            uart.write_byte(char);
+
+        if uart.has_xmit_irq() {
+            let mut ring = self.tx_ring.lock();
+            while !uart.tx_fifo_full() {
+                match ring.pop() {
+                    Some(byte) => uart.0.dr.set(byte as u32),
+                    None => break,
+                }
+            }
+            uart.0.icr.write(InterruptClear::TXIC::SET);
+            if ring.is_empty() {
+                uart.disable_txim();
+            }
         }
 
-        // Clear Uart interrupt
-        uart.0.icr.write(InterruptClear::RXIC::SET);
+        if uart.has_recv_irq() {
+            let mut ring = self.rx_ring.lock();
+
+            while !uart.rx_fifo_empty() {
+                let byte = uart.read_byte();
+                if byte == b'\r' {
+                    uart.write_byte_blocking(b'\n');
+                } else {
+                    uart.write_byte_blocking(byte);
+                }
+
+                ring.push(byte);
+            }
+
+            // Clear Uart interrupt
+            uart.0.icr.write(InterruptClear::RXIC::SET);
+        }
     }
 }
 
@@ -134,9 +351,57 @@ pub unsafe fn enable() {
     enable_irq(UART_IRQ_NUM);
 }
 
+/// Blocks until every byte enqueued for transmission has reached the FIFO.
+///
+/// Intended for the panic handler, where diagnostics must survive even when
+/// interrupts are wedged.
+pub fn flush() {
+    IRQ_HANDLER.flush();
+}
+
+/// Non-blocking receive: returns the next byte the RX interrupt handler has
+/// buffered, or `None` if nothing has arrived yet.
+pub fn try_getchar() -> Option<u8> {
+    IRQ_HANDLER.try_getchar()
+}
+
+/// Blocks until a byte has been received. Waits on `wfi` between checks of
+/// the RX ring rather than busy-waiting on `UART0_FR` directly, so the core
+/// stays idle until the RX IRQ handler actually has something for us.
+pub fn getchar() -> u8 {
+    loop {
+        if let Some(byte) = try_getchar() {
+            return byte;
+        }
+
+        aarch64_cpu::asm::wfi();
+    }
+}
+
+/// Formats and writes `args` straight to the PL011 FIFO, busy-waiting on each byte
+/// rather than going through the interrupt-driven TX ring.
+///
+/// Intended for the panic handler: diagnostics printed this way survive even if the
+/// fault wedged the TX ring or its IRQ (e.g. by panicking mid-`enqueue` with the
+/// ring mutex held).
+pub fn print_blocking(args: core::fmt::Arguments) {
+    use core::fmt::Write as _;
+    match IRQ_HANDLER.uart.try_lock() {
+        Some(mut uart) => {
+            let _ = uart.write_fmt(args);
+        }
+        // `enqueue` holds the register lock for as long as it fills the ring, so a
+        // panic raised inside it leaves that lock taken: write through a second
+        // handle to the same registers instead of spinning on it.
+        None => {
+            let _ = Pl011Uart::default().write_fmt(args);
+        }
+    }
+}
+
 impl Write for Pl011Uart {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        self.write_str(s);
+        self.write_str_blocking(s);
         Ok(())
     }
 }
@@ -154,10 +419,21 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
-/// Prints the given formatted string to the UART0 instance.
+/// Prints the given formatted string to the UART0 instance, enqueueing it onto the
+/// interrupt-driven TX ring rather than busy-waiting on the FIFO.
 #[doc(hidden)]
 pub fn _print(args: core::fmt::Arguments) {
-    IRQ_HANDLER.uart.lock().write_fmt(args).unwrap();
+    use core::fmt::Write as _;
+
+    struct RingWriter;
+    impl Write for RingWriter {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            IRQ_HANDLER.enqueue(s);
+            Ok(())
+        }
+    }
+
+    RingWriter.write_fmt(args).unwrap();
 }
 
 // UART Register Fields:
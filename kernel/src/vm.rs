@@ -1,8 +1,11 @@
+use spin::Mutex;
+
 use crate::{
     address::{Address, PhysicalAddress, VirtualAddress},
     address_map::{LOCAL_REGISTERS_BASE, LOCAL_REGISTERS_END, PERIPHERALS_BASE, PERIPHERALS_END},
     error::{Error, Result},
     kimage::{kernel_phy_range, kernel_stack_range},
+    mmu::{AddressSpace, Mapping},
 };
 
 // From https://lwn.net/Articles/718895/
@@ -33,20 +36,487 @@ lazy_static! {
         VirtualAddress::new(0x0000_0000_0000_0000).unwrap();
 }
 
-/// Works only for statically mapped physical addresses
+/// Access permissions for a [`MemoryMap`], taken directly from an ELF segment's
+/// R/W/X program-header flags (see [`crate::kimage::kernel_segments`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Permissions {
+    pub const fn from_elf_flags(flags: u32) -> Self {
+        Self {
+            read: flags & crate::elf::PF_R != 0,
+            write: flags & crate::elf::PF_W != 0,
+            execute: flags & crate::elf::PF_X != 0,
+        }
+    }
+}
+
+/// A physical range and the permissions it should be mapped with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub phys: PhysicalAddress,
+    pub size: usize,
+    pub perms: Permissions,
+}
+
+/// Works for statically mapped physical addresses, falling back to the dynamic
+/// MMIO remap table (see `map_mmio`) for anything that's been remapped on demand.
 pub fn phy2virt(paddr: PhysicalAddress) -> Result<VirtualAddress> {
     let peripherals_range = PERIPHERALS_BASE..PERIPHERALS_END;
     let local_peripherals_range = LOCAL_REGISTERS_BASE..LOCAL_REGISTERS_END;
     let kernel_image_range = kernel_phy_range();
     let kernel_stack_range = kernel_stack_range();
 
-    if !peripherals_range.contains(&paddr)
-        && !local_peripherals_range.contains(&paddr)
-        && !kernel_image_range.contains(&paddr)
-        && !kernel_stack_range.contains(&paddr)
+    if peripherals_range.contains(&paddr)
+        || local_peripherals_range.contains(&paddr)
+        || kernel_image_range.contains(&paddr)
+        || kernel_stack_range.contains(&paddr)
+    {
+        return Ok(*KERNEL_VIRT_ADDRESS_BASE + paddr.as_raw_ptr());
+    }
+
+    MMIO_REMAP
+        .lock()
+        .lookup(paddr)
+        .ok_or(Error::PhysicalAddressNotStaticallyMapped(paddr))
+}
+
+/// Page size assumed throughout the static and dynamic mapping schemes (4KB
+/// granule, matching the translation-table layout documented above).
+pub(crate) const PAGE_SIZE: usize = 4096;
+
+/// Maximum number of distinct MMIO remaps the window can hold. Raised if a board
+/// ends up needing more concurrently-mapped devices than this.
+const MAX_MMIO_MAPS: usize = 32;
+
+/// Reserved 4 GiB VA window in the kernel half of the address space, ending at
+/// `MMIO_WINDOW_END` (4 GiB below the top of the address space), carved out for dynamic MMIO remapping so device drivers aren't limited to the
+/// board's up-front identity-visible ranges. Grown downward from the top via a
+/// bump cursor, one page (or run of pages) per `map_mmio` call.
+const MMIO_WINDOW_SIZE: usize = 0x1_0000_0000;
+const MMIO_WINDOW_END: usize = 0xFFFF_FFFF_0000_0000;
+const MMIO_WINDOW_BASE: usize = MMIO_WINDOW_END - MMIO_WINDOW_SIZE;
+
+lazy_static! {
+    static ref MMIO_REMAP: Mutex<MmioRemapTable> = Mutex::new(MmioRemapTable::new());
+}
+
+/// A single active entry in the dynamic MMIO remap window: `num_pages` pages
+/// starting at `phy_page` are visible starting at `virt_page`.
+#[derive(Debug, Clone, Copy)]
+struct MmioMapping {
+    phy_page: PhysicalAddress,
+    virt_page: VirtualAddress,
+    num_pages: usize,
+}
+
+impl MmioMapping {
+    fn contains(&self, page_base: usize) -> bool {
+        let start = self.phy_page.as_raw_ptr();
+        let end = start + self.num_pages * PAGE_SIZE;
+        (start..end).contains(&page_base)
+    }
+}
+
+struct MmioRemapTable {
+    maps: [Option<MmioMapping>; MAX_MMIO_MAPS],
+    count: usize,
+    /// Next VA to hand out, descending from `MMIO_WINDOW_END`.
+    bump_cursor: usize,
+}
+
+impl MmioRemapTable {
+    const fn new() -> Self {
+        Self {
+            maps: [None; MAX_MMIO_MAPS],
+            count: 0,
+            bump_cursor: MMIO_WINDOW_END,
+        }
+    }
+
+    fn active_maps(&self) -> impl Iterator<Item = &MmioMapping> {
+        self.maps[..self.count].iter().flatten()
+    }
+
+    /// Resolves `paddr` against already-active mappings, if any.
+    fn lookup(&self, paddr: PhysicalAddress) -> Option<VirtualAddress> {
+        let page_base = page_align_down(paddr.as_raw_ptr());
+        let offset = paddr.as_raw_ptr() - page_base;
+
+        self.active_maps()
+            .find(|map| map.contains(page_base))
+            .map(|map| map.virt_page + (page_base - map.phy_page.as_raw_ptr()) + offset)
+    }
+
+    /// Reserves `num_pages` pages of VA space for `phy_page`, or returns
+    /// `Error::VMMapExists` with the already-assigned VA if it's covered by an
+    /// existing mapping already.
+    fn insert(&mut self, phy_page: PhysicalAddress, num_pages: usize) -> Result<VirtualAddress> {
+        if let Some(existing) = self.active_maps().find(|map| map.contains(phy_page.as_raw_ptr()))
+        {
+            return Err(Error::VMMapExists(existing.virt_page));
+        }
+
+        if self.count == MAX_MMIO_MAPS {
+            return Err(Error::MmioWindowExhausted);
+        }
+
+        let len = num_pages * PAGE_SIZE;
+        let new_cursor = self
+            .bump_cursor
+            .checked_sub(len)
+            .filter(|&va| va >= MMIO_WINDOW_BASE)
+            .ok_or(Error::MmioWindowExhausted)?;
+
+        let virt_page = VirtualAddress::new(new_cursor).map_err(|_| Error::MmioWindowExhausted)?;
+        self.bump_cursor = new_cursor;
+
+        self.maps[self.count] = Some(MmioMapping {
+            phy_page,
+            virt_page,
+            num_pages,
+        });
+        self.count += 1;
+
+        Ok(virt_page)
+    }
+}
+
+const fn page_align_down(addr: usize) -> usize {
+    addr & !(PAGE_SIZE - 1)
+}
+
+/// Maximum number of distinct demand-paged regions the kernel can track at once
+/// (lazily-grown stacks, COW segments, ...).
+const MAX_DEMAND_REGIONS: usize = 16;
+
+/// A VA range that has been promised to a caller but has no backing frames yet --
+/// the first access to any page in it takes a translation fault, which
+/// `exception::try_recover_page_fault` resolves by calling back into `mmu` to
+/// allocate and map the faulting page with `perms`.
+#[derive(Debug, Clone, Copy)]
+struct DemandRegion {
+    base: VirtualAddress,
+    num_pages: usize,
+    perms: Permissions,
+}
+
+impl DemandRegion {
+    fn contains(&self, virt: VirtualAddress) -> bool {
+        let start = self.base.as_raw_ptr();
+        let end = start + self.num_pages * PAGE_SIZE;
+        (start..end).contains(&virt.as_raw_ptr())
+    }
+}
+
+struct DemandRegionTable {
+    regions: [Option<DemandRegion>; MAX_DEMAND_REGIONS],
+    count: usize,
+}
+
+impl DemandRegionTable {
+    const fn new() -> Self {
+        Self {
+            regions: [None; MAX_DEMAND_REGIONS],
+            count: 0,
+        }
+    }
+
+    fn active_regions(&self) -> impl Iterator<Item = &DemandRegion> {
+        self.regions[..self.count].iter().flatten()
+    }
+
+    fn insert(&mut self, region: DemandRegion) -> Result<()> {
+        if self.count == MAX_DEMAND_REGIONS {
+            return Err(Error::DemandRegionTableFull);
+        }
+
+        self.regions[self.count] = Some(region);
+        self.count += 1;
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref DEMAND_REGIONS: Mutex<DemandRegionTable> = Mutex::new(DemandRegionTable::new());
+}
+
+/// Marks `num_pages` pages starting at `base` as demand-paged, to be mapped
+/// with `flags` once backed: no physical frame is allocated until the first
+/// access to one of them faults through the sync exception path.
+pub fn map_lazy(base: VirtualAddress, num_pages: usize, flags: Permissions) -> Result<()> {
+    DEMAND_REGIONS.lock().insert(DemandRegion {
+        base,
+        num_pages,
+        perms: flags,
+    })
+}
+
+/// Looks up the demand-paged region (if any) covering `virt`, returning the
+/// permissions it should be mapped with. Used by `exception::try_recover_page_fault`
+/// to decide whether a translation fault is recoverable.
+pub(crate) fn demand_region_for(virt: VirtualAddress) -> Option<Permissions> {
+    DEMAND_REGIONS
+        .lock()
+        .active_regions()
+        .find(|region| region.contains(virt))
+        .map(|region| region.perms)
+}
+
+/// Maximum number of distinct VA ranges `fork` can mark copy-on-write at once.
+const MAX_COW_REGIONS: usize = 16;
+
+/// A VA range `fork` has made copy-on-write: every page in it is currently
+/// mapped read-only into at least two address spaces, sharing the same
+/// physical frame tracked in `COW_FRAMES`. `perms` is the full, writable
+/// permissions the page should be remapped with once a write fault gives the
+/// faulting side its own private copy.
+#[derive(Debug, Clone, Copy)]
+struct CowRegion {
+    base: VirtualAddress,
+    num_pages: usize,
+    perms: Permissions,
+}
+
+impl CowRegion {
+    fn contains(&self, virt: VirtualAddress) -> bool {
+        let start = self.base.as_raw_ptr();
+        let end = start + self.num_pages * PAGE_SIZE;
+        (start..end).contains(&virt.as_raw_ptr())
+    }
+}
+
+struct CowRegionTable {
+    regions: [Option<CowRegion>; MAX_COW_REGIONS],
+    count: usize,
+}
+
+impl CowRegionTable {
+    const fn new() -> Self {
+        Self {
+            regions: [None; MAX_COW_REGIONS],
+            count: 0,
+        }
+    }
+
+    fn active_regions(&self) -> impl Iterator<Item = &CowRegion> {
+        self.regions[..self.count].iter().flatten()
+    }
+
+    fn insert(&mut self, region: CowRegion) -> Result<()> {
+        if self.count == MAX_COW_REGIONS {
+            return Err(Error::CowRegionTableFull);
+        }
+
+        self.regions[self.count] = Some(region);
+        self.count += 1;
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref COW_REGIONS: Mutex<CowRegionTable> = Mutex::new(CowRegionTable::new());
+}
+
+/// Marks `num_pages` pages starting at `base` copy-on-write, to be remapped
+/// with `perms` once a write fault against one of them gives the faulting
+/// side its own copy. Only ever called by `fork`, which is what actually
+/// shares the underlying frames -- there's no standalone "make this region
+/// COW" entry point.
+///
+/// A no-op if `base` is already tracked: forking the same parent mapping a
+/// second time (a second child, or a grandchild) re-reads that mapping's
+/// *current* table entry, which a prior `fork` has already downgraded to
+/// read-only -- `perms` passed in on this call would be that downgraded,
+/// no-longer-writable copy. Keeping the first-recorded region instead of
+/// overwriting it both preserves the original, correct restore permissions
+/// and avoids burning a fresh `COW_REGIONS` slot on every repeat fork of the
+/// same mapping.
+fn mark_cow(base: VirtualAddress, num_pages: usize, perms: Permissions) -> Result<()> {
+    let mut regions = COW_REGIONS.lock();
+
+    if regions
+        .active_regions()
+        .any(|region| region.base == base && region.num_pages == num_pages)
     {
-        return Err(Error::PhysicalAddressNotStaticallyMapped(paddr));
+        return Ok(());
+    }
+
+    regions.insert(CowRegion {
+        base,
+        num_pages,
+        perms,
+    })
+}
+
+/// Looks up the COW region (if any) covering `virt`, returning the full
+/// (writable) permissions it should be remapped with. Used by
+/// `exception::try_recover_cow_fault` to decide whether a write-caused
+/// permission fault is recoverable via `mmu::handle_cow_fault`.
+pub(crate) fn cow_region_for(virt: VirtualAddress) -> Option<Permissions> {
+    COW_REGIONS
+        .lock()
+        .active_regions()
+        .find(|region| region.contains(virt))
+        .map(|region| region.perms)
+}
+
+/// Maximum number of physical frames that can be concurrently COW-shared.
+const MAX_COW_FRAMES: usize = 32;
+
+struct CowFrame {
+    phys: PhysicalAddress,
+    refcount: u32,
+}
+
+struct CowFrameTable {
+    frames: [Option<CowFrame>; MAX_COW_FRAMES],
+    count: usize,
+}
+
+impl CowFrameTable {
+    const fn new() -> Self {
+        Self {
+            frames: [None; MAX_COW_FRAMES],
+            count: 0,
+        }
+    }
+
+    fn find_mut(&mut self, phys: PhysicalAddress) -> Option<&mut CowFrame> {
+        self.frames[..self.count]
+            .iter_mut()
+            .flatten()
+            .find(|frame| frame.phys == phys)
     }
 
-    Ok(*KERNEL_VIRT_ADDRESS_BASE + paddr.as_raw_ptr())
+    fn insert(&mut self, frame: CowFrame) -> Result<()> {
+        if self.count == MAX_COW_FRAMES {
+            return Err(Error::CowFrameTableFull);
+        }
+
+        self.frames[self.count] = Some(frame);
+        self.count += 1;
+        Ok(())
+    }
+
+    fn remove(&mut self, phys: PhysicalAddress) {
+        let Some(idx) = self.frames[..self.count]
+            .iter()
+            .position(|frame| matches!(frame, Some(frame) if frame.phys == phys))
+        else {
+            return;
+        };
+
+        self.count -= 1;
+        self.frames[idx] = self.frames[self.count];
+        self.frames[self.count] = None;
+    }
+}
+
+lazy_static! {
+    static ref COW_FRAMES: Mutex<CowFrameTable> = Mutex::new(CowFrameTable::new());
+}
+
+/// Records one more address space sharing `phys`, starting its tracked
+/// refcount at 2 the first time (the original owner plus this new sharer) and
+/// incrementing it on every subsequent share. Called by `fork` once per frame
+/// it hands to the child alongside the parent.
+fn cow_share(phys: PhysicalAddress) -> Result<()> {
+    let mut frames = COW_FRAMES.lock();
+
+    if let Some(frame) = frames.find_mut(phys) {
+        frame.refcount += 1;
+        return Ok(());
+    }
+
+    frames.insert(CowFrame { phys, refcount: 2 })
+}
+
+/// Drops one sharer of `phys` -- called by `mmu::handle_cow_fault` once the
+/// faulting side has copied it onto a private frame of its own and no longer
+/// needs this one. Returns `true` once every other sharer has let go too,
+/// telling the caller it's safe to `phys_alloc::free_pages` the frame;
+/// `false` means at least one other address space still maps it read-only.
+///
+/// A `phys` with no tracked entry is treated as "nothing else references it":
+/// the entry is dropped as soon as only one sharer is left, so the last
+/// sharer's release always takes this path.
+pub(crate) fn cow_release(phys: PhysicalAddress) -> bool {
+    let mut frames = COW_FRAMES.lock();
+
+    let Some(frame) = frames.find_mut(phys) else {
+        return true;
+    };
+
+    frame.refcount -= 1;
+    let remaining = frame.refcount;
+    if remaining <= 1 {
+        frames.remove(phys);
+    }
+
+    remaining == 0
+}
+
+/// Forks `parent` into a brand-new child address space: every page currently
+/// mapped in `parent`'s own (TTBR0) half is shared read-only between `parent`
+/// and the child and marked copy-on-write, so the first write through either
+/// side takes a permission fault that `mmu::handle_cow_fault` resolves by
+/// giving that side its own private copy.
+///
+/// `parent` is remapped read-only too, not just the child -- if it were left
+/// writable, parent and child would silently diverge through the same frame
+/// the moment parent wrote to it, without either side ever faulting.
+pub fn fork(parent: &mut AddressSpace) -> Result<AddressSpace> {
+    let mut child = AddressSpace::new_user_space()?;
+
+    let mappings: alloc::vec::Vec<Mapping> = parent.user_mappings().collect();
+    for mapping in mappings {
+        let shared = Mapping {
+            perms: Permissions {
+                write: false,
+                ..mapping.perms
+            },
+            ..mapping
+        };
+
+        parent.remap(shared)?;
+        child.map(shared)?;
+
+        for page in 0..mapping.num_pages {
+            let phys = PhysicalAddress::new(mapping.phys.as_raw_ptr() + page * PAGE_SIZE);
+            cow_share(phys)?;
+        }
+
+        mark_cow(mapping.virt, mapping.num_pages, mapping.perms)?;
+    }
+
+    Ok(child)
+}
+
+/// Carves `size` bytes of device MMIO out of the reserved remap window and returns
+/// the freshly assigned virtual address for `paddr`. Repeated calls covering the
+/// same physical page return the existing mapping rather than failing, matching
+/// how a driver would re-probe a device without caring whether it's the first
+/// caller to touch it.
+///
+/// This only reserves the VA range and makes `phy2virt` aware of it; installing
+/// the Device-nGnRE attributes into a live translation table awaits the kernel's
+/// own page-table walker.
+pub fn map_mmio(paddr: PhysicalAddress, size: usize) -> Result<VirtualAddress> {
+    let page_base = PhysicalAddress::new(page_align_down(paddr.as_raw_ptr()));
+    let offset = paddr.as_raw_ptr() - page_base.as_raw_ptr();
+    let num_pages = (offset + size).div_ceil(PAGE_SIZE).max(1);
+
+    let mut table = MMIO_REMAP.lock();
+    if let Some(va) = table.lookup(paddr) {
+        return Ok(va);
+    }
+
+    let virt_page = table.insert(page_base, num_pages)?;
+
+    Ok(virt_page + offset)
 }
@@ -11,8 +11,25 @@ use crate::{
 };
 
 pub const VIRTUAL_ADDRESS_IGNORE_MSB: u32 = 16;
+
+/// Bits of virtual-address index consumed per translation level, selected by
+/// whichever `va.*` granule/width feature is enabled (see the `VA`
+/// `register_bitfields!` below, which is gated the same way). Defaults to the
+/// 4 KiB-granule, 48-bit, 4-level layout when no `va.*` feature is set.
+#[cfg(feature = "va.16kb_48bit")]
+pub const VIRTUAL_ADDRESS_LEVEL_IDX_BITS: u32 = 11;
+#[cfg(feature = "va.64kb_42bit")]
+pub const VIRTUAL_ADDRESS_LEVEL_IDX_BITS: u32 = 13;
+#[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
 pub const VIRTUAL_ADDRESS_LEVEL_IDX_BITS: u32 = 9;
 
+/// Width of the `VA::TTBR_Select` field for the configured VA layout --
+/// `64 - <configured VA width>`.
+#[cfg(feature = "va.64kb_42bit")]
+const TTBR_SELECT_NUMBITS: u32 = 22;
+#[cfg(not(feature = "va.64kb_42bit"))]
+const TTBR_SELECT_NUMBITS: u32 = 16;
+
 /// Base trait common to both Physical and Virtual Addresses
 #[const_trait]
 pub trait Address: Clone + Copy + Ord + core::fmt::Display {
@@ -161,21 +178,25 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn get_page_offset_4KiB(&self) -> usize {
         InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::PageOffset_4KiB)
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn get_page_offset_2MiB(&self) -> usize {
         InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::PageOffset_2MiB)
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn get_page_offset_1GiB(&self) -> usize {
         InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::PageOffset_1GiB)
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn set_page_offset_4KiB(&mut self, pgoff: usize) {
         let bits = InMemoryRegister::<usize, VA::Register>::new(self.0);
@@ -183,6 +204,7 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn clear_page_offset_4KiB(&mut self) {
         let bits = InMemoryRegister::<usize, VA::Register>::new(self.0);
@@ -190,6 +212,7 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn set_page_offset_2MiB(&mut self, pgoff: usize) {
         let bits = InMemoryRegister::<usize, VA::Register>::new(self.0);
@@ -197,6 +220,7 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn clear_page_offset_2MiB(&mut self) {
         let bits = InMemoryRegister::<usize, VA::Register>::new(self.0);
@@ -204,6 +228,7 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn set_page_offset_1GiB(&mut self, pgoff: usize) {
         let bits = InMemoryRegister::<usize, VA::Register>::new(self.0);
@@ -211,6 +236,7 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
     #[allow(non_snake_case)]
     pub fn clear_page_offset_1GiB(&mut self) {
         let bits = InMemoryRegister::<usize, VA::Register>::new(self.0);
@@ -218,10 +244,35 @@ impl VirtualAddress {
         self.0 = bits.get();
     }
 
+    #[cfg(feature = "va.16kb_48bit")]
+    #[allow(non_snake_case)]
+    pub fn get_page_offset_16KiB(&self) -> usize {
+        InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::PageOffset_16KiB)
+    }
+
+    #[cfg(feature = "va.16kb_48bit")]
+    #[allow(non_snake_case)]
+    pub fn get_page_offset_32MiB(&self) -> usize {
+        InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::PageOffset_32MiB)
+    }
+
+    #[cfg(feature = "va.64kb_42bit")]
+    #[allow(non_snake_case)]
+    pub fn get_page_offset_64KiB(&self) -> usize {
+        InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::PageOffset_64KiB)
+    }
+
+    /// Top and bottom of the 16-bit-wide (or, with `va.64kb_42bit`'s narrower
+    /// VA, `TTBR_SELECT_NUMBITS`-wide) `TTBR_Select` field -- all-zeros
+    /// selects TTBR0, all-ones selects TTBR1, anything else isn't a
+    /// canonical address.
     fn identify_ttbr_select(&self) -> Option<TTBR> {
-        match InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::TTBR_Select) {
-            0xFFFF => Some(TTBR::One),
-            0x0000 => Some(TTBR::Zero),
+        let select = InMemoryRegister::<usize, VA::Register>::new(self.0).read(VA::TTBR_Select);
+        let all_ones = (1usize << TTBR_SELECT_NUMBITS) - 1;
+
+        match select {
+            0 => Some(TTBR::Zero),
+            s if s == all_ones => Some(TTBR::One),
             _ => None,
         }
     }
@@ -233,7 +284,9 @@ impl core::fmt::Display for VirtualAddress {
     }
 }
 
-// Virtual Address with 4KB granule and 4 level translation
+// Virtual Address with 4KB granule and 4 level translation (the default VA
+// layout, used when no other `va.*` feature is enabled).
+#[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
 register_bitfields![usize,
     VA [
         /// Offset within page
@@ -260,3 +313,63 @@ register_bitfields![usize,
         TTBR_Select OFFSET(48) NUMBITS(16) [],
     ]
 ];
+
+// Virtual Address with 16KB granule and 4 level translation, 48-bit VAs.
+// Per the ARMv8-A ARM's 16KB-granule lookup tables, level 0 only contributes
+// a single index bit at this VA width.
+#[cfg(feature = "va.16kb_48bit")]
+register_bitfields![usize,
+    VA [
+        /// Offset within page
+        // For 16 KiB Page
+        PageOffset_16KiB OFFSET(0) NUMBITS(14) [],
+        // For 32 MiB Page (Level 2 block)
+        PageOffset_32MiB OFFSET(0) NUMBITS(25) [],
+
+        /// Level 3 Index
+        Level_3 OFFSET(14) NUMBITS(11) [],
+
+        /// Level 2 Index
+        Level_2 OFFSET(25) NUMBITS(11) [],
+
+        /// Level 1 Index
+        Level_1 OFFSET(36) NUMBITS(11) [],
+
+        /// Level 0 Index
+        Level_0 OFFSET(47) NUMBITS(1) [],
+
+        /// TTBR select
+        TTBR_Select OFFSET(48) NUMBITS(16) [],
+    ]
+];
+
+// Virtual Address with 64KB granule and 2 level translation, 42-bit VAs (64KB
+// granule's 13-bit-per-level index divides the 42-bit space with no leftover,
+// so levels 0 and 1 aren't walked at all). `Level_0`/`Level_1` are kept as
+// unused 1-bit placeholders so callers that loop over every
+// `AddressTranslationLevel` variant still compile; see
+// `mmu::translation_table`'s module doc for the tracked follow-up to teach it
+// this granule's descriptor layout.
+#[cfg(feature = "va.64kb_42bit")]
+register_bitfields![usize,
+    VA [
+        /// Offset within page
+        // For 64 KiB Page
+        PageOffset_64KiB OFFSET(0) NUMBITS(16) [],
+
+        /// Level 3 Index
+        Level_3 OFFSET(16) NUMBITS(13) [],
+
+        /// Level 2 Index
+        Level_2 OFFSET(29) NUMBITS(13) [],
+
+        /// Unused placeholder -- 64KiB/42-bit VAs have no Level 1 lookup.
+        Level_1 OFFSET(41) NUMBITS(1) [],
+
+        /// Unused placeholder -- 64KiB/42-bit VAs have no Level 0 lookup.
+        Level_0 OFFSET(42) NUMBITS(1) [],
+
+        /// TTBR select
+        TTBR_Select OFFSET(42) NUMBITS(22) [],
+    ]
+];
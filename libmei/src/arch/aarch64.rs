@@ -0,0 +1,107 @@
+//! AArch64 `Arch` backend: 4KiB granule, 4-level translation, EL1/EL0.
+//!
+//! This is the only backend meiOS has ever run on; the VA layout it exposes
+//! here is the one documented at the top of [`crate::vm`], and its
+//! descriptor bit layout mirrors the `STAGE1_*` `register_bitfields!` in
+//! [`crate::mmu`].
+
+use aarch64_cpu::registers::MPIDR_EL1;
+use tock_registers::interfaces::Readable;
+
+use crate::{
+    arch::{Arch, PageTableEntry, PrivilegeLevel, TrapFrame},
+    vm::AccessPermissions,
+};
+
+/// AArch64, 4KiB pages / 4 translation levels.
+pub struct AArch64;
+
+impl Arch for AArch64 {
+    const PAGE_TABLE_LEVELS: usize = 4;
+    const KERNEL_VIRT_BASE: usize = 0xFFFF_FFFF_0000_0000;
+
+    type Entry = Aarch64Pte;
+    type Frame = Aarch64TrapFrame;
+
+    /// Matches `kernel::smp::current_core_id`'s use of `MPIDR_EL1.Aff0`.
+    fn current_core_id() -> usize {
+        (MPIDR_EL1.get() & 0b11) as usize
+    }
+}
+
+/// One stage-1 translation-table descriptor (table, block, or page). Kept as
+/// a thin wrapper over the raw bits so generic code can ask "is this
+/// valid/a table" without committing to the rest of the descriptor layout,
+/// which stays owned by `crate::mmu::translation_table`.
+#[derive(Debug, Clone, Copy)]
+pub struct Aarch64Pte(u64);
+
+impl PageTableEntry for Aarch64Pte {
+    const INVALID: Self = Aarch64Pte(0);
+
+    fn is_valid(&self) -> bool {
+        self.0 & 0b1 != 0
+    }
+
+    fn is_table(&self) -> bool {
+        self.0 & 0b10 != 0
+    }
+}
+
+/// Saved register state for a synchronous/IRQ/FIQ trap, in the layout
+/// `macros::exception_handler` pushes onto the stack: 30 general-purpose
+/// registers followed by LR, ELR_EL1, SPSR_EL1 and ESR_EL1.
+#[derive(Debug)]
+#[repr(C)]
+pub struct Aarch64TrapFrame {
+    gpr: [u64; 30],
+    lr: u64,
+    elr_el1: u64,
+    spsr_el1: u64,
+    esr_el1: u64,
+}
+
+impl TrapFrame for Aarch64TrapFrame {
+    fn source_level(&self) -> PrivilegeLevel {
+        // SPSR_EL1.M[3:2] == 0b00 selects EL0t; any other mode field is EL1.
+        if self.spsr_el1 & 0b1100 == 0 {
+            PrivilegeLevel::User
+        } else {
+            PrivilegeLevel::Kernel
+        }
+    }
+
+    fn return_pc(&self) -> usize {
+        self.elr_el1 as usize
+    }
+
+    fn set_return_pc(&mut self, pc: usize) {
+        self.elr_el1 = pc as u64;
+    }
+}
+
+/// Encodes a portable [`AccessPermissions`] set into the `AP`/`UXN`/`PXN`
+/// bits of a stage-1 last-level descriptor, as `STAGE1_LAST_LEVEL_DESCRIPTOR`
+/// defines them. Mirrors `mmu::translation_table::parse_map_attrs`'s AP/XN
+/// selection so both stay in lock-step with the same source of truth.
+pub fn access_permissions_to_ap_xn_bits(perms: AccessPermissions) -> (u8, bool, bool) {
+    let el1_rw = perms.contains(AccessPermissions::EL1_READ | AccessPermissions::EL1_WRITE);
+    let el0_rw = perms.contains(AccessPermissions::EL0_READ | AccessPermissions::EL0_WRITE);
+    let el0_ro = perms.contains(AccessPermissions::EL0_READ);
+
+    // AP encoding, per STAGE1_LAST_LEVEL_DESCRIPTOR::AP: RW_EL1 = 0b00,
+    // RW_EL1_EL0 = 0b01, RO_EL1 = 0b10, RO_EL1_EL0 = 0b11.
+    let ap = match (el1_rw, el0_rw) {
+        (true, true) => 0b01,
+        (true, false) => 0b00,
+        (false, _) if el0_ro => 0b11,
+        (false, _) => 0b10,
+    };
+
+    let pxn = perms.contains(AccessPermissions::EL1_WRITE)
+        || !perms.contains(AccessPermissions::EL1_EXECUTE);
+    let uxn = perms.contains(AccessPermissions::EL0_WRITE)
+        || !perms.contains(AccessPermissions::EL0_EXECUTE);
+
+    (ap, pxn, uxn)
+}
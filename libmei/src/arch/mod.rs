@@ -0,0 +1,87 @@
+//! Architecture-abstraction boundary.
+//!
+//! Code outside this module -- `vm::phy2virt`, `MapDesc`/`MemoryMap`, the
+//! physical and capability allocators -- is written against the [`Arch`]
+//! trait rather than against AArch64 directly, so it compiles unchanged
+//! against any backend that implements it. Exactly one backend is active at
+//! a time, selected through the `arch.aarch64` / `arch.riscv64` Cargo
+//! features and re-exported here as [`Current`].
+//!
+//! A backend owns three things:
+//! - the kernel virtual-address window `vm::phy2virt` maps physical memory
+//!   into (`KERNEL_VIRT_BASE`)
+//! - its page-table descriptor format and level count (`Entry`,
+//!   `PAGE_TABLE_LEVELS`)
+//! - its trap frame and privilege levels (`Frame`, [`PrivilegeLevel`])
+
+#[cfg(feature = "arch.aarch64")]
+pub mod aarch64;
+#[cfg(feature = "arch.riscv64")]
+pub mod riscv64;
+
+#[cfg(feature = "arch.aarch64")]
+pub use aarch64::AArch64 as Current;
+#[cfg(feature = "arch.riscv64")]
+pub use riscv64::Riscv64Sv39 as Current;
+
+/// Privilege level a trap was taken from / returns to.
+///
+/// Named after the AArch64 exception levels this kernel first ran on;
+/// `Kernel` maps to EL1 on AArch64 and S-mode on RISC-V, `User` to EL0 /
+/// U-mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivilegeLevel {
+    Kernel,
+    User,
+}
+
+/// The register state a backend must save across a trap and restore on
+/// return. Backends give this a concrete layout (AArch64's 30 GPRs plus
+/// LR/ELR_EL1/SPSR_EL1/ESR_EL1, RISC-V's 31 GPRs plus sepc/sstatus/scause);
+/// code above this trait only ever asks for the handful of fields it needs.
+pub trait TrapFrame {
+    /// Privilege level the trap was taken from.
+    fn source_level(&self) -> PrivilegeLevel;
+
+    /// Program counter the trap will return to.
+    fn return_pc(&self) -> usize;
+
+    fn set_return_pc(&mut self, pc: usize);
+}
+
+/// A single page-table entry in a backend's native descriptor format.
+pub trait PageTableEntry: Copy {
+    /// Entry with no mapping and no permissions -- `is_valid()` is false.
+    const INVALID: Self;
+
+    fn is_valid(&self) -> bool;
+
+    /// True when this entry points at a next-level table rather than a leaf
+    /// mapping (AArch64's table descriptor, RISC-V's non-leaf PTE).
+    fn is_table(&self) -> bool;
+}
+
+/// One MMU backend: the VA layout it exposes, its descriptor format, and its
+/// trap frame / privilege-level types.
+pub trait Arch {
+    /// Number of levels a full page-table walk takes: 4 for AArch64's 4KiB
+    /// granule, 3 for RISC-V Sv39, 4 for Sv48.
+    const PAGE_TABLE_LEVELS: usize;
+
+    /// Base of the high kernel virtual-address window that all of physical
+    /// memory is linearly mapped into (see `vm::phy2virt`).
+    const KERNEL_VIRT_BASE: usize;
+
+    type Entry: PageTableEntry;
+    type Frame: TrapFrame;
+
+    /// Translate a statically-mapped physical address into its kernel VA.
+    fn phy_to_virt(paddr: usize) -> usize {
+        Self::KERNEL_VIRT_BASE + paddr
+    }
+
+    /// 0-based index of the core this code is currently running on. Used to
+    /// key per-core allocator state (the slab's magazine cache) without
+    /// depending on any OS-level thread/task id.
+    fn current_core_id() -> usize;
+}
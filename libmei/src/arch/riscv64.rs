@@ -0,0 +1,159 @@
+//! RISC-V `Arch` backends: Sv39 (3-level) and Sv48 (4-level) paged virtual
+//! memory.
+//!
+//! Sv39 and Sv48 share one PTE format -- a 10-bit flags field followed by a
+//! physical page number, per the RISC-V Privileged spec section 4.4/4.5 --
+//! and differ only in how many levels a walk takes and how wide the VA
+//! field is. [`Current`](super::Current) defaults to [`Riscv64Sv39`]; a
+//! target whose address space needs more than 512GiB per translation regime
+//! should use [`Riscv64Sv48`] instead.
+
+use crate::{
+    arch::{Arch, PageTableEntry, PrivilegeLevel, TrapFrame},
+    vm::AccessPermissions,
+};
+
+bitflags! {
+    /// Sv39/Sv48 PTE flag bits (RISC-V Privileged spec table 4.4).
+    pub struct PteFlags: u64 {
+        const VALID = 1 << 0;
+        const READ = 1 << 1;
+        const WRITE = 1 << 2;
+        const EXECUTE = 1 << 3;
+        const USER = 1 << 4;
+        const GLOBAL = 1 << 5;
+        const ACCESSED = 1 << 6;
+        const DIRTY = 1 << 7;
+    }
+}
+
+impl From<AccessPermissions> for PteFlags {
+    /// Translates the portable EL0/EL1 permission bits `MapDesc` carries
+    /// into the R/W/X/U bits a Sv39/Sv48 PTE needs. `A`/`D` are left unset
+    /// here -- they're set by the backend's page-fault path the first time
+    /// the mapping is actually accessed/written, same as hardware would if
+    /// `menvcfg.ADUE` were in use.
+    fn from(perms: AccessPermissions) -> Self {
+        let mut flags = PteFlags::VALID;
+
+        if perms.contains(AccessPermissions::EL1_READ) || perms.contains(AccessPermissions::EL0_READ) {
+            flags |= PteFlags::READ;
+        }
+        if perms.contains(AccessPermissions::EL1_WRITE) || perms.contains(AccessPermissions::EL0_WRITE) {
+            flags |= PteFlags::WRITE;
+        }
+        if perms.contains(AccessPermissions::EL1_EXECUTE) || perms.contains(AccessPermissions::EL0_EXECUTE) {
+            flags |= PteFlags::EXECUTE;
+        }
+        if perms.contains(AccessPermissions::EL0_READ)
+            || perms.contains(AccessPermissions::EL0_WRITE)
+            || perms.contains(AccessPermissions::EL0_EXECUTE)
+        {
+            flags |= PteFlags::USER;
+        }
+
+        flags
+    }
+}
+
+/// One Sv39/Sv48 page-table entry.
+#[derive(Debug, Clone, Copy)]
+pub struct SvPte(u64);
+
+impl SvPte {
+    fn flags(&self) -> PteFlags {
+        PteFlags::from_bits_truncate(self.0 & 0xFF)
+    }
+}
+
+impl PageTableEntry for SvPte {
+    const INVALID: Self = SvPte(0);
+
+    fn is_valid(&self) -> bool {
+        self.flags().contains(PteFlags::VALID)
+    }
+
+    fn is_table(&self) -> bool {
+        // A valid PTE with none of R/W/X set points at the next-level table
+        // rather than a leaf page (RISC-V Privileged spec 4.3.1).
+        self.is_valid()
+            && !self
+                .flags()
+                .intersects(PteFlags::READ | PteFlags::WRITE | PteFlags::EXECUTE)
+    }
+}
+
+/// Trap frame shape shared by Sv39 and Sv48: S-mode saves the same 31
+/// general-purpose registers (x1-x31) plus `sepc`/`sstatus`/`scause`
+/// regardless of which paging mode is active.
+#[derive(Debug)]
+#[repr(C)]
+pub struct RiscvTrapFrame {
+    gpr: [u64; 31],
+    sepc: u64,
+    sstatus: u64,
+    scause: u64,
+}
+
+impl TrapFrame for RiscvTrapFrame {
+    fn source_level(&self) -> PrivilegeLevel {
+        // sstatus.SPP (bit 8): 0 selects U-mode, 1 selects S-mode.
+        if self.sstatus & (1 << 8) == 0 {
+            PrivilegeLevel::User
+        } else {
+            PrivilegeLevel::Kernel
+        }
+    }
+
+    fn return_pc(&self) -> usize {
+        self.sepc as usize
+    }
+
+    fn set_return_pc(&mut self, pc: usize) {
+        self.sepc = pc as u64;
+    }
+}
+
+/// Sv39: 3-level, 39-bit virtual addresses (512GiB per address space).
+pub struct Riscv64Sv39;
+
+impl Arch for Riscv64Sv39 {
+    const PAGE_TABLE_LEVELS: usize = 3;
+    /// Canonical higher half for a 39-bit VA space: bits [63:39] sign-extended.
+    const KERNEL_VIRT_BASE: usize = 0xFFFF_FFC0_0000_0000;
+
+    type Entry = SvPte;
+    type Frame = RiscvTrapFrame;
+
+    fn current_core_id() -> usize {
+        riscv64_hart_id()
+    }
+}
+
+/// Sv48: 4-level, 48-bit virtual addresses (256TiB per address space).
+pub struct Riscv64Sv48;
+
+impl Arch for Riscv64Sv48 {
+    const PAGE_TABLE_LEVELS: usize = 4;
+    /// Canonical higher half for a 48-bit VA space: bits [63:48] sign-extended.
+    const KERNEL_VIRT_BASE: usize = 0xFFFF_8000_0000_0000;
+
+    type Entry = SvPte;
+    type Frame = RiscvTrapFrame;
+
+    fn current_core_id() -> usize {
+        riscv64_hart_id()
+    }
+}
+
+/// S-mode has no MPIDR-equivalent CSR it can read directly; by convention
+/// (mirroring Linux/xv6-riscv) the M-mode boot stub stashes the hart id it
+/// was handed in `a0` into `tp` before entering S-mode, so this just reads
+/// it back out.
+fn riscv64_hart_id() -> usize {
+    let hart_id: usize;
+    unsafe {
+        core::arch::asm!("mv {0}, tp", out(reg) hart_id);
+    }
+    hart_id
+}
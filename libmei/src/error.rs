@@ -1,3 +1,4 @@
+use crate::address::PhysicalAddress;
 use crate::vm::MemoryMap;
 
 #[derive(Debug, Clone, Copy)]
@@ -11,6 +12,16 @@ pub enum Error {
 
     PhysicalOOM,
     ContigiousPhysicalRangeUnavailable(u64),
+    PhysicalRangeAlreadyReserved(PhysicalAddress),
+
+    UntypedExhausted(u64),
+
+    InvalidDeviceTree,
+
+    DuplicatePhysicalMapping(PhysicalAddress),
+    ReverseMapExhausted,
+
+    AllocError,
 }
 
 impl core::fmt::Display for Error {
@@ -35,6 +46,27 @@ impl core::fmt::Display for Error {
                     "Contigious Physical Memory Range Unavailable for {num_pages} pages"
                 )
             }
+            Error::PhysicalRangeAlreadyReserved(paddr) => {
+                write!(f, "Physical Address(`{paddr}`) is already reserved")
+            }
+
+            Error::UntypedExhausted(size_bits) => {
+                write!(
+                    f,
+                    "Untyped(2^{size_bits} bytes) has no room left for the requested retype"
+                )
+            }
+
+            Error::InvalidDeviceTree => write!(f, "Device tree blob is malformed or unsupported"),
+
+            Error::DuplicatePhysicalMapping(paddr) => {
+                write!(f, "Physical frame `{paddr}` is already reverse-mapped")
+            }
+            Error::ReverseMapExhausted => {
+                write!(f, "ReverseMap has no room left for another populated chunk")
+            }
+
+            Error::AllocError => write!(f, "Allocation request could not be satisfied"),
         }
     }
 }
@@ -0,0 +1,419 @@
+//! Minimal, allocation-free parser for the Flattened Device Tree (DTB) blob
+//! the bootloader leaves at the address passed in `x0`.
+//!
+//! Only walks far enough to answer the two questions early boot actually
+//! needs answered: which physical stripes are usable DRAM (`/memory` nodes),
+//! and which are MMIO peripherals (`/soc` node's children). That output seeds
+//! [`crate::vm::physical_page_alloc`]'s free regions and the device
+//! [`MemoryMap`]s fed to [`crate::mmu::TranslationTable::map`], so the kernel
+//! stops guessing the memory layout from a hardcoded Raspi board id.
+
+use core::{ops::Range, slice};
+
+use heapless::Vec;
+
+use crate::{
+    address::PhysicalAddress,
+    error::{Error, Result},
+    mmu::GRANULE_SIZE,
+    vm::{phy2virt, AccessPermissions, MapDesc, MemoryKind, MemoryMap},
+};
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+/// Generous for any board this kernel targets; keeps the parser's output
+/// allocation-free.
+const MAX_MEMORY_REGIONS: usize = 8;
+const MAX_DEVICE_REGIONS: usize = 32;
+/// Deepest `#address-cells`/`#size-cells` nesting the walk tracks. `/`, `/soc`
+/// and `/soc`'s children is 3; doubled for headroom.
+const MAX_DEPTH: usize = 6;
+
+#[repr(C)]
+struct FdtHeader {
+    magic: u32,
+    totalsize: u32,
+    off_dt_struct: u32,
+    off_dt_strings: u32,
+    off_mem_rsvmap: u32,
+    version: u32,
+    last_comp_version: u32,
+    boot_cpuid_phys: u32,
+    size_dt_strings: u32,
+    size_dt_struct: u32,
+}
+
+/// `#address-cells`/`#size-cells` a node declares for interpreting its
+/// children's `reg`/`ranges` properties.
+#[derive(Clone, Copy)]
+struct CellSizes {
+    address_cells: u32,
+    size_cells: u32,
+}
+
+impl Default for CellSizes {
+    /// The Devicetree spec's fallback when a node declares neither property.
+    fn default() -> Self {
+        Self {
+            address_cells: 2,
+            size_cells: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Root,
+    Memory,
+    Soc,
+    SocChild,
+    Other,
+}
+
+/// Reads big-endian tokens out of the struct block, tracking position.
+struct Cursor<'a> {
+    blob: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(blob: &'a [u8], pos: usize) -> Self {
+        Self { blob, pos }
+    }
+
+    fn read_u32(&mut self) -> u32 {
+        let bytes = [
+            self.blob[self.pos],
+            self.blob[self.pos + 1],
+            self.blob[self.pos + 2],
+            self.blob[self.pos + 3],
+        ];
+        self.pos += 4;
+        u32::from_be_bytes(bytes)
+    }
+
+    /// Reads a NUL-terminated string starting at `pos`, then advances past
+    /// the terminator and pads `pos` up to the next 4-byte boundary.
+    fn read_cstr(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while self.blob[self.pos] != 0 {
+            self.pos += 1;
+        }
+        let name = &self.blob[start..self.pos];
+        self.pos += 1;
+        self.align4();
+        name
+    }
+
+    fn read_bytes(&mut self, len: usize) -> &'a [u8] {
+        let data = &self.blob[self.pos..self.pos + len];
+        self.pos += len;
+        self.align4();
+        data
+    }
+
+    fn align4(&mut self) {
+        self.pos = (self.pos + 3) & !3;
+    }
+}
+
+/// Reads a big-endian cell value of `num_cells` 32-bit words (1 or 2) out of
+/// `data` at `*offset`, advancing `*offset` past it.
+fn read_cells(data: &[u8], offset: &mut usize, num_cells: u32) -> u64 {
+    let mut value: u64 = 0;
+    for _ in 0..num_cells {
+        let word = u32::from_be_bytes([
+            data[*offset],
+            data[*offset + 1],
+            data[*offset + 2],
+            data[*offset + 3],
+        ]);
+        value = (value << 32) | word as u64;
+        *offset += 4;
+    }
+    value
+}
+
+fn node_kind(parent_kind: NodeKind, name: &[u8]) -> NodeKind {
+    match parent_kind {
+        NodeKind::Root if name.starts_with(b"memory") => NodeKind::Memory,
+        NodeKind::Root if name == b"soc" || name.starts_with(b"soc@") => NodeKind::Soc,
+        NodeKind::Soc => NodeKind::SocChild,
+        _ => NodeKind::Other,
+    }
+}
+
+/// Physical memory and device layout read out of a flattened device tree.
+pub struct DeviceTree {
+    memory_regions: Vec<Range<PhysicalAddress>, MAX_MEMORY_REGIONS>,
+    device_regions: Vec<Range<PhysicalAddress>, MAX_DEVICE_REGIONS>,
+}
+
+impl DeviceTree {
+    /// Parses the DTB at `dtb_ptr`, collecting every `/memory` node's `reg`
+    /// stripes and every `/soc` child's `reg` range (translated through
+    /// `/soc`'s `ranges`, if any, into CPU physical addresses).
+    ///
+    /// # Safety
+    ///
+    /// `dtb_ptr` must point to a valid flattened device tree blob, at least
+    /// `totalsize` bytes of which (per its own header) are mapped and
+    /// readable for the duration of this call.
+    pub unsafe fn parse(dtb_ptr: *const u8) -> Result<Self> {
+        let header = &*(dtb_ptr as *const FdtHeader);
+        if u32::from_be(header.magic) != FDT_MAGIC {
+            return Err(Error::InvalidDeviceTree);
+        }
+
+        let totalsize = u32::from_be(header.totalsize) as usize;
+        let off_dt_struct = u32::from_be(header.off_dt_struct) as usize;
+        let off_dt_strings = u32::from_be(header.off_dt_strings) as usize;
+
+        let blob = slice::from_raw_parts(dtb_ptr, totalsize);
+        let strings = &blob[off_dt_strings..];
+
+        let mut tree = DeviceTree {
+            memory_regions: Vec::new(),
+            device_regions: Vec::new(),
+        };
+
+        let mut cells: Vec<CellSizes, MAX_DEPTH> = Vec::new();
+        let mut kinds: Vec<NodeKind, MAX_DEPTH> = Vec::new();
+        // `/soc`'s `(child_base, parent_base)` translation, valid once its
+        // `ranges` property has been read; `None` means identity mapping.
+        let mut soc_translation: Option<(u64, u64)> = None;
+
+        let mut cursor = Cursor::new(blob, off_dt_struct);
+        loop {
+            match cursor.read_u32() {
+                FDT_BEGIN_NODE => {
+                    let name = cursor.read_cstr();
+                    let parent_kind = kinds.last().copied().unwrap_or(NodeKind::Root);
+                    let kind = if kinds.is_empty() {
+                        NodeKind::Root
+                    } else {
+                        node_kind(parent_kind, name)
+                    };
+
+                    cells
+                        .push(CellSizes::default())
+                        .map_err(|_| Error::InvalidDeviceTree)?;
+                    kinds.push(kind).map_err(|_| Error::InvalidDeviceTree)?;
+                }
+                FDT_END_NODE => {
+                    if kinds.last() == Some(&NodeKind::Soc) {
+                        soc_translation = None;
+                    }
+                    cells.pop();
+                    kinds.pop();
+                }
+                FDT_PROP => {
+                    let len = cursor.read_u32() as usize;
+                    let nameoff = cursor.read_u32() as usize;
+                    let data = cursor.read_bytes(len);
+                    let name = read_cstr_at(strings, nameoff);
+
+                    let kind = *kinds.last().unwrap_or(&NodeKind::Root);
+                    // `reg`/`ranges` addresses on a node are expressed using
+                    // its *parent's* declared cell sizes; `#address-cells`
+                    // and `#size-cells` properties govern this node's own
+                    // children and so update this node's own scope.
+                    let parent_cells = if cells.len() >= 2 {
+                        cells[cells.len() - 2]
+                    } else {
+                        CellSizes::default()
+                    };
+
+                    match name {
+                        b"#address-cells" if len == 4 => {
+                            if let Some(own) = cells.last_mut() {
+                                own.address_cells = u32::from_be_bytes(data.try_into().unwrap());
+                            }
+                        }
+                        b"#size-cells" if len == 4 => {
+                            if let Some(own) = cells.last_mut() {
+                                own.size_cells = u32::from_be_bytes(data.try_into().unwrap());
+                            }
+                        }
+                        b"reg" if kind == NodeKind::Memory => {
+                            push_reg_entries(
+                                data,
+                                parent_cells,
+                                |addr, size| Range {
+                                    start: PhysicalAddress::new(addr as usize),
+                                    end: PhysicalAddress::new((addr + size) as usize),
+                                },
+                                &mut tree.memory_regions,
+                            );
+                        }
+                        b"reg" if kind == NodeKind::SocChild => {
+                            let (child_base, parent_base) = soc_translation.unwrap_or((0, 0));
+                            push_reg_entries(
+                                data,
+                                parent_cells,
+                                |addr, size| {
+                                    let phy = parent_base + (addr - child_base);
+                                    Range {
+                                        start: PhysicalAddress::new(phy as usize),
+                                        end: PhysicalAddress::new((phy + size) as usize),
+                                    }
+                                },
+                                &mut tree.device_regions,
+                            );
+                        }
+                        b"ranges" if kind == NodeKind::Soc && !data.is_empty() => {
+                            // Only the first `(child-addr, parent-addr, size)`
+                            // triple is used -- boards with more than one
+                            // `/soc` ranges stripe need a richer translation
+                            // table than this early-boot parser provides.
+                            let own_cells = *cells.last().unwrap();
+                            let mut offset = 0;
+                            let child_addr = read_cells(data, &mut offset, own_cells.address_cells);
+                            let parent_addr = read_cells(data, &mut offset, parent_cells.address_cells);
+                            soc_translation = Some((child_addr, parent_addr));
+                        }
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => break,
+                _ => return Err(Error::InvalidDeviceTree),
+            }
+        }
+
+        Ok(tree)
+    }
+
+    /// Usable DRAM physical ranges straight off `/memory` nodes, with
+    /// `exclude` (typically `kernel_phy_range()`) carved out so the physical
+    /// page allocator never hands out memory the kernel image itself lives
+    /// in.
+    pub fn memory_regions(
+        &self,
+        exclude: Range<PhysicalAddress>,
+    ) -> Vec<Range<PhysicalAddress>, MAX_MEMORY_REGIONS> {
+        let mut out = Vec::new();
+        for region in self.memory_regions.iter() {
+            for piece in subtract_range(region.clone(), exclude.clone()) {
+                let _ = out.push(piece);
+            }
+        }
+        out
+    }
+
+    /// `/memory` regions (minus `exclude`) as page-table-ready
+    /// [`MemoryKind::NormalCacheable`] [`MemoryMap`]s, virtually mapped
+    /// through [`phy2virt`].
+    pub fn memory_maps(
+        &self,
+        exclude: Range<PhysicalAddress>,
+        access_perms: AccessPermissions,
+    ) -> Vec<MemoryMap, MAX_MEMORY_REGIONS> {
+        let mut out = Vec::new();
+        for region in self.memory_regions(exclude) {
+            if let Some(map) = map_desc_for(region, access_perms) {
+                let _ = out.push(MemoryMap::new(map, MemoryKind::NormalCacheable));
+            }
+        }
+        out
+    }
+
+    /// `/soc` children as page-table-ready
+    /// [`MemoryKind::DeviceNonGatheringNonReorderingEarlyAck`] [`MemoryMap`]s,
+    /// virtually mapped through [`phy2virt`].
+    pub fn device_maps(&self, access_perms: AccessPermissions) -> Vec<MemoryMap, MAX_DEVICE_REGIONS> {
+        let mut out = Vec::new();
+        for region in self.device_regions.iter() {
+            if let Some(map) = map_desc_for(region.clone(), access_perms) {
+                let _ = out.push(MemoryMap::new(
+                    map,
+                    MemoryKind::DeviceNonGatheringNonReorderingEarlyAck,
+                ));
+            }
+        }
+        out
+    }
+}
+
+fn map_desc_for(region: Range<PhysicalAddress>, access_perms: AccessPermissions) -> Option<MapDesc> {
+    let size = region.end.as_raw_ptr() - region.start.as_raw_ptr();
+    if size == 0 {
+        return None;
+    }
+
+    let num_pages = size.div_ceil(GRANULE_SIZE);
+    Some(MapDesc::new(
+        region.start,
+        phy2virt(region.start),
+        num_pages,
+        access_perms,
+    ))
+}
+
+/// Splits `region` into the pieces left over after removing `exclude`.
+/// Yields zero, one or two pieces depending on how the two overlap.
+fn subtract_range(
+    region: Range<PhysicalAddress>,
+    exclude: Range<PhysicalAddress>,
+) -> Vec<Range<PhysicalAddress>, 2> {
+    let mut out = Vec::new();
+
+    if exclude.end <= region.start || exclude.start >= region.end {
+        let _ = out.push(region);
+        return out;
+    }
+
+    if exclude.start > region.start {
+        let _ = out.push(Range {
+            start: region.start,
+            end: exclude.start,
+        });
+    }
+    if exclude.end < region.end {
+        let _ = out.push(Range {
+            start: exclude.end,
+            end: region.end,
+        });
+    }
+
+    out
+}
+
+/// Reads a NUL-terminated string out of the strings block at `offset`.
+fn read_cstr_at(strings: &[u8], offset: usize) -> &[u8] {
+    let end = strings[offset..]
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(strings.len() - offset);
+    &strings[offset..offset + end]
+}
+
+/// Walks a `reg` property's `(addr, size)` pairs (cell-sized per `cells`),
+/// converting each to a `Range<PhysicalAddress>` via `to_range` and pushing it
+/// onto `out`.
+fn push_reg_entries<const N: usize>(
+    data: &[u8],
+    cells: CellSizes,
+    to_range: impl Fn(u64, u64) -> Range<PhysicalAddress>,
+    out: &mut Vec<Range<PhysicalAddress>, N>,
+) {
+    let entry_len = ((cells.address_cells + cells.size_cells) * 4) as usize;
+    if entry_len == 0 {
+        return;
+    }
+
+    let mut offset = 0;
+    while offset + entry_len <= data.len() {
+        let addr = read_cells(data, &mut offset, cells.address_cells);
+        let size = read_cells(data, &mut offset, cells.size_cells);
+        if size > 0 {
+            let _ = out.push(to_range(addr, size));
+        }
+    }
+}
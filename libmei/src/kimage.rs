@@ -0,0 +1,36 @@
+//! Physical bounds of the running kernel image, as placed by the linker
+//! script. Mirrors `kernel::kimage`'s simpler (pre-ELF-segment) form: just
+//! enough to let `mmu::setup_ttbr1_entries` map the image and its boot stack
+//! into the TTBR1 half of the address space.
+
+use core::{cell::UnsafeCell, ops::Range};
+
+use crate::address::PhysicalAddress;
+
+#[allow(improper_ctypes)]
+extern "C" {
+    /// Provided by Linker
+    static __kernel_start_marker: UnsafeCell<()>;
+    static __kernel_end_marker: UnsafeCell<()>;
+    static __kernel_stack_start_marker: UnsafeCell<()>;
+}
+
+pub fn kernel_phy_range() -> Range<PhysicalAddress> {
+    let kstart = unsafe { __kernel_start_marker.get() as usize };
+    let kend = unsafe { __kernel_end_marker.get() as usize };
+    PhysicalAddress::new(kstart)..PhysicalAddress::new(kend)
+}
+
+pub fn kernel_stack_range() -> Range<PhysicalAddress> {
+    let stack_top = unsafe { __kernel_stack_start_marker.get() as usize };
+    PhysicalAddress::new(0)..PhysicalAddress::new(stack_top)
+}
+
+pub fn kernel_image_size() -> usize {
+    let krange = kernel_phy_range();
+    (krange.end - krange.start) as usize
+}
+
+pub fn kernel_stack_base() -> usize {
+    unsafe { __kernel_stack_start_marker.get() as usize }
+}
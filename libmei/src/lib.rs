@@ -21,6 +21,10 @@ pub mod address;
 pub mod address_map;
 pub mod bug;
 pub mod error;
+pub mod fdt;
+#[cfg(feature = "no_std")]
+pub mod kimage;
 pub mod mimo;
 pub mod mmu;
+pub mod syscall;
 pub mod vm;
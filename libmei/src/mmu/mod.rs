@@ -3,20 +3,62 @@ use aarch64_cpu::{
     registers::{MAIR_EL1, SCTLR_EL1, TCR_EL1},
 };
 use tock_registers::{
+    fields::FieldValue,
     interfaces::{ReadWriteable, Writeable},
     register_bitfields,
 };
 
-use crate::address::VIRTUAL_ADDRESS_LEVEL_IDX_BITS;
+use crate::{
+    address::{PhysicalAddress, TTBR, VIRTUAL_ADDRESS_LEVEL_IDX_BITS},
+    bug,
+    error::Result,
+    kimage,
+    vm::{self, AccessPermissions, MapDesc, MemoryKind, MemoryMap, PhysicalPageAllocator},
+};
 
-pub const GRANULE_SIZE: usize = 4096;
+// `translation_table`'s block/page descriptor installation
+// (`find_best_mapping_scheme`'s span sizes, `install_l2_block_desc`,
+// `install_l1_block_desc`) and this module's `OUTPUT_ADDR_*` descriptor field
+// widths are still hardcoded to the default 4 KiB granule's layout -- see
+// `translation_table`'s module doc. `address::VirtualAddress` and the
+// `TCR_EL1`/`GRANULE_SIZE` setup below really do honor `va.16kb_48bit`/
+// `va.64kb_42bit`, so selecting either without also finishing that
+// parameterization would reprogram the hardware for one granule while the
+// table walker still builds/walks tables for another -- silent page-table
+// corruption, not a documented gap. Block it at compile time until
+// `translation_table` and this module's `OUTPUT_ADDR_*` are made granule-aware.
+#[cfg(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit"))]
+compile_error!(
+    "va.16kb_48bit/va.64kb_42bit select a non-4KiB MMU granule, but \
+     mmu::translation_table's block/page descriptor installation and this \
+     module's OUTPUT_ADDR_* field widths are still 4 KiB-only -- enabling \
+     either feature today would silently corrupt page tables. Finish that \
+     granule parameterization before selecting one."
+);
+
+/// Granule (and, equivalently, leaf page) size selected by whichever `va.*`
+/// feature is enabled. Defaults to 4 KiB -- see `address::VirtualAddress`'s
+/// `VA` bitfield, which is gated the same way.
+#[cfg(feature = "va.16kb_48bit")]
+pub const GRANULE_SIZE: usize = 16 * 1024;
+#[cfg(feature = "va.64kb_42bit")]
+pub const GRANULE_SIZE: usize = 64 * 1024;
+#[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
+pub const GRANULE_SIZE: usize = 4 * 1024;
 pub const GRANULE_SIZE_BITS: u32 = GRANULE_SIZE.ilog2();
 
 pub const TRANSLATION_TABLE_DESC_ALIGN: usize =
     core::mem::size_of::<u64>() * (1 << VIRTUAL_ADDRESS_LEVEL_IDX_BITS);
 pub const TRANSLATION_TABLE_DESC_ALIGN_BITS: u32 = TRANSLATION_TABLE_DESC_ALIGN.ilog2();
 
+/// Maximum Output Address width for the configured VA layout (`48` for the
+/// default 4 KiB/48-bit and `va.16kb_48bit` layouts, `42` for
+/// `va.64kb_42bit`).
+#[cfg(feature = "va.64kb_42bit")]
+pub const OUTPUT_ADDR_BITS: u32 = 42;
+#[cfg(not(feature = "va.64kb_42bit"))]
 pub const OUTPUT_ADDR_BITS: u32 = 48;
+
 pub const NEXT_LEVEL_TABLE_ADDR_BITS: u32 = 36;
 pub const NEXT_LEVEL_TABLE_ADDR_SHIFT: u32 = OUTPUT_ADDR_BITS - NEXT_LEVEL_TABLE_ADDR_BITS;
 pub const LEVEL_1_OUTPUT_ADDR_BITS: u32 = 18;
@@ -27,57 +69,152 @@ pub const LEVEL_3_OUTPUT_ADDR_BITS: u32 = 36;
 pub const LEVEL_3_OUTPUT_ADDR_SHIFT: u32 = OUTPUT_ADDR_BITS - LEVEL_3_OUTPUT_ADDR_BITS;
 
 mod translation_table;
+pub mod revmap;
+pub mod stage2;
+pub mod tlb;
+mod utils;
+
+use translation_table::TranslationTable;
+
+/// The TTBR1 (kernel) and TTBR0 (user) root tables installed by `setup_mmu`.
+/// Each lives for the rest of the kernel's life once activated, so they're
+/// kept in `'static` storage rather than on `setup_mmu`'s stack frame.
+///
+/// # Safety
+///
+/// Written exactly once, by `setup_mmu`, before any other core is brought up
+/// (see `kernel::smp`) -- there is no concurrent access to race against.
+static mut TTBR1_TABLE: Option<TranslationTable> = None;
+static mut TTBR0_TABLE: Option<TranslationTable> = None;
 
 /// Setup all registers before enabling MMU
 /// Also return the value to be written to SCTLR_EL1 for enabling MMU.
-pub fn setup_mmu() {
-    setup_ttbr1_entries();
-    setup_ttbr0_entries();
-    config_4kb_48bit_virtual_address_space();
+pub fn setup_mmu<DescAlloc: PhysicalPageAllocator>(desc_alloc: &DescAlloc) -> Result<()> {
+    setup_ttbr1_entries(desc_alloc)?;
+    setup_ttbr0_entries(desc_alloc)?;
+    config_virtual_address_space();
     config_el1_memory_attributes();
     enable_mmu();
+    Ok(())
 }
 
-/// Setup Virtual Memory for Kernel Space (TTBR1)
-fn setup_ttbr1_entries() {
-    todo!()
+/// Setup Virtual Memory for Kernel Space (TTBR1): identity/offset-maps the
+/// running kernel image and its boot stack, found via `kimage`, so code and
+/// data keep resolving once the MMU is live.
+fn setup_ttbr1_entries<DescAlloc: PhysicalPageAllocator>(desc_alloc: &DescAlloc) -> Result<()> {
+    let maps = [
+        offset_map(kimage::kernel_phy_range()),
+        offset_map(kimage::kernel_stack_range()),
+    ];
+
+    let tt = TranslationTable::new(&maps, desc_alloc)?;
+
+    // SAFETY: see the safety note on `TTBR1_TABLE`.
+    unsafe {
+        TTBR1_TABLE = Some(tt);
+        TTBR1_TABLE
+            .as_ref()
+            .unwrap_or_else(|| bug!("TTBR1_TABLE was just initialized"))
+            .activate(TTBR::One);
+    }
+
+    Ok(())
 }
 
-/// Setup Virtual Memory for User Space (TTBR0)
-fn setup_ttbr0_entries() {
-    todo!()
+/// Setup Virtual Memory for User Space (TTBR0). No user address space exists
+/// this early in boot, so this installs an empty table purely so TTBR0_EL1
+/// points at valid (if mapping-less) memory the moment the MMU is enabled;
+/// per-process tables replace it once process/user-space support lands.
+fn setup_ttbr0_entries<DescAlloc: PhysicalPageAllocator>(desc_alloc: &DescAlloc) -> Result<()> {
+    let tt = TranslationTable::new(&[], desc_alloc)?;
+
+    // SAFETY: see the safety note on `TTBR0_TABLE`.
+    unsafe {
+        TTBR0_TABLE = Some(tt);
+        TTBR0_TABLE
+            .as_ref()
+            .unwrap_or_else(|| bug!("TTBR0_TABLE was just initialized"))
+            .activate(TTBR::Zero);
+    }
+
+    Ok(())
 }
 
-/// Setup VA space for both Kernel and User space to contain 48 bits and 4KB granule
-/// This means there are 4 levels of Translation required to obtain Physical address
-/// from Virtual address
-fn config_4kb_48bit_virtual_address_space() {
+/// Builds the `MemoryMap` that offset-maps `phy_range` into the kernel's
+/// linear view of physical memory (`vm::phy2virt`), rounding up to whole
+/// pages the way every other `TranslationTable` caller's `MapDesc` already
+/// expects.
+fn offset_map(phy_range: core::ops::Range<PhysicalAddress>) -> MemoryMap {
+    let phy_addr = phy_range.start;
+    let num_pages = ((phy_range.end - phy_range.start) as usize).div_ceil(GRANULE_SIZE);
+    let virt_addr = vm::phy2virt(phy_addr);
+
+    MemoryMap::new(
+        MapDesc::new(
+            phy_addr,
+            virt_addr,
+            num_pages,
+            AccessPermissions::normal_memory_default(),
+        ),
+        MemoryKind::NormalCacheable,
+    )
+}
+
+/// Setup VA space for both Kernel and User space with the granule/width
+/// selected by whichever `va.*` feature is enabled (4 KiB/48-bit/4-level by
+/// default; see `address::VirtualAddress`'s `VA` bitfield for the others).
+fn config_virtual_address_space() {
     TCR_EL1.write(
         TCR_EL1::A1::TTBR0
-            + TCR_EL1::IPS::Bits_48
-            + TCR_EL1::TG0::KiB_4
-            + TCR_EL1::TG1::KiB_4
+            + TCR_EL1::AS::Bits_16
+            + granule_tcr_bits()
             + TCR_EL1::SH1::Inner
             + TCR_EL1::SH0::Inner
             + TCR_EL1::ORGN1::WriteBack_ReadAlloc_WriteAlloc_Cacheable
             + TCR_EL1::IRGN1::WriteBack_ReadAlloc_WriteAlloc_Cacheable
-            + TCR_EL1::T0SZ.val(16) // 16 MSB's are ignored
-            + TCR_EL1::T1SZ.val(16), // 16 MSB's are ignored
+            + TCR_EL1::T0SZ.val((64 - OUTPUT_ADDR_BITS) as usize)
+            + TCR_EL1::T1SZ.val((64 - OUTPUT_ADDR_BITS) as usize),
     );
 
     isb(SY);
 }
 
-/// Setup Memory Attribute Indirection Register to include Normal and Device Memory
+#[cfg(feature = "va.16kb_48bit")]
+fn granule_tcr_bits() -> FieldValue<u64, TCR_EL1::Register> {
+    TCR_EL1::IPS::Bits_48 + TCR_EL1::TG0::KiB_16 + TCR_EL1::TG1::KiB_16
+}
+
+#[cfg(feature = "va.64kb_42bit")]
+fn granule_tcr_bits() -> FieldValue<u64, TCR_EL1::Register> {
+    TCR_EL1::IPS::Bits_42 + TCR_EL1::TG0::KiB_64 + TCR_EL1::TG1::KiB_64
+}
+
+#[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
+fn granule_tcr_bits() -> FieldValue<u64, TCR_EL1::Register> {
+    TCR_EL1::IPS::Bits_48 + TCR_EL1::TG0::KiB_4 + TCR_EL1::TG1::KiB_4
+}
+
+/// Setup Memory Attribute Indirection Register with one slot per
+/// `vm::MemoryKind` variant -- `AttrIndx` on a leaf descriptor is one of
+/// `MAIR_IDX_*` in `mmu::translation_table`, which must match these slots.
 fn config_el1_memory_attributes() {
-    // Define the memory types being mapped.
     MAIR_EL1.write(
-        // Attribute 1 - Cacheable normal DRAM.
+        // Attr0 - Device-nGnRE.
+        MAIR_EL1::Attr0_Device::nonGathering_nonReordering_EarlyWriteAck +
+
+        // Attr1 - Cacheable Normal DRAM.
         MAIR_EL1::Attr1_Normal_Outer::WriteBack_NonTransient_ReadWriteAlloc +
-    MAIR_EL1::Attr1_Normal_Inner::WriteBack_NonTransient_ReadWriteAlloc +
+        MAIR_EL1::Attr1_Normal_Inner::WriteBack_NonTransient_ReadWriteAlloc +
+
+        // Attr2 - Non-cacheable Normal DRAM (write-combining).
+        MAIR_EL1::Attr2_Normal_Outer::NonCacheable +
+        MAIR_EL1::Attr2_Normal_Inner::NonCacheable +
+
+        // Attr3 - Device-nGnRnE.
+        MAIR_EL1::Attr3_Device::nonGathering_nonReordering_nonEarlyWriteAck +
 
-    // Attribute 0 - Device.
-    MAIR_EL1::Attr0_Device::nonGathering_nonReordering_EarlyWriteAck,
+        // Attr4 - Device-GRE.
+        MAIR_EL1::Attr4_Device::Gathering_Reordering_EarlyWriteAck,
     );
 }
 
@@ -118,6 +255,14 @@ register_bitfields! {u64,
         /// Bits for Software Use
         SWUSE OFFSET(55) NUMBITS(4) [],
 
+        /// Contiguous hint: set on every member of a naturally-aligned run
+        /// of 16 entries sharing identical output-address spacing and
+        /// attributes, letting the TLB fold them into a single entry.
+        CONTIGUOUS OFFSET(52) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         /// Unprivileged execute-never.
         UXN OFFSET(54) NUMBITS(1) [
             False = 0,
@@ -135,6 +280,17 @@ register_bitfields! {u64,
         /// Bits [47:21] of Output Address. Points to a 2MiB Physical Page.
         OUTPUT_ADDR_2MiB OFFSET(21) NUMBITS(27) [], // [47:21]
 
+        /// Dirty Bit Modifier: on a writable entry installed read-only
+        /// (see `AP`), lets hardware demote `AP[2]` to 0 on the first write
+        /// instead of raising a permission fault, so a clean and a dirty
+        /// page can be told apart without software tracking every write.
+        /// Only takes effect with `TCR_EL1.HD` set; without it, the first
+        /// write still faults and the handler must clear `AP[2]` itself.
+        DBM OFFSET(51) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         /// Access flag.
         AF OFFSET(10) NUMBITS(1) [
             False = 0,
@@ -174,6 +330,14 @@ register_bitfields! {u64,
         /// Bits for Software Use
         SWUSE OFFSET(55) NUMBITS(4) [],
 
+        /// Contiguous hint: set on every member of a naturally-aligned run
+        /// of 16 entries sharing identical output-address spacing and
+        /// attributes, letting the TLB fold them into a single entry.
+        CONTIGUOUS OFFSET(52) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         /// Unprivileged execute-never.
         UXN OFFSET(54) NUMBITS(1) [
             False = 0,
@@ -189,6 +353,17 @@ register_bitfields! {u64,
         /// Bits [47:12] of Output Address. Points to a 4KiB Physical Page.
         OUTPUT_ADDR_4KiB OFFSET(12) NUMBITS(36) [], // [47:12]
 
+        /// Dirty Bit Modifier: on a writable entry installed read-only
+        /// (see `AP`), lets hardware demote `AP[2]` to 0 on the first write
+        /// instead of raising a permission fault, so a clean and a dirty
+        /// page can be told apart without software tracking every write.
+        /// Only takes effect with `TCR_EL1.HD` set; without it, the first
+        /// write still faults and the handler must clear `AP[2]` itself.
+        DBM OFFSET(51) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         /// Access flag.
         AF OFFSET(10) NUMBITS(1) [
             False = 0,
@@ -228,6 +403,14 @@ register_bitfields! {u64,
         /// Bits for Software Use
         SWUSE OFFSET(55) NUMBITS(4) [],
 
+        /// Contiguous hint: set on every member of a naturally-aligned run
+        /// of 16 entries sharing identical output-address spacing and
+        /// attributes, letting the TLB fold them into a single entry.
+        CONTIGUOUS OFFSET(52) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         /// Unprivileged execute-never.
         UXN OFFSET(54) NUMBITS(1) [
             False = 0,
@@ -247,6 +430,17 @@ register_bitfields! {u64,
         /// Bits [47:30] of Output Address. Points to a 1GiB Physical Page. (Level 1)
         OUTPUT_ADDR_1GiB OFFSET(30) NUMBITS(18) [], // [47:30]
 
+        /// Dirty Bit Modifier: on a writable entry installed read-only
+        /// (see `AP`), lets hardware demote `AP[2]` to 0 on the first write
+        /// instead of raising a permission fault, so a clean and a dirty
+        /// page can be told apart without software tracking every write.
+        /// Only takes effect with `TCR_EL1.HD` set; without it, the first
+        /// write still faults and the handler must clear `AP[2]` itself.
+        DBM OFFSET(51) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
         /// Access flag.
         AF OFFSET(10) NUMBITS(1) [
             False = 0,
@@ -275,6 +469,67 @@ register_bitfields! {u64,
             Page = 1
         ],
 
+        VALID OFFSET(0) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ]
+    ],
+
+    // A Stage-2 level 1/2 block or level 3 page descriptor, as per ARMv8-A
+    // Architecture Reference Manual Figure D5-20. Output-address fields and
+    // TYPE/VALID share Stage-1's layout; access permissions and memory type
+    // do not -- see `stage2` module docs.
+    STAGE2_LAST_LEVEL_DESCRIPTOR [
+        /// Execute-never. Stage-2 has no privileged/unprivileged split, so
+        /// unlike Stage-1's `UXN`/`PXN` pair there is just one bit: a
+        /// mapping is executable from both EL0 and EL1 of the guest, or
+        /// neither.
+        XN OFFSET(54) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
+        /// Bits [47:12] of Output Address. Points to a 4KiB Physical Page. (Level 3)
+        OUTPUT_ADDR_4KiB OFFSET(12) NUMBITS(36) [], // [47:12]
+        /// Bits [47:21] of Output Address. Points to a 2MiB Physical Page. (Level 2)
+        OUTPUT_ADDR_2MiB OFFSET(21) NUMBITS(27) [], // [47:21]
+        /// Bits [47:30] of Output Address. Points to a 1GiB Physical Page. (Level 1)
+        OUTPUT_ADDR_1GiB OFFSET(30) NUMBITS(18) [], // [47:30]
+
+        /// Access flag.
+        AF OFFSET(10) NUMBITS(1) [
+            False = 0,
+            True = 1
+        ],
+
+        /// Shareability field.
+        SH OFFSET(8) NUMBITS(2) [
+            OuterShareable = 0b10,
+            InnerShareable = 0b11
+        ],
+
+        /// Stage-2 access permissions -- unlike Stage-1's `AP`, there is no
+        /// EL1/EL0 split to encode; a mapping is simply unreadable,
+        /// read-only, write-only, or read-write from the guest.
+        S2AP OFFSET(6) NUMBITS(2) [
+            NoAccess = 0b00,
+            ReadOnly = 0b01,
+            WriteOnly = 0b10,
+            ReadWrite = 0b11
+        ],
+
+        /// Stage-2 memory type, encoded directly rather than via an index
+        /// into MAIR_EL1 -- Stage-2 translations have no MAIR of their own.
+        MemAttr OFFSET(2) NUMBITS(4) [
+            Device_nGnRE = 0b0001,
+            Normal_Cacheable = 0b1111
+        ],
+
+        TYPE OFFSET(1) NUMBITS(1) [
+            Block = 0,
+            Page = 1
+        ],
+
         VALID OFFSET(0) NUMBITS(1) [
             False = 0,
             True = 1
@@ -0,0 +1,316 @@
+//! Reverse mapping (`phys2virt`) -- answers "which virtual address maps this
+//! physical frame?", the inverse of [`super::translation_table`]'s
+//! `virt2phy`. Needed for page reclamation (find every mapping of a frame
+//! before handing it back to the allocator), COW fork, and detecting
+//! accidental double-maps of the same frame.
+//!
+//! Backed by a two-level sparse bitmap in the style of a roaring bitmap /
+//! sparsemap: physical memory is divided into fixed 2 MiB chunks
+//! ([`FRAMES_PER_CHUNK`] 4 KiB frames each), and a [`Chunk`] -- a bitmap of
+//! which frames are mapped plus the owning [`VirtualAddress`] per set bit --
+//! is allocated only once a chunk's first frame is recorded. The top-level
+//! index ([`ReverseMap::chunks`]) holds one small [`ChunkHeader`] per
+//! *populated* chunk; an unpopulated chunk is simply absent from it, so
+//! memory cost is O(mapped frames), not O(physical address space).
+//!
+//! This only tracks one owning `VirtualAddress` per frame, not the full set
+//! [`TranslationListener`]'s callers could in principle produce -- simultaneous
+//! aliasing of one frame from two virtual addresses is surfaced as
+//! [`Error::DuplicatePhysicalMapping`] from [`ReverseMap::record`] rather
+//! than silently tracked as a second owner, since every caller in this tree
+//! so far (`map`/`unmap_range`) maps a frame from exactly one place at a
+//! time. Storing the full set is follow-up work for whenever a COW/aliasing
+//! caller actually needs it.
+//!
+//! [`TranslationListener`]: super::translation_table::TranslationListener
+//!
+//! `ReverseMap` is not wired up as a [`TranslationListener`] itself:
+//! `on_map`/`on_unmap` don't carry a `PhysicalPageAllocator` to allocate or
+//! free `Chunk`s with, so a caller that wants `record`/`forget` kept in sync
+//! with a `TranslationTable` has to call them itself alongside `map`/
+//! `unmap_range` for now.
+
+use core::{alloc::Layout, cell::UnsafeCell, ptr::NonNull};
+
+use heapless::Vec;
+
+use crate::{
+    address::{Address, PhysicalAddress, VirtualAddress},
+    error::{Error, Result},
+    vm::PhysicalPageAllocator,
+};
+
+use super::utils::consts::{FOUR_KIB, TWO_MIB};
+
+/// 4 KiB frames tracked per 2 MiB chunk.
+const FRAMES_PER_CHUNK: usize = TWO_MIB / FOUR_KIB;
+const BITMAP_WORDS: usize = FRAMES_PER_CHUNK / u64::BITS as usize;
+
+/// Maximum number of chunks this `ReverseMap` can have populated at once --
+/// sized for the whole of a Raspberry Pi 3's DRAM (`address_map::DRAM_SIZE`,
+/// ~992 MiB) divided into 2 MiB chunks, so every frame of physical memory
+/// can be tracked at once even in the worst case of every chunk being
+/// populated.
+const MAX_CHUNKS: usize = 512;
+
+/// One populated chunk's frame-presence bitmap and owning `VirtualAddress`
+/// per set bit. Allocated the moment a chunk's first frame is recorded
+/// ([`ReverseMap::chunk_mut`]) and freed the moment its last frame is
+/// forgotten ([`ReverseMap::forget_frame`]).
+struct Chunk {
+    bitmap: [u64; BITMAP_WORDS],
+    vaddrs: [VirtualAddress; FRAMES_PER_CHUNK],
+}
+
+/// Compact per-populated-chunk header kept in [`ReverseMap::chunks`] --
+/// the "header word" the sparsemap/roaring design calls for, pointing at
+/// the chunk's lazily-allocated dense bitmap.
+#[derive(Clone, Copy)]
+struct ChunkHeader {
+    /// Chunk-aligned (2 MiB) base physical address this header covers.
+    base: PhysicalAddress,
+    chunk: NonNull<Chunk>,
+}
+
+/// Splits `paddr` into its chunk-aligned base and the frame index within
+/// that chunk.
+fn chunk_coords(paddr: PhysicalAddress) -> (PhysicalAddress, usize) {
+    let raw = paddr.as_raw_ptr();
+    let offset_in_chunk = raw % TWO_MIB;
+    (PhysicalAddress::new(raw - offset_in_chunk), offset_in_chunk / FOUR_KIB)
+}
+
+#[derive(Default)]
+pub struct ReverseMap {
+    chunks: UnsafeCell<Vec<ChunkHeader, MAX_CHUNKS>>,
+}
+
+impl ReverseMap {
+    /// Records that `vaddr` maps the `num_frames` 4 KiB physical frames
+    /// starting at `paddr`, allocating a backing [`Chunk`] for any newly
+    /// populated 2 MiB span as it goes. Fails with
+    /// [`Error::DuplicatePhysicalMapping`] the moment a frame already has a
+    /// recorded owner, rather than silently overwriting it -- everything
+    /// recorded before the failing frame is left in place, mirroring
+    /// `TranslationTable::map_impl`'s own "best effort, caller decides what
+    /// to do with a partial failure" behavior.
+    pub fn record<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr: VirtualAddress,
+        paddr: PhysicalAddress,
+        num_frames: usize,
+        desc_alloc: &DescAlloc,
+    ) -> Result<()> {
+        for i in 0..num_frames {
+            self.record_frame(vaddr + i * FOUR_KIB, paddr + i * FOUR_KIB, desc_alloc)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the record of the `num_frames` physical frames starting at
+    /// `paddr`, freeing any [`Chunk`] left fully empty behind it. Frames
+    /// that were never recorded are silently skipped, the same way
+    /// `TranslationTable::unmap_range` tolerates unmapping an already-empty
+    /// range.
+    pub fn forget<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        paddr: PhysicalAddress,
+        num_frames: usize,
+        desc_alloc: &DescAlloc,
+    ) {
+        for i in 0..num_frames {
+            self.forget_frame(paddr + i * FOUR_KIB, desc_alloc);
+        }
+    }
+
+    /// The virtual address currently recorded as owning the physical frame
+    /// `paddr` falls in, if any.
+    pub fn phys2virt(&self, paddr: PhysicalAddress) -> Option<VirtualAddress> {
+        let (chunk_base, frame_idx) = chunk_coords(paddr);
+        let chunks = unsafe { &*self.chunks.get() };
+        let header = chunks.iter().find(|h| h.base == chunk_base)?;
+        let chunk = unsafe { header.chunk.as_ref() };
+
+        let (word, bit) = (frame_idx / u64::BITS as usize, frame_idx % u64::BITS as usize);
+        (chunk.bitmap[word] & (1 << bit) != 0).then(|| chunk.vaddrs[frame_idx])
+    }
+
+    fn record_frame<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr: VirtualAddress,
+        paddr: PhysicalAddress,
+        desc_alloc: &DescAlloc,
+    ) -> Result<()> {
+        let (chunk_base, frame_idx) = chunk_coords(paddr);
+        let chunk = self.chunk_mut(chunk_base, desc_alloc)?;
+
+        let (word, bit) = (frame_idx / u64::BITS as usize, frame_idx % u64::BITS as usize);
+        if chunk.bitmap[word] & (1 << bit) != 0 {
+            return Err(Error::DuplicatePhysicalMapping(paddr));
+        }
+
+        chunk.bitmap[word] |= 1 << bit;
+        chunk.vaddrs[frame_idx] = vaddr;
+
+        Ok(())
+    }
+
+    fn forget_frame<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        paddr: PhysicalAddress,
+        desc_alloc: &DescAlloc,
+    ) {
+        let (chunk_base, frame_idx) = chunk_coords(paddr);
+        let chunks = unsafe { &mut *self.chunks.get() };
+        let Some(pos) = chunks.iter().position(|h| h.base == chunk_base) else {
+            return;
+        };
+
+        let (word, bit) = (frame_idx / u64::BITS as usize, frame_idx % u64::BITS as usize);
+        let now_empty = {
+            let chunk = unsafe { chunks[pos].chunk.as_mut() };
+            chunk.bitmap[word] &= !(1u64 << bit);
+            chunk.bitmap.iter().all(|w| *w == 0)
+        };
+
+        if now_empty {
+            let header = chunks.swap_remove(pos);
+            unsafe {
+                desc_alloc.deallocate(header.chunk.cast::<u8>(), Layout::new::<Chunk>());
+            }
+        }
+    }
+
+    /// Returns the already-allocated `Chunk` covering `chunk_base`, or
+    /// allocates and zero-initializes a fresh one (an all-zero bitmap, i.e.
+    /// no frames recorded yet) the first time this chunk is populated.
+    fn chunk_mut<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        chunk_base: PhysicalAddress,
+        desc_alloc: &DescAlloc,
+    ) -> Result<&mut Chunk> {
+        let chunks = unsafe { &mut *self.chunks.get() };
+
+        if let Some(pos) = chunks.iter().position(|h| h.base == chunk_base) {
+            return Ok(unsafe { chunks[pos].chunk.as_mut() });
+        }
+
+        let layout = Layout::new::<Chunk>();
+        let chunk = desc_alloc
+            .allocate_zeroed(layout)
+            .map_err(|_| Error::PhysicalOOM)?
+            .as_non_null_ptr()
+            .cast::<Chunk>();
+
+        if let Err(header) = chunks.push(ChunkHeader { base: chunk_base, chunk }) {
+            unsafe {
+                desc_alloc.deallocate(header.chunk.cast::<u8>(), layout);
+            }
+            return Err(Error::ReverseMapExhausted);
+        }
+
+        Ok(unsafe {
+            chunks
+                .last_mut()
+                .unwrap_or_else(|| crate::bug!("chunk header was just pushed"))
+                .chunk
+                .as_mut()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::{
+        alloc::{AllocError, Allocator},
+        cell::RefCell,
+    };
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct TestAllocator {
+        mem: RefCell<HashMap<*mut u8, Layout>>,
+    }
+
+    unsafe impl Allocator for TestAllocator {
+        fn allocate(&self, layout: Layout) -> core::result::Result<NonNull<[u8]>, AllocError> {
+            let ptr = unsafe { std::alloc::alloc(layout) };
+            self.mem.borrow_mut().insert(ptr, layout);
+            unsafe {
+                Ok(NonNull::slice_from_raw_parts(
+                    NonNull::new_unchecked(ptr),
+                    layout.size(),
+                ))
+            }
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            let ptr = ptr.addr().get() as *mut u8;
+            self.mem.borrow_mut().remove(&ptr);
+            unsafe { std::alloc::dealloc(ptr, layout) };
+        }
+    }
+
+    impl Drop for TestAllocator {
+        fn drop(&mut self) {
+            for (ptr, layout) in self.mem.borrow().iter() {
+                unsafe { std::alloc::dealloc(*ptr, *layout) };
+            }
+        }
+    }
+
+    impl PhysicalPageAllocator for TestAllocator {}
+
+    #[test]
+    fn record_then_phys2virt_round_trips() {
+        let alloc = TestAllocator::default();
+        let revmap = ReverseMap::default();
+        let vaddr = VirtualAddress::new(0x4000_0000).unwrap();
+        let paddr = PhysicalAddress::new(3 * TWO_MIB);
+
+        revmap.record(vaddr, paddr, 4, &alloc).unwrap();
+
+        for i in 0..4 {
+            assert_eq!(
+                revmap.phys2virt(paddr + i * FOUR_KIB),
+                Some(vaddr + i * FOUR_KIB)
+            );
+        }
+        assert_eq!(revmap.phys2virt(paddr + 4 * FOUR_KIB), None);
+    }
+
+    #[test]
+    fn record_twice_over_same_frame_fails() {
+        let alloc = TestAllocator::default();
+        let revmap = ReverseMap::default();
+        let paddr = PhysicalAddress::new(5 * TWO_MIB);
+
+        revmap
+            .record(VirtualAddress::new(0x1000_0000).unwrap(), paddr, 1, &alloc)
+            .unwrap();
+
+        let result = revmap.record(VirtualAddress::new(0x2000_0000).unwrap(), paddr, 1, &alloc);
+        assert!(matches!(result, Err(Error::DuplicatePhysicalMapping(_))));
+    }
+
+    #[test]
+    fn forget_clears_and_frees_empty_chunks() {
+        let alloc = TestAllocator::default();
+        let revmap = ReverseMap::default();
+        let vaddr = VirtualAddress::new(0x4000_0000).unwrap();
+        let paddr = PhysicalAddress::new(9 * TWO_MIB);
+
+        revmap.record(vaddr, paddr, 1, &alloc).unwrap();
+        assert_eq!(revmap.phys2virt(paddr), Some(vaddr));
+        assert_eq!(unsafe { (*revmap.chunks.get()).len() }, 1);
+
+        revmap.forget(paddr, 1, &alloc);
+        assert_eq!(revmap.phys2virt(paddr), None);
+        assert_eq!(unsafe { (*revmap.chunks.get()).len() }, 0);
+    }
+}
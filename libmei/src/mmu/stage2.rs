@@ -0,0 +1,120 @@
+//! Stage-2 (IPA -> PA) leaf descriptor attributes.
+//!
+//! Everything else in `mmu` -- `TranslationTable`, `map_impl`, `virt2phy`,
+//! the `TraverseIterator` walk -- is still Stage-1 only (TTBR0/TTBR1,
+//! loaded at EL1). This module only factors out the two places Stage-2's
+//! leaf descriptor format differs from Stage-1's: access permissions use
+//! the 2-bit `S2AP` field instead of Stage-1's EL1/EL0-split `AP`, and
+//! memory type is encoded directly in the 4-bit `MemAttr` field rather
+//! than through an `AttrIndx` into `MAIR_EL1` (Stage-2 translations have
+//! no MAIR of their own).
+//!
+//! Wiring a `VTTBR_EL2`-loaded `TranslationTable` through these needs the
+//! kernel to actually stay resident at EL2 and field guest exits, which
+//! this kernel doesn't do -- EL2 is touched only transiently, to drop
+//! straight to EL1 at boot (see `arch::boot::switch_from_el2_to_el1`).
+//! These helpers exist so that follow-up work has a descriptor-format
+//! starting point rather than a blank page.
+
+use tock_registers::{
+    fields::FieldValue,
+    interfaces::{ReadWriteable, Readable},
+    registers::InMemoryRegister,
+};
+
+use crate::vm::{AccessPermissions, MemoryKind};
+
+use super::STAGE2_LAST_LEVEL_DESCRIPTOR;
+
+type Stage2LastLevelDescriptor = InMemoryRegister<u64, STAGE2_LAST_LEVEL_DESCRIPTOR::Register>;
+
+/// Decodes the Stage-2 access permissions of an existing leaf descriptor.
+/// Unlike Stage-1's `AP`, there's no privileged/unprivileged split to
+/// recover -- a guest mapping is simply readable and/or writable from
+/// both EL1 and EL0 of the guest at once.
+pub fn parse_access_perms(desc: u64) -> AccessPermissions {
+    let ll_desc = Stage2LastLevelDescriptor::new(desc);
+
+    let mut access_perms = match ll_desc.read(STAGE2_LAST_LEVEL_DESCRIPTOR::S2AP) {
+        0b01 => AccessPermissions::EL1_READ | AccessPermissions::EL0_READ,
+        0b10 => AccessPermissions::EL1_WRITE | AccessPermissions::EL0_WRITE,
+        0b11 => {
+            AccessPermissions::EL1_READ
+                | AccessPermissions::EL1_WRITE
+                | AccessPermissions::EL0_READ
+                | AccessPermissions::EL0_WRITE
+        }
+        _ => AccessPermissions::empty(),
+    };
+
+    if !ll_desc.is_set(STAGE2_LAST_LEVEL_DESCRIPTOR::XN)
+        && !access_perms.contains(AccessPermissions::EL1_WRITE)
+    {
+        access_perms |= AccessPermissions::EL1_EXECUTE | AccessPermissions::EL0_EXECUTE;
+    }
+
+    access_perms
+}
+
+/// Decodes the Stage-2 `MemAttr` field directly into a `MemoryKind` --
+/// there's no MAIR indirection to resolve, unlike Stage-1's `AttrIndx`. Only
+/// the two `MemAttr` encodings below are defined on
+/// `STAGE2_LAST_LEVEL_DESCRIPTOR`, so that's the full range `encode_attrs`
+/// can produce and this can decode; the non-cacheable/nGnRnE/GRE
+/// `MemoryKind` variants have no Stage-2 `MemAttr` encoding yet.
+pub fn parse_memory_kind(desc: u64) -> MemoryKind {
+    let ll_desc = Stage2LastLevelDescriptor::new(desc);
+
+    match ll_desc.read(STAGE2_LAST_LEVEL_DESCRIPTOR::MemAttr) {
+        STAGE2_LAST_LEVEL_DESCRIPTOR::MemAttr::Normal_Cacheable.value => {
+            MemoryKind::NormalCacheable
+        }
+        _ => MemoryKind::DeviceNonGatheringNonReorderingEarlyAck,
+    }
+}
+
+/// Builds the attribute bits (everything but `VALID`/`TYPE`/output-address)
+/// of a Stage-2 leaf descriptor, mirroring `translation_table::parse_map_attrs`.
+pub fn encode_attrs(access_perms: &AccessPermissions, memory_kind: MemoryKind) -> u64 {
+    let ll_desc = Stage2LastLevelDescriptor::new(0);
+
+    let el1_rw = access_perms.contains(AccessPermissions::EL1_READ | AccessPermissions::EL1_WRITE);
+    let el0_rw = access_perms.contains(AccessPermissions::EL0_READ | AccessPermissions::EL0_WRITE);
+    let el1_ro = access_perms.contains(AccessPermissions::EL1_READ);
+    let el0_ro = access_perms.contains(AccessPermissions::EL0_READ);
+
+    let s2ap = match (el1_rw || el0_rw, el1_ro || el0_ro) {
+        (true, _) => STAGE2_LAST_LEVEL_DESCRIPTOR::S2AP::ReadWrite,
+        (false, true) => STAGE2_LAST_LEVEL_DESCRIPTOR::S2AP::ReadOnly,
+        (false, false) => STAGE2_LAST_LEVEL_DESCRIPTOR::S2AP::NoAccess,
+    };
+    ll_desc.modify(s2ap);
+
+    if access_perms.contains(AccessPermissions::EL1_EXECUTE)
+        || access_perms.contains(AccessPermissions::EL0_EXECUTE)
+    {
+        ll_desc.modify(STAGE2_LAST_LEVEL_DESCRIPTOR::XN::False);
+    } else {
+        ll_desc.modify(STAGE2_LAST_LEVEL_DESCRIPTOR::XN::True);
+    }
+
+    // `STAGE2_LAST_LEVEL_DESCRIPTOR::MemAttr` only has the two encodings
+    // below defined, unlike Stage-1's 5-slot MAIR -- every Normal kind maps
+    // to cacheable Normal and every Device kind to nGnRE until Stage-2 gets
+    // more `MemAttr` encodings of its own.
+    let mem_attr: FieldValue<u64, STAGE2_LAST_LEVEL_DESCRIPTOR::Register> = match memory_kind {
+        MemoryKind::NormalCacheable | MemoryKind::NormalNonCacheable => {
+            ll_desc.modify(STAGE2_LAST_LEVEL_DESCRIPTOR::SH::InnerShareable);
+            STAGE2_LAST_LEVEL_DESCRIPTOR::MemAttr::Normal_Cacheable
+        }
+        MemoryKind::DeviceNonGatheringNonReorderingEarlyAck
+        | MemoryKind::DeviceNonGatheringNonReorderingNonEarlyAck
+        | MemoryKind::DeviceGatheringReorderingEarlyAck => {
+            ll_desc.modify(STAGE2_LAST_LEVEL_DESCRIPTOR::SH::OuterShareable);
+            STAGE2_LAST_LEVEL_DESCRIPTOR::MemAttr::Device_nGnRE
+        }
+    };
+    ll_desc.modify(mem_attr);
+
+    ll_desc.get()
+}
@@ -0,0 +1,93 @@
+//! TLB invalidation and cache-maintenance operations for the AArch64 stage-1
+//! translation tables. `TranslationTable::activate` already flushes the whole
+//! TLB once at boot; everything here is for mutating a table that's already
+//! live in TTBR0/TTBR1, where stale TLB entries or dirty cache lines would
+//! otherwise leave a remap silently using the old translation.
+
+use core::arch::asm;
+
+use aarch64_cpu::asm::barrier::{dsb, isb, SY};
+
+use crate::address::{Address, VirtualAddress};
+
+/// Cortex-A53 (rpi3) and every other AArch64 core this crate targets use a
+/// 64-byte D-cache line.
+const DCACHE_LINE_SIZE: usize = 64;
+
+/// Bit position of the ASID field in `TTBR0_EL1`/`TTBR1_EL1` and the operand
+/// of `TLBI *ASID*` variants, whether `TCR_EL1::AS` selects an 8- or 16-bit
+/// ASID (the unused high bits of the 16-bit field are simply RES0).
+pub(super) const ASID_SHIFT: usize = 48;
+
+/// Invalidates every TLB entry matching `va` in the current translation
+/// regime (`TLBI VAE1`), so a subsequent access is forced to re-walk the
+/// tables rather than reuse a stale cached translation.
+pub fn invalidate_va(va: VirtualAddress) {
+    let page = va.as_raw_ptr() >> 12;
+
+    unsafe {
+        asm!(
+            "dsb ishst",
+            "tlbi vae1, {page}",
+            "dsb ish",
+            "isb",
+            page = in(reg) page,
+            options(nostack)
+        );
+    }
+}
+
+/// Invalidates every TLB entry tagged with `asid` (`TLBI ASIDE1`), leaving
+/// every other address space's cached translations untouched. Used to tear
+/// down a process's mappings without the global flush `invalidate_all` would
+/// force on every other running process.
+pub fn invalidate_asid(asid: u16) {
+    let tagged_asid = (asid as usize) << ASID_SHIFT;
+
+    unsafe {
+        asm!(
+            "dsb ishst",
+            "tlbi aside1, {tagged_asid}",
+            "dsb ish",
+            "isb",
+            tagged_asid = in(reg) tagged_asid,
+            options(nostack)
+        );
+    }
+}
+
+/// Invalidates the entire TLB for the current translation regime (`TLBI
+/// VMALLE1`).
+pub fn invalidate_all() {
+    unsafe {
+        asm!(
+            "dsb ishst",
+            "tlbi vmalle1",
+            "dsb ish",
+            "isb",
+            options(nomem, nostack)
+        );
+    }
+}
+
+/// Cleans (writes back without invalidating) every D-cache line covering
+/// `[start, end)`, so descriptor words written through the cache are visible
+/// to anyone walking the table through a non-cacheable alias (e.g. another
+/// core, or the MMU's own table walker before caching of the walk is
+/// guaranteed coherent).
+pub fn clean_dcache_range(start: *const u8, end: *const u8) {
+    let mut addr = (start as usize) & !(DCACHE_LINE_SIZE - 1);
+    let end = end as usize;
+
+    dsb(SY);
+
+    while addr < end {
+        unsafe {
+            asm!("dc cvac, {addr}", addr = in(reg) addr, options(nostack));
+        }
+        addr += DCACHE_LINE_SIZE;
+    }
+
+    dsb(SY);
+    isb(SY);
+}
@@ -8,25 +8,52 @@
 //!     - Mappings can be created/modified/destroyed dynamically
 //!     - Supports splitting/merging adjacent mappings.
 //!     - This is loaded into TTBR0 and is used in Un-privileged (User) mode.
+//!
+//! ## `va.*` feature gates
+//!
+//! `address::VirtualAddress` and `mmu`'s `TCR_EL1` setup adapt to the
+//! `va.16kb_48bit`/`va.64kb_42bit` cargo features (see their doc comments),
+//! and so do `NUM_TABLE_DESC_ENTRIES` below (512/2048/8192 entries, mirroring
+//! `address::VIRTUAL_ADDRESS_LEVEL_IDX_BITS`) and `utils::VIRTUAL_ADDRESS_PAGE_OFFSET_NBITS`
+//! (mirroring `mmu::GRANULE_SIZE_BITS`). But the block/page descriptor
+//! installation below -- `install_page_descs`, `install_l2_block_desc`,
+//! `install_l1_block_desc`, and `find_best_mapping_scheme`'s `ALIGNMENTS`
+//! (`FOUR_KIB`/`TWO_MIB`/`ONE_GIB`) span sizes -- is still hardcoded to the
+//! default 4 KiB-granule block sizes, and the `OUTPUT_ADDR_*` descriptor
+//! fields in `mmu::mod` are still the 4 KiB-granule field widths regardless of
+//! which `va.*` feature is active. Since that would silently build/walk
+//! tables with the wrong block sizes against hardware configured for a
+//! different granule, `mmu::mod` has a `compile_error!` that blocks selecting
+//! either `va.*` feature until this gap is closed -- teaching this module the
+//! other granules' output-address field widths and level counts
+//! (`va.64kb_42bit` walks only 2 levels, not 4) is tracked as follow-up work.
+//!
+//! `TranslationTable` itself is still Stage-1 only. The sibling
+//! `mmu::stage2` module factors out Stage-2's leaf-descriptor attribute
+//! encoding (`S2AP`, `MemAttr`) for when a `VTTBR_EL2`-loaded table is
+//! needed, but generalizing `map_impl`/`virt2phy`/`TraverseIterator` over a
+//! `Stage1`/`Stage2` split is follow-up work, same as the `va.*` gap above.
 
 use core::{
     alloc::Layout,
+    arch::asm,
     cell::UnsafeCell,
     cmp::{max, min},
     mem::size_of,
-    ops::Range,
+    ops::{Range, RangeInclusive},
     ptr::NonNull,
 };
 
+use aarch64_cpu::registers::{TTBR0_EL1, TTBR1_EL1};
 use heapless::Vec;
 
 use tock_registers::{
-    interfaces::{ReadWriteable, Readable},
+    interfaces::{ReadWriteable, Readable, Writeable},
     registers::InMemoryRegister,
 };
 
 use crate::{
-    address::{Address, AddressTranslationLevel, PhysicalAddress, VirtualAddress},
+    address::{Address, AddressTranslationLevel, PhysicalAddress, TTBR, VirtualAddress},
     bug,
     error::{Error, Result},
     mmu::NEXT_LEVEL_TABLE_ADDR_SHIFT,
@@ -34,12 +61,21 @@ use crate::{
 };
 
 use super::{
+    tlb,
     utils::{consts::MAX_TRANSLATION_LEVELS, *},
     GRANULE_SIZE, LEVEL_1_OUTPUT_ADDR_SHIFT, LEVEL_2_OUTPUT_ADDR_SHIFT, LEVEL_3_OUTPUT_ADDR_SHIFT,
     STAGE1_BLOCK_DESCRIPTOR, STAGE1_LAST_LEVEL_DESCRIPTOR, STAGE1_PAGE_DESCRIPTOR,
     STAGE1_TABLE_DESCRIPTOR, TRANSLATION_TABLE_DESC_ALIGN,
 };
 
+/// Entries per `DescriptorTable`, i.e. `granule_size / size_of::<u64>()` --
+/// mirrors `address::VIRTUAL_ADDRESS_LEVEL_IDX_BITS`, which is what actually
+/// carves the per-level index out of a `VirtualAddress` for each granule.
+#[cfg(feature = "va.16kb_48bit")]
+const NUM_TABLE_DESC_ENTRIES: usize = 2048;
+#[cfg(feature = "va.64kb_42bit")]
+const NUM_TABLE_DESC_ENTRIES: usize = 8192;
+#[cfg(not(any(feature = "va.16kb_48bit", feature = "va.64kb_42bit")))]
 const NUM_TABLE_DESC_ENTRIES: usize = 512;
 const INVALID_DESCRIPTOR: u64 = 0;
 const TRANSLATION_LEVELS: &[AddressTranslationLevel] = &[
@@ -52,6 +88,21 @@ const ONE_GIB: usize = consts::ONE_GIB;
 const TWO_MIB: usize = consts::TWO_MIB;
 const FOUR_KIB: usize = consts::FOUR_KIB;
 
+/// MAIR_EL1 indices `mmu::config_el1_memory_attributes` installs each
+/// `MemoryKind` as. `AttrIndx` on every leaf descriptor is one of these.
+const MAIR_IDX_DEVICE_NGNRE: u64 = 0;
+const MAIR_IDX_NORMAL_CACHEABLE: u64 = 1;
+const MAIR_IDX_NORMAL_NONCACHEABLE: u64 = 2;
+const MAIR_IDX_DEVICE_NGNRNE: u64 = 3;
+const MAIR_IDX_DEVICE_GRE: u64 = 4;
+
+/// Arbitrary non-zero `SWUSE` value marking a leaf descriptor as reserved
+/// but not yet backed by a frame -- chosen only to not collide with
+/// `INVALID_DESCRIPTOR`'s all-zero pattern, since `SWUSE` is otherwise
+/// unused by hardware or anywhere else in this file. See
+/// `Descriptor::Reserved`/`to_raw_desc`.
+const RESERVED_SWUSE_MARKER: u64 = 0b1010;
+
 type Stage1LastLevelDescriptor = InMemoryRegister<u64, STAGE1_LAST_LEVEL_DESCRIPTOR::Register>;
 type Stage1PageDescriptor = InMemoryRegister<u64, STAGE1_PAGE_DESCRIPTOR::Register>;
 type Stage1TableDescriptor = InMemoryRegister<u64, STAGE1_TABLE_DESCRIPTOR::Register>;
@@ -71,6 +122,41 @@ impl Default for DescriptorTable {
     }
 }
 
+/// Maximum listeners a single `TranslationTable` can have registered via
+/// [`TranslationTable::add_listener`] -- generous for how many kernel
+/// subsystems (TLB maintenance, a shadow mapping table, ...) realistically
+/// watch one table's mutations at once.
+const MAX_LISTENERS: usize = 4;
+
+/// Observes mutations [`TranslationTable`] performs, at coalesced block
+/// granularity -- a single 1 GiB unmap fires one [`Self::on_unmap`], not
+/// one per 4 KiB page, mirroring the granularity `traverse` itself yields.
+/// Lets a caller emit exactly the `TLBI` maintenance a change needs, or
+/// keep a shadow of the active mappings, without re-traversing the table
+/// after the fact. Borrows the `MemoryListener` pattern from QEMU's
+/// `memory.c`. Every method has a no-op default, so a listener only needs
+/// to override what it cares about.
+pub trait TranslationListener {
+    fn on_map(
+        &self,
+        _vaddr: VirtualAddress,
+        _paddr: PhysicalAddress,
+        _size: usize,
+        _perms: AccessPermissions,
+    ) {
+    }
+
+    fn on_unmap(&self, _vaddr: VirtualAddress, _size: usize) {}
+
+    fn on_permissions_changed(
+        &self,
+        _vaddr: VirtualAddress,
+        _size: usize,
+        _perms: AccessPermissions,
+    ) {
+    }
+}
+
 /// This stores the root of Translation Table
 /// Address of `root` is stored in TTBR0/1.
 ///
@@ -88,9 +174,45 @@ impl Default for DescriptorTable {
 ///
 /// Though, an 1 GiB VA mapping consisting of 512 2MiB PA pages is only needed to be aligned at 2MiB boundary.
 /// Similarly, a 2 MiB VA mapping consisting of 512 4KiB PA pages is only needed to be aligned at 4KiB boundary.
+/// Maximum listeners a single `TranslationTable` can have registered via
+/// [`TranslationTable::add_listener`] -- generous for how many kernel
+/// subsystems (TLB maintenance, a shadow mapping table, ...) realistically
+/// watch one table's mutations at once.
+const MAX_LISTENERS: usize = 4;
+
+/// Observes mutations [`TranslationTable`] performs, at coalesced block
+/// granularity -- a single 1 GiB unmap fires one [`Self::on_unmap`], not
+/// one per 4 KiB page, mirroring the granularity `traverse` itself yields.
+/// Lets a caller emit exactly the `TLBI` maintenance a change needs, or
+/// keep a shadow of the active mappings, without re-traversing the table
+/// after the fact. Borrows the `MemoryListener` pattern from QEMU's
+/// `memory.c`. Every method has a no-op default, so a listener only needs
+/// to override what it cares about.
+pub trait TranslationListener {
+    fn on_map(
+        &self,
+        _vaddr: VirtualAddress,
+        _paddr: PhysicalAddress,
+        _size: usize,
+        _perms: AccessPermissions,
+    ) {
+    }
+
+    fn on_unmap(&self, _vaddr: VirtualAddress, _size: usize) {}
+
+    fn on_permissions_changed(
+        &self,
+        _vaddr: VirtualAddress,
+        _size: usize,
+        _perms: AccessPermissions,
+    ) {
+    }
+}
+
 #[derive(Default)]
 pub struct TranslationTable {
     root: DescriptorTable,
+    listeners: UnsafeCell<Vec<&'static dyn TranslationListener, MAX_LISTENERS>>,
 }
 
 impl TranslationTable {
@@ -100,6 +222,7 @@ impl TranslationTable {
     ) -> Result<Self> {
         let tt = Self {
             root: DescriptorTable::default(),
+            listeners: UnsafeCell::new(Vec::default()),
         };
 
         for map in maps {
@@ -109,13 +232,118 @@ impl TranslationTable {
         Ok(tt)
     }
 
+    /// Builds a table where every `regions[i]`'s physical range is identity
+    /// mapped -- `vaddr == paddr` -- automatically picking the largest
+    /// aligned block size (1 GiB/2 MiB/4 KiB) for each region the same way
+    /// [`Self::map`] does, via `find_best_mapping_scheme`. This is the usual
+    /// bootstrap pattern for bringing the MMU up before switching to a real
+    /// address space, and saves every caller from hand-filling
+    /// `MapDesc::virt_addr` to match `phy_addr`. Pair it with
+    /// [`Self::get_base_address`] to get the value to load into TTBR via
+    /// [`Self::activate`].
+    pub fn new_identity<DescAlloc: PhysicalPageAllocator>(
+        regions: &[MemoryMap],
+        desc_alloc: &DescAlloc,
+    ) -> Result<Self> {
+        let tt = Self {
+            root: DescriptorTable::default(),
+            listeners: UnsafeCell::new(Vec::default()),
+        };
+
+        for map in regions {
+            let desc = map.desc();
+            let vaddr = VirtualAddress::new(desc.physical_address().as_raw_ptr() as usize)?;
+            let identity_desc = MapDesc::new(
+                desc.physical_address(),
+                vaddr,
+                desc.num_pages(),
+                desc.access_permissions(),
+            );
+            let identity_map = MemoryMap::new(identity_desc, map.kind());
+
+            tt.map_impl(&parse_memory_map(&identity_map), desc_alloc, &identity_map)?;
+        }
+
+        Ok(tt)
+    }
+
     /// Add Mapping to translation table
     pub fn map<DescAlloc: PhysicalPageAllocator>(
         &self,
         map: &MemoryMap,
         desc_alloc: &DescAlloc,
     ) -> Result<()> {
-        self.map_impl(&parse_memory_map(map), desc_alloc, map)
+        self.map_impl(&parse_memory_map(map), desc_alloc, map)?;
+
+        let desc = map.desc();
+
+        // The root descriptor `map_impl` just wrote needs to make it out of
+        // the cache before anyone (another core, or this core with caching
+        // momentarily off) walks the table through a non-cacheable alias,
+        // and the stale translations it replaces need to leave the TLB.
+        let base = self.get_base_address() as *const u8;
+        tlb::clean_dcache_range(base, unsafe { base.add(size_of::<DescriptorTable>()) });
+
+        // `map_impl` picks the biggest block (1 GiB/2 MiB/4 KiB) alignment
+        // allows for each span, so re-walk what actually landed rather than
+        // assuming 4 KiB pages: a single `TLBI VAE1` targeting any VA inside
+        // a block invalidates that whole entry, so one invalidate per
+        // installed block is both necessary and sufficient.
+        let vaddr_start = desc.virtual_address();
+        let vaddr_end = vaddr_start + desc.num_pages() * GRANULE_SIZE;
+        for yielded in self.traverse(vaddr_start..vaddr_end, false) {
+            if let TraverseYield::PhysicalBlock(block) = yielded? {
+                tlb::invalidate_va(block.vaddr());
+                self.notify_map(
+                    block.vaddr(),
+                    block.phy_block().start,
+                    block.size(),
+                    block.access_perms(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Registers `listener` to be notified of every future `map`/
+    /// `unmap_range`/`set_access_permissions` call on this table. Mappings
+    /// already installed before this call do not retroactively fire
+    /// `on_map` -- a listener that needs to know about those must be handed
+    /// them some other way (e.g. by walking [`Self::traverse`] itself).
+    pub fn add_listener(&self, listener: &'static dyn TranslationListener) {
+        unsafe { &mut *self.listeners.get() }
+            .push(listener)
+            .unwrap_or_else(|_| bug!("listener limit reached"));
+    }
+
+    fn notify_map(
+        &self,
+        vaddr: VirtualAddress,
+        paddr: PhysicalAddress,
+        size: usize,
+        perms: AccessPermissions,
+    ) {
+        for listener in unsafe { &*self.listeners.get() } {
+            listener.on_map(vaddr, paddr, size, perms);
+        }
+    }
+
+    fn notify_unmap(&self, vaddr: VirtualAddress, size: usize) {
+        for listener in unsafe { &*self.listeners.get() } {
+            listener.on_unmap(vaddr, size);
+        }
+    }
+
+    fn notify_permissions_changed(
+        &self,
+        vaddr: VirtualAddress,
+        size: usize,
+        perms: AccessPermissions,
+    ) {
+        for listener in unsafe { &*self.listeners.get() } {
+            listener.on_permissions_changed(vaddr, size, perms);
+        }
     }
 
     /// Traverse a range of Virtual Address.
@@ -128,9 +356,15 @@ impl TranslationTable {
         TraverseIterator::new(&self.root, vaddr_rng, free_empty_descs)
     }
 
-    /// Walk the translation table using the VirtualAddress `vaddr` and produce corresponding PhysicalAddress
-    /// This is similar to what CPU does after a TLB Miss.
-    pub fn virt2phy(&self, vaddr: VirtualAddress) -> Option<TranslationDesc> {
+    /// Walk the translation table using the VirtualAddress `vaddr` and
+    /// produce either its current translation or, for a reserved-but-not-
+    /// present range, the permissions/kind a fault should resolve it with.
+    /// This is similar to what CPU does after a TLB Miss -- except a real
+    /// TLB miss can't tell these two cases apart, which is exactly why
+    /// `Descriptor::Reserved` exists: software needs to, so
+    /// [`Self::resolve_fault`] knows what to install.
+    /// Returns `None` only for a genuinely unmapped (all-zero) descriptor.
+    pub fn virt2phy(&self, vaddr: VirtualAddress) -> Option<VirtToPhyResult> {
         let mut descs = &self.root;
 
         for level in TRANSLATION_LEVELS.iter() {
@@ -139,19 +373,23 @@ impl TranslationTable {
 
             let to_translation_desc = |desc: u64| {
                 let ll_desc = Stage1LastLevelDescriptor::new(desc);
-                let is_cacheable =
-                    !ll_desc.matches_all(STAGE1_LAST_LEVEL_DESCRIPTOR::SH::OuterShareable);
 
-                Some(TranslationDesc {
+                // `parse_output_address` only recovers the block/page-aligned
+                // base -- the low bits of `vaddr` within that block are an
+                // identity-mapped offset, exactly as the hardware walker
+                // leaves them untranslated, so they have to be added back in.
+                let block_base = parse_output_address(&ll_desc, level).as_raw_ptr() as usize;
+                let in_block_offset =
+                    vaddr.as_raw_ptr() as usize & (get_vaddr_spacing_per_entry(level) - 1);
+
+                Some(VirtToPhyResult::Mapped(TranslationDesc {
                     virt_addr: vaddr,
-                    phy_addr: parse_output_address(&ll_desc, level),
+                    phy_addr: PhysicalAddress::new(block_base + in_block_offset),
                     access_perms: parse_access_perms(&ll_desc),
-                    memory_kind: if is_cacheable {
-                        MemoryKind::Normal
-                    } else {
-                        MemoryKind::Device
-                    },
-                })
+                    memory_kind: parse_memory_kind(&ll_desc),
+                    accessed: ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::AF),
+                    dirty: parse_dirty(&ll_desc),
+                }))
             };
 
             match parse_desc(desc, level).ok()? {
@@ -161,6 +399,12 @@ impl TranslationTable {
                 }
                 Descriptor::Block(block_desc) => return to_translation_desc(block_desc.get()),
                 Descriptor::Page(page_desc) => return to_translation_desc(page_desc.get()),
+                Descriptor::Reserved(ll_desc) => {
+                    return Some(VirtToPhyResult::Faultable {
+                        perms: parse_access_perms(&ll_desc),
+                        kind: parse_memory_kind(&ll_desc),
+                    })
+                }
                 Descriptor::Invalid => return None,
             }
         }
@@ -168,23 +412,321 @@ impl TranslationTable {
         bug!("Cannot reach here");
     }
 
+    /// Resolves a fault on a reserved-but-not-present range: allocates a
+    /// frame from `desc_alloc`, writes a resident `Page` descriptor over
+    /// the `Reserved` one with the permissions/kind that were stashed in it,
+    /// and returns the newly mapped translation so the caller can zero the
+    /// frame (and, for a file-backed mapping, populate it) before retrying
+    /// the access that faulted. Returns `Error::InvalidVirtualAddress` if
+    /// `vaddr` isn't currently `Reserved` -- i.e. `virt2phy` wouldn't have
+    /// returned `VirtToPhyResult::Faultable` for it.
+    pub fn resolve_fault<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr: VirtualAddress,
+        desc_alloc: &DescAlloc,
+    ) -> Result<TranslationDesc> {
+        let mut descs = &self.root;
+
+        for level in TRANSLATION_LEVELS.iter() {
+            let idx = vaddr.get_idx_for_level(level);
+            let desc = load_desc(descs, idx);
+
+            match parse_desc(desc, level).map_err(|_| Error::CorruptedTranslationTable(desc))? {
+                Descriptor::Table(tbl_desc) => {
+                    assert_ne!(level, &AddressTranslationLevel::Three);
+                    descend_tbl_desc(tbl_desc, &mut descs);
+                }
+                Descriptor::Reserved(ll_desc) => {
+                    let access_perms = parse_access_perms(&ll_desc);
+                    let memory_kind = parse_memory_kind(&ll_desc);
+                    // A freshly faulted-in frame starts eager and clean --
+                    // a caller that wants lazy/dirty-tracked semantics for a
+                    // reserved range can still apply them afterwards via
+                    // `set_access_permissions`/`modify_range`.
+                    let attributes = parse_map_attrs(&access_perms, memory_kind, false, false);
+
+                    let layout = Layout::from_size_align(FOUR_KIB, FOUR_KIB)
+                        .unwrap_or_else(|_| bug!("Frame Layout Mismatch"));
+                    let frame = desc_alloc
+                        .allocate_zeroed(layout)
+                        .map_err(|_| Error::PhysicalOOM)?
+                        .as_non_null_ptr()
+                        .addr()
+                        .get();
+
+                    let desc_ptr = load_desc_mut(descs, idx);
+                    *desc_ptr = new_stage1_page_desc(frame as u64, attributes);
+                    let desc_ptr = desc_ptr as *const u64 as *const u8;
+                    tlb::clean_dcache_range(desc_ptr, unsafe { desc_ptr.add(size_of::<u64>()) });
+                    tlb::invalidate_va(vaddr);
+
+                    let block_vaddr = VirtualAddress::new(vaddr.align_down(FOUR_KIB))
+                        .unwrap_or_else(|_| bug!("aligning a valid VA down can't make it invalid"));
+                    self.notify_map(
+                        block_vaddr,
+                        PhysicalAddress::new(frame),
+                        FOUR_KIB,
+                        access_perms,
+                    );
+
+                    return Ok(TranslationDesc {
+                        virt_addr: block_vaddr,
+                        phy_addr: PhysicalAddress::new(frame),
+                        access_perms,
+                        memory_kind,
+                        accessed: true,
+                        dirty: false,
+                    });
+                }
+                Descriptor::Block(_) | Descriptor::Page(_) | Descriptor::Invalid => {
+                    return Err(Error::InvalidVirtualAddress(vaddr.as_raw_ptr() as usize))
+                }
+            }
+        }
+
+        bug!("Cannot reach here");
+    }
+
     pub fn get_base_address(&self) -> u64 {
         self.root.0.get() as u64
     }
 
+    /// Removes every mapping covering `vaddr_rng`, handing back whichever
+    /// intermediate tables `traverse` finds holding no valid entries once
+    /// their last leaf is gone.
+    pub fn unmap_range<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr_rng: Range<VirtualAddress>,
+        desc_alloc: &DescAlloc,
+    ) -> Result<()> {
+        for yielded in self.traverse(vaddr_rng.clone(), true) {
+            match yielded? {
+                TraverseYield::PhysicalBlock(mut block) => {
+                    // Whatever granularity this entry was mapped at -- 4 KiB,
+                    // 2 MiB, or 1 GiB -- a single `TLBI VAE1` targeting any VA
+                    // inside it invalidates the whole entry, so there's no
+                    // need to step through the block page-by-page.
+                    tlb::invalidate_va(block.vaddr());
+
+                    // Captured before `remove_overlapping_range` rewrites
+                    // `desc_ptr` out from under this block's own `overlap`.
+                    let overlap = block.overlapping_range().clone();
+                    let notify_vaddr = block.vaddr() + overlap.start as usize;
+                    let notify_size = (overlap.end - overlap.start) as usize;
+
+                    block.remove_overlapping_range(self, desc_alloc)?;
+                    self.notify_unmap(notify_vaddr, notify_size);
+                }
+                TraverseYield::UnusedMemory(desc_table) => unsafe {
+                    desc_alloc.deallocate(
+                        desc_table,
+                        Layout::from_size_align(
+                            size_of::<DescriptorTable>(),
+                            TRANSLATION_TABLE_DESC_ALIGN,
+                        )
+                        .unwrap_or_else(|_| bug!("Descriptor Layout Mismatch")),
+                    );
+                },
+            }
+        }
+
+        // Same reasoning as `map`: the now-cleared descriptors need to leave
+        // the cache before anyone relies on the range being unmapped.
+        let base = self.get_base_address() as *const u8;
+        tlb::clean_dcache_range(base, unsafe { base.add(size_of::<DescriptorTable>()) });
+
+        Ok(())
+    }
+
+    /// Rewrites the access permissions of every mapping covering
+    /// `vaddr_rng` in place, applying `f` to each overlapping leaf's
+    /// decoded `MapDesc`. A block only partially covered by `vaddr_rng` is
+    /// split at its non-overlapping edges first, so the rest of the block
+    /// keeps its existing permissions untouched.
+    pub fn modify_range<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr_rng: Range<VirtualAddress>,
+        desc_alloc: &DescAlloc,
+        f: impl Fn(&mut MapDesc),
+    ) -> Result<()> {
+        for yielded in self.traverse(vaddr_rng, false) {
+            if let TraverseYield::PhysicalBlock(mut block) = yielded? {
+                // Same reasoning as `unmap_range`: any VA inside the old
+                // block invalidates the whole entry, so one invalidate per
+                // modified block is enough.
+                tlb::invalidate_va(block.vaddr());
+                block.modify_overlapping_range(self, desc_alloc, &f)?;
+            }
+        }
+
+        // Same reasoning as `map`/`unmap_range`: the rewritten descriptors
+        // need to leave the cache before anyone relies on the new
+        // permissions being in effect.
+        let base = self.get_base_address() as *const u8;
+        tlb::clean_dcache_range(base, unsafe { base.add(size_of::<DescriptorTable>()) });
+
+        Ok(())
+    }
+
+    /// Rewrites `AccessPermissions` over every mapping covering `vaddr_rng`,
+    /// splitting at non-overlapping edges the same way [`Self::modify_range`]
+    /// does. This is the common mprotect-style case of that more general
+    /// API, named for callers that only want permissions changed -- guard
+    /// pages, W^X transitions, copy-on-write demotion -- without writing
+    /// their own closure.
+    pub fn set_access_permissions<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr_rng: Range<VirtualAddress>,
+        access_perms: AccessPermissions,
+        desc_alloc: &DescAlloc,
+    ) -> Result<()> {
+        self.modify_range(vaddr_rng.clone(), desc_alloc, |desc| {
+            desc.set_access_permissions(access_perms);
+        })?;
+
+        for yielded in self.traverse(vaddr_rng, false) {
+            if let TraverseYield::PhysicalBlock(block) = yielded? {
+                self.notify_permissions_changed(block.vaddr(), block.size(), access_perms);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Clears the Access Flag of every mapping covering `vaddr_rng`, via the
+    /// same `traverse` walk `unmap_range`/`modify_range` use. A pager calls
+    /// this after sampling [`PhysicalBlockOverlapInfo::is_accessed`] across
+    /// the range, to reset working-set information for the next sampling
+    /// period -- no splitting is needed since, unlike `modify_range`, the
+    /// Access Flag of a partially-overlapped block can be cleared in place
+    /// without touching the rest of that block's attributes.
+    pub fn clear_access_flags(&self, vaddr_rng: Range<VirtualAddress>) -> Result<()> {
+        for yielded in self.traverse(vaddr_rng, false) {
+            if let TraverseYield::PhysicalBlock(mut block) = yielded? {
+                block.clear_access_flag();
+            }
+        }
+
+        let base = self.get_base_address() as *const u8;
+        tlb::clean_dcache_range(base, unsafe { base.add(size_of::<DescriptorTable>()) });
+
+        Ok(())
+    }
+
+    /// Folds back together whatever `vaddr_rng` has been fragmented into --
+    /// the inverse of `split_block_desc` and `map_impl`'s block splitting.
+    /// Walks bottom-up: a child table is only considered for promotion into
+    /// a single block one level up once its own children have already been
+    /// coalesced. A full `DescriptorTable` of 512 leaf entries promotes when
+    /// every entry is present, physically contiguous (`entry[i + 1] ==
+    /// entry[i] + granule`), shares identical `AccessPermissions`,
+    /// `MemoryKind`, and Access Flag/DBM state, and the run's base physical
+    /// address is naturally aligned to the promoted block's span (2 MiB or
+    /// 1 GiB). Freed table pages go back to `desc_alloc`, same as
+    /// `unmap_range`'s `TraverseYield::UnusedMemory` handling.
+    ///
+    /// `virt2phy` must resolve identically for every address in `vaddr_rng`
+    /// before and after a call to this -- only the table shape changes, not
+    /// what it translates to.
+    pub fn coalesce<DescAlloc: PhysicalPageAllocator>(
+        &self,
+        vaddr_rng: Range<VirtualAddress>,
+        desc_alloc: &DescAlloc,
+    ) -> Result<()> {
+        if vaddr_rng.start >= vaddr_rng.end {
+            return Ok(());
+        }
+
+        let level = AddressTranslationLevel::Zero;
+        let start_idx = vaddr_rng.start.get_idx_for_level(&level);
+        let end_idx = (vaddr_rng.end - 1).get_idx_for_level(&level);
+
+        coalesce_entries(&self.root, &level, start_idx..=end_idx, desc_alloc)?;
+
+        let base = self.get_base_address() as *const u8;
+        tlb::clean_dcache_range(base, unsafe { base.add(size_of::<DescriptorTable>()) });
+
+        Ok(())
+    }
+
+    /// Installs `self` as the active translation table for `ttbr` and
+    /// invalidates any stale TLB entries left over from whatever was resident
+    /// there before.
+    ///
+    /// # Safety
+    ///
+    /// The caller must not rely on whatever table was active in `ttbr` before
+    /// this call still being active afterwards, and `self` must outlive every
+    /// subsequent access made through a virtual address it maps.
+    pub unsafe fn activate(&self, ttbr: TTBR) {
+        let root = self.get_base_address();
+
+        match ttbr {
+            TTBR::Zero => TTBR0_EL1.set(root),
+            TTBR::One => TTBR1_EL1.set(root),
+        }
+
+        asm!(
+            "dsb ishst",
+            "tlbi vmalle1",
+            "dsb ish",
+            "isb",
+            options(nomem, nostack)
+        );
+    }
+
+    /// Installs `self` as the active TTBR0 (user) translation table, tagged
+    /// with `asid`. Unlike [`Self::activate`], this does not flush the TLB:
+    /// the whole point of ASID-tagging is that a previous occupant's entries
+    /// stay cached and simply go unused until that ASID is scheduled again,
+    /// so a context switch only needs the new `TTBR0_EL1` value to take
+    /// effect -- an `ISB` is enough for that.
+    ///
+    /// # Safety
+    ///
+    /// Same contract as [`Self::activate`]: the caller must not rely on
+    /// whatever table previously occupied `asid` in TTBR0_EL1 still being
+    /// active afterwards, and `self` must outlive every subsequent access
+    /// made through a virtual address it maps while tagged with `asid`.
+    /// Callers are responsible for not reusing `asid` for a different
+    /// `TranslationTable` without first calling [`tlb::invalidate_asid`].
+    pub unsafe fn activate_user(&self, asid: u16) {
+        let root = self.get_base_address();
+        let ttbr0 = root | ((asid as u64) << tlb::ASID_SHIFT);
+
+        TTBR0_EL1.set(ttbr0);
+
+        asm!("isb", options(nomem, nostack));
+    }
+
     fn map_impl<DescAlloc: PhysicalPageAllocator>(
         &self,
         map: &ParsedMemoryMap,
         desc_alloc: &DescAlloc,
         mmap: &MemoryMap,
     ) -> Result<()> {
-        let map_scheme =
-            find_best_mapping_scheme(map.virt_addr, map.phy_addr, map.num_pages * GRANULE_SIZE);
+        // A reserved mapping has no real `phy_addr` to align a huge block
+        // against (it's 0, trivially "aligned" to anything), and is only
+        // meaningful at page granularity (`Descriptor::Reserved` is
+        // level-Three-only, same as `Page`) -- so skip
+        // `find_best_mapping_scheme` entirely and always install 4 KiB
+        // spans.
+        let map_scheme = if map.reserved {
+            let mut scheme = MappingScheme::default();
+            scheme
+                .spans
+                .push(ContiguousSpan::FourKiB(map.num_pages))
+                .unwrap_or_else(|_| bug!("spans limit reached"));
+            scheme
+        } else {
+            find_best_mapping_scheme(map.virt_addr, map.phy_addr, map.num_pages * GRANULE_SIZE)
+        };
         let mut map = ParsedMemoryMap {
             phy_addr: map.phy_addr,
             virt_addr: map.virt_addr,
             attributes: map.attributes,
             num_pages: 0,
+            reserved: map.reserved,
         };
 
         for scheme in map_scheme.spans {
@@ -220,9 +762,17 @@ impl TranslationTable {
         &self,
         map: &mut ParsedMemoryMap,
         desc_alloc: &DescAlloc,
-        mmap: &MemoryMap,
+        _mmap: &MemoryMap,
     ) -> Result<()> {
         let mut descs = &self.root;
+        // A reserved mapping installs `Descriptor::Reserved` placeholders
+        // instead of resident `Page` descriptors -- same attribute word,
+        // different leaf-assembly function.
+        let new_leaf_desc: fn(u64, u64) -> u64 = if map.reserved {
+            new_stage1_reserved_desc
+        } else {
+            new_stage1_page_desc
+        };
 
         for level in TRANSLATION_LEVELS {
             let idx = map.virt_addr.get_idx_for_level(level);
@@ -233,8 +783,26 @@ impl TranslationTable {
                     assert_ne!(level, &AddressTranslationLevel::Three);
                     descend_tbl_desc(tbl_desc, &mut descs);
                 }
-                Descriptor::Block(_) | Descriptor::Page(_) => {
-                    return Err(Error::VMMapExists(*mmap))
+                // A 4 KiB page always lands inside a coarser block, never
+                // beside one at the same level (Block is never valid at
+                // level Three) -- so there's always a finer table to split
+                // into here, never a same-granularity conflict to reject.
+                Descriptor::Block(_) => {
+                    let tbl_desc =
+                        split_block_desc(desc_alloc, descs, idx, level, map.virt_addr)?;
+                    descend_tbl_desc(tbl_desc, &mut descs);
+                }
+                // A Page or Reserved descriptor is already installed here --
+                // this is a remap rather than a fresh mapping, so
+                // break-before-make it: invalidate whatever's here now (and
+                // the rest of the target range) before installing the new
+                // descriptors over it.
+                Descriptor::Page(_) | Descriptor::Reserved(_) => {
+                    let num_mapped_pages =
+                        core::cmp::min(map.num_pages, NUM_TABLE_DESC_ENTRIES - idx);
+                    bbm_invalidate_existing(descs, idx, num_mapped_pages, map.virt_addr, FOUR_KIB);
+                    install_contigious_mappings(map, idx, descs, FOUR_KIB, &new_leaf_desc);
+                    break;
                 }
 
                 Descriptor::Invalid => {
@@ -249,15 +817,7 @@ impl TranslationTable {
                             descend_tbl_desc(tbl_desc, &mut descs);
                         }
                         AddressTranslationLevel::Three => {
-                            install_contigious_mappings(
-                                map,
-                                idx,
-                                descs,
-                                FOUR_KIB,
-                                &|output_address, attributes| {
-                                    new_stage1_page_desc(output_address, attributes)
-                                },
-                            );
+                            install_contigious_mappings(map, idx, descs, FOUR_KIB, &new_leaf_desc);
                             break;
                         }
                     }
@@ -272,7 +832,7 @@ impl TranslationTable {
         &self,
         map: &mut ParsedMemoryMap,
         desc_alloc: &DescAlloc,
-        mmap: &MemoryMap,
+        _mmap: &MemoryMap,
     ) -> Result<()> {
         let mut descs = &self.root;
 
@@ -285,8 +845,37 @@ impl TranslationTable {
                     assert_ne!(level, &AddressTranslationLevel::Three);
                     descend_tbl_desc(tbl_desc, &mut descs);
                 }
-                Descriptor::Block(_) => return Err(Error::VMMapExists(*mmap)),
-                Descriptor::Page(_) => return Err(Error::CorruptedTranslationTable(desc)),
+                // A level One block exists one level above where we want to
+                // install a 2 MiB block -- split it down into a level Two
+                // table and keep descending. A level Two block is the same
+                // granularity we're trying to install, so that's a genuine
+                // conflict.
+                Descriptor::Block(_) if level == &AddressTranslationLevel::One => {
+                    let tbl_desc =
+                        split_block_desc(desc_alloc, descs, idx, level, map.virt_addr)?;
+                    descend_tbl_desc(tbl_desc, &mut descs);
+                }
+                // Same granularity as the one we're installing -- this is a
+                // remap, not a fresh mapping: break-before-make the existing
+                // run of 2 MiB blocks before installing the new ones.
+                Descriptor::Block(_) => {
+                    let num_mapped_pages =
+                        core::cmp::min(map.num_pages, NUM_TABLE_DESC_ENTRIES - idx);
+                    bbm_invalidate_existing(descs, idx, num_mapped_pages, map.virt_addr, TWO_MIB);
+                    install_contigious_mappings(
+                        map,
+                        idx,
+                        descs,
+                        TWO_MIB,
+                        &|output_address, attributes| {
+                            new_stage1_block_desc(BlockDescLevel::Two, output_address, attributes)
+                        },
+                    );
+                    break;
+                }
+                Descriptor::Page(_) | Descriptor::Reserved(_) => {
+                    return Err(Error::CorruptedTranslationTable(desc))
+                }
 
                 Descriptor::Invalid => {
                     // We need to insert only Level 2 Block Descriptor.
@@ -328,7 +917,7 @@ impl TranslationTable {
         &self,
         map: &mut ParsedMemoryMap,
         desc_alloc: &DescAlloc,
-        mmap: &MemoryMap,
+        _mmap: &MemoryMap,
     ) -> Result<()> {
         let mut descs = &self.root;
 
@@ -343,12 +932,40 @@ impl TranslationTable {
                 }
                 Descriptor::Block(_) => {
                     if *level == AddressTranslationLevel::One {
-                        return Err(Error::VMMapExists(*mmap));
+                        // Same granularity as the one we're installing --
+                        // this is a remap, not a fresh mapping:
+                        // break-before-make the existing run of 1 GiB
+                        // blocks before installing the new ones.
+                        let num_mapped_pages =
+                            core::cmp::min(map.num_pages, NUM_TABLE_DESC_ENTRIES - idx);
+                        bbm_invalidate_existing(
+                            descs,
+                            idx,
+                            num_mapped_pages,
+                            map.virt_addr,
+                            ONE_GIB,
+                        );
+                        install_contigious_mappings(
+                            map,
+                            idx,
+                            descs,
+                            ONE_GIB,
+                            &|output_address, attributes| {
+                                new_stage1_block_desc(
+                                    BlockDescLevel::One,
+                                    output_address,
+                                    attributes,
+                                )
+                            },
+                        );
+                        break;
                     } else {
                         return Err(Error::CorruptedTranslationTable(desc));
                     }
                 }
-                Descriptor::Page(_) => return Err(Error::CorruptedTranslationTable(desc)),
+                Descriptor::Page(_) | Descriptor::Reserved(_) => {
+                    return Err(Error::CorruptedTranslationTable(desc))
+                }
                 Descriptor::Invalid => {
                     // We need to insert only Level 1 Block Descriptor.
                     // Until we reach level 1, insert Table Descriptors.
@@ -401,6 +1018,11 @@ pub struct PhysicalBlockOverlapInfo<'tt> {
     /// Offest within the above `phy_block`, which ovelaps the provided VA space.
     overlap: Range<u32>,
     desc_ptr: &'tt mut u64,
+
+    /// Table and index `desc_ptr` was loaded from, kept around so a
+    /// Contiguous group can be cleared before `desc_ptr` is invalidated.
+    descs: &'tt DescriptorTable,
+    idx: usize,
 }
 
 impl<'tt> PhysicalBlockOverlapInfo<'tt> {
@@ -409,6 +1031,8 @@ impl<'tt> PhysicalBlockOverlapInfo<'tt> {
         paddr: PhysicalAddress,
         vaddr: VirtualAddress,
         block_size: u32,
+        descs: &'tt DescriptorTable,
+        idx: usize,
         desc_ptr: &'tt mut u64,
     ) -> Self {
         let phy_start = paddr;
@@ -424,6 +1048,8 @@ impl<'tt> PhysicalBlockOverlapInfo<'tt> {
             overlap: (va_space_overlap_start - vaddr_start) as u32
                 ..(va_space_overlap_end - vaddr_start) as u32,
             desc_ptr,
+            descs,
+            idx,
         }
     }
 
@@ -439,6 +1065,59 @@ impl<'tt> PhysicalBlockOverlapInfo<'tt> {
         self.size as usize
     }
 
+    /// Whether this entry's TLB entry has been folded with its 15 group
+    /// siblings via the ARMv8-A Contiguous hint (see `CONTIGUOUS_GROUP_SIZE`).
+    /// This is purely informational -- `size()`/`phy_block()` stay
+    /// per-descriptor on purpose, since `remove_overlapping_range`'s and
+    /// `modify_overlapping_range`'s non-overlapping-edge math operates on
+    /// this single table slot, not the whole group.
+    pub fn is_contiguous(&self) -> bool {
+        let desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
+        desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::CONTIGUOUS)
+    }
+
+    /// The span of the 16-entry group this descriptor belongs to, if the
+    /// Contiguous hint is set -- the coalesced TLB entry's actual coverage,
+    /// as opposed to `size()`'s single-descriptor span. `None` when
+    /// `is_contiguous()` is false.
+    pub fn coalesced_size(&self) -> Option<usize> {
+        self.is_contiguous()
+            .then(|| self.size() * CONTIGUOUS_GROUP_SIZE)
+    }
+
+    /// Decoded `AccessPermissions` this entry currently maps with.
+    pub fn access_perms(&self) -> AccessPermissions {
+        let desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
+        parse_access_perms(&desc)
+    }
+
+    /// Whether this entry has been touched since its Access Flag was last
+    /// cleared (by [`Self::clear_access_flag`] or at install time via
+    /// `MapDesc::set_lazy_access`). A pager samples this to build up
+    /// working-set information before resetting it.
+    pub fn is_accessed(&self) -> bool {
+        let desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
+        desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::AF)
+    }
+
+    /// Whether this entry has been written since it was mapped, per
+    /// `parse_dirty`'s Dirty Bit Modifier decode.
+    pub fn is_dirty(&self) -> bool {
+        let desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
+        parse_dirty(&desc)
+    }
+
+    /// Clears the Access Flag and invalidates this entry's TLB entry, so the
+    /// next access re-sets AF the same way a brand-new `lazy_access` mapping
+    /// would fault. Used by a pager to reset working-set information after
+    /// sampling it with [`Self::is_accessed`].
+    pub fn clear_access_flag(&mut self) {
+        let desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
+        desc.modify(STAGE1_LAST_LEVEL_DESCRIPTOR::AF::False);
+        *self.desc_ptr = desc.get();
+        tlb::invalidate_va(self.vaddr());
+    }
+
     pub fn overlapping_range(&self) -> &Range<u32> {
         &self.overlap
     }
@@ -454,6 +1133,10 @@ impl<'tt> PhysicalBlockOverlapInfo<'tt> {
     ) -> Result<()> {
         let ll_desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
 
+        // This entry may be part of a Contiguous group -- clear the hint
+        // across the whole group before invalidating any single member.
+        clear_contiguous_group(self.descs, self.idx);
+
         // Remove the existing mapping
         *self.desc_ptr = INVALID_DESCRIPTOR;
 
@@ -476,6 +1159,47 @@ impl<'tt> PhysicalBlockOverlapInfo<'tt> {
         Ok(())
     }
 
+    /// Applies `f` to the decoded attributes of the portion of this block
+    /// overlapping the traversed range, then rewrites it. A block only
+    /// partially covered by the traversed range is split at its
+    /// non-overlapping edges first -- reinstalled unchanged, the same way
+    /// `remove_overlapping_range` does -- so the rest of the block keeps
+    /// whatever permissions it already had.
+    pub fn modify_overlapping_range<DescAlloc: PhysicalPageAllocator>(
+        &mut self,
+        tt: &TranslationTable,
+        desc_alloc: &DescAlloc,
+        f: &impl Fn(&mut MapDesc),
+    ) -> Result<()> {
+        let ll_desc = Stage1LastLevelDescriptor::new(*self.desc_ptr);
+
+        // This entry may be part of a Contiguous group -- clear the hint
+        // across the whole group before invalidating any single member.
+        clear_contiguous_group(self.descs, self.idx);
+
+        // Remove the existing mapping
+        *self.desc_ptr = INVALID_DESCRIPTOR;
+
+        let (first_rng, last_rng) = self.non_overlapping_range();
+        let overlap_rng = self.overlap.clone();
+
+        // Install the non-overlapping edges back unchanged, and the
+        // overlapping middle with `f`'s modified attributes.
+        if let Some(map) = self.create_memory_map(first_rng, &ll_desc) {
+            tt.map(&map, desc_alloc)?;
+        }
+        if let Some(map) = self.create_memory_map(overlap_rng, &ll_desc) {
+            let mut desc = *map.desc();
+            f(&mut desc);
+            tt.map(&MemoryMap::new(desc, map.kind()), desc_alloc)?;
+        }
+        if let Some(map) = self.create_memory_map(last_rng, &ll_desc) {
+            tt.map(&map, desc_alloc)?;
+        }
+
+        Ok(())
+    }
+
     fn create_memory_map(
         &self,
         rng: Range<u32>,
@@ -489,13 +1213,15 @@ impl<'tt> PhysicalBlockOverlapInfo<'tt> {
         let access_perms = parse_access_perms(ll_desc);
         let paddr = self.phy_block + rng.start as usize;
         let vaddr = self.vaddr + rng.start as usize;
-        let is_cacheable = !ll_desc.matches_all(STAGE1_LAST_LEVEL_DESCRIPTOR::SH::OuterShareable);
-        let map = MapDesc::new(paddr, vaddr, num_pages, access_perms);
+        let mut map = MapDesc::new(paddr, vaddr, num_pages, access_perms);
+        // Carry the existing AF/DBM state forward -- this edge is being
+        // reinstalled unchanged (or handed to `f` for the overlapping
+        // middle), not freshly mapped, so it shouldn't silently turn eager
+        // or stop being dirty-tracked.
+        map.set_lazy_access(!ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::AF));
+        map.set_track_dirty(ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::DBM));
 
-        Some(match is_cacheable {
-            true => MemoryMap::Normal(map),
-            false => MemoryMap::Device(map),
-        })
+        Some(MemoryMap::new(map, parse_memory_kind(ll_desc)))
     }
 }
 
@@ -768,6 +1494,13 @@ impl<'tt> TraverseIterator<'tt> {
         false
     }
 
+    // By the time every entry here is Invalid, any Contiguous group that
+    // used to live in this table was already cleared a member at a time by
+    // `clear_contiguous_group` (called from `remove_overlapping_range` /
+    // `modify_overlapping_range` / `split_block_desc` before each of those
+    // members was rewritten), so there's nothing group-shaped left to
+    // reason about here -- a table is only ever freed once it's fully
+    // Invalid, never mid-way through a still-Contiguous group.
     fn free_descs_if_empty(&mut self, descs: &DescriptorTable, level: &AddressTranslationLevel) {
         if !self.should_free_empty_descs || level == &AddressTranslationLevel::Zero {
             return;
@@ -821,6 +1554,8 @@ impl<'tt> TraverseIterator<'tt> {
             PhysicalAddress::new(paddr),
             self.va_space_explored,
             get_vaddr_spacing_per_entry(level) as u32,
+            descs,
+            idx,
             desc,
         )
     }
@@ -862,6 +1597,228 @@ fn install_new_tbl_desc<DescAlloc: PhysicalPageAllocator>(
     Ok(tbl_desc)
 }
 
+/// Break-before-make split of the block descriptor at `descs[idx]` (covering
+/// `parent_level`'s span, 1 GiB or 2 MiB) into a full table of 512 child
+/// block/page descriptors one level down, each carrying forward the same
+/// physical stride and attributes as the block they replace.
+///
+/// "Break": the old descriptor is cleared and its TLB entry invalidated
+/// before anything else happens, so no walker can ever observe a
+/// half-built child table through the still-live parent slot. "Make": the
+/// child table is allocated and fully populated off to the side -- through
+/// a throwaway local rather than `descs[idx]` itself -- and only published
+/// into the parent slot once every one of its 512 entries is in place.
+fn split_block_desc<DescAlloc: PhysicalPageAllocator>(
+    desc_alloc: &DescAlloc,
+    descs: &DescriptorTable,
+    idx: usize,
+    parent_level: &AddressTranslationLevel,
+    vaddr: VirtualAddress,
+) -> Result<Stage1TableDescriptor> {
+    let block_desc = Stage1LastLevelDescriptor::new(load_desc(descs, idx));
+    let parent_paddr = parse_output_address(&block_desc, parent_level);
+    let access_perms = parse_access_perms(&block_desc);
+    // Carry the original AF/DBM state forward too, not just permissions and
+    // memory type -- a lazy or dirty-tracked block shouldn't turn into an
+    // eagerly-accessed, non-tracked one just because it got split finer.
+    let attributes = parse_map_attrs(
+        &access_perms,
+        parse_memory_kind(&block_desc),
+        !block_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::AF),
+        block_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::DBM),
+    );
+
+    // `idx`'s own entry is about to be invalidated -- if it's part of a
+    // Contiguous group, the whole group must lose the hint first.
+    clear_contiguous_group(descs, idx);
+
+    *load_desc_mut(descs, idx) = INVALID_DESCRIPTOR;
+    tlb::invalidate_va(vaddr);
+
+    let child_level = parent_level.next();
+    let child_span = get_vaddr_spacing_per_entry(&child_level);
+
+    let mut throwaway_desc = INVALID_DESCRIPTOR;
+    let tbl_desc = install_new_tbl_desc(desc_alloc, &mut throwaway_desc)?;
+    let child_table = get_next_level_desc(&tbl_desc);
+
+    for i in 0..NUM_TABLE_DESC_ENTRIES {
+        let child_paddr = (parent_paddr + i * child_span).as_raw_ptr() as u64;
+        let child_desc = match child_level {
+            AddressTranslationLevel::Two => {
+                new_stage1_block_desc(BlockDescLevel::Two, child_paddr, attributes)
+            }
+            AddressTranslationLevel::Three => new_stage1_page_desc(child_paddr, attributes),
+            _ => bug!("split_block_desc: unexpected child level"),
+        };
+        *load_desc_mut(child_table, i) = child_desc;
+    }
+
+    *load_desc_mut(descs, idx) = tbl_desc.get();
+
+    Ok(tbl_desc)
+}
+
+/// Naturally-aligned run length that earns the Contiguous hint -- 16
+/// entries at any leaf level, per the ARMv8-A Contiguous bit definition.
+const CONTIGUOUS_GROUP_SIZE: usize = 16;
+
+/// Break-before-make remap over `[idx, idx + num_entries)`: every valid
+/// descriptor in the range (whatever it currently maps) is cleared and its
+/// TLB entry invalidated before the caller installs new descriptors over
+/// it, so a remap never leaves a walker observing a stale translation
+/// alongside the new one. Mirrors `split_block_desc`'s "break" half, except
+/// here the replacement is same-granularity rather than a finer table.
+fn bbm_invalidate_existing(
+    descs: &DescriptorTable,
+    idx: usize,
+    num_entries: usize,
+    base_vaddr: VirtualAddress,
+    entry_span: usize,
+) {
+    for i in 0..num_entries {
+        if load_desc(descs, idx + i) != INVALID_DESCRIPTOR {
+            clear_contiguous_group(descs, idx + i);
+            *load_desc_mut(descs, idx + i) = INVALID_DESCRIPTOR;
+            tlb::invalidate_va(base_vaddr + i * entry_span);
+        }
+    }
+}
+
+/// Recurses into every `Table` entry of `descs` at `idx_rng`, coalescing
+/// each child table's own contents first (bottom-up), then promoting that
+/// child table into a single block descriptor at `level` if
+/// [`uniform_leaf_run`] says it's now uniform enough. Mirrors
+/// `split_block_desc` in reverse, down to the same break-before-make care:
+/// the child table's descriptors are never touched, only the parent slot
+/// that points at it -- a walker still observing the old table through a
+/// stale TLB entry sees a consistent (if temporarily stale) translation
+/// either way.
+fn coalesce_entries<DescAlloc: PhysicalPageAllocator>(
+    parent: &DescriptorTable,
+    level: &AddressTranslationLevel,
+    idx_rng: RangeInclusive<usize>,
+    desc_alloc: &DescAlloc,
+) -> Result<()> {
+    // Level Three holds only Page descriptors -- there's no Table entry to
+    // recurse into, and nothing coarser than a block exists below it.
+    if level == &AddressTranslationLevel::Three {
+        return Ok(());
+    }
+
+    let child_level = level.next();
+
+    for idx in idx_rng {
+        let desc = load_desc(parent, idx);
+        let Ok(Descriptor::Table(tbl_desc)) = parse_desc(desc, level) else {
+            continue;
+        };
+        let child_table = get_next_level_desc(&tbl_desc);
+
+        coalesce_entries(child_table, &child_level, 0..=NUM_TABLE_DESC_ENTRIES - 1, desc_alloc)?;
+
+        // Only levels One and Two have a Block descriptor to promote into
+        // -- a 512 GiB run at level Zero has no coarser encoding to fold up
+        // into.
+        if level == &AddressTranslationLevel::Zero {
+            continue;
+        }
+
+        if let Some((base_paddr, attributes)) = uniform_leaf_run(child_table, &child_level) {
+            let vaddr = {
+                let mut v = VirtualAddress::new(0).unwrap_or_else(|_| bug!("VA(0) is valid"));
+                v.set_idx_for_level(level, idx);
+                v
+            };
+
+            // Break: drop the Table descriptor and its TLB entry before the
+            // child table's memory is handed back -- a late walker must
+            // never see the freed page through a stale translation.
+            *load_desc_mut(parent, idx) = INVALID_DESCRIPTOR;
+            tlb::invalidate_va(vaddr);
+
+            // Make: install the promoted block in its place.
+            let block_desc = new_stage1_block_desc(
+                BlockDescLevel::from(level),
+                base_paddr.as_raw_ptr() as u64,
+                attributes,
+            );
+            *load_desc_mut(parent, idx) = block_desc;
+
+            let child_table_ptr = NonNull::from(unsafe { &*(child_table.0.get() as *const u8) });
+            unsafe {
+                desc_alloc.deallocate(
+                    child_table_ptr,
+                    Layout::from_size_align(
+                        size_of::<DescriptorTable>(),
+                        TRANSLATION_TABLE_DESC_ALIGN,
+                    )
+                    .unwrap_or_else(|_| bug!("Descriptor Layout Mismatch")),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether every entry of `descs` -- all leaves at `level` (Block for One
+/// or Two, Page for Three) -- maps a physically contiguous, identically
+/// attributed run starting at a physical address naturally aligned to the
+/// whole run's span. Returns the run's base physical address and the
+/// already-encoded attribute bits (via `parse_map_attrs`) a single block
+/// descriptor at the parent level would need, ready for
+/// `new_stage1_block_desc`.
+fn uniform_leaf_run(
+    descs: &DescriptorTable,
+    level: &AddressTranslationLevel,
+) -> Option<(PhysicalAddress, u64)> {
+    let entry_span = get_vaddr_spacing_per_entry(level);
+
+    let is_leaf = |desc_val: u64| {
+        matches!(
+            parse_desc(desc_val, level),
+            Ok(Descriptor::Block(_)) | Ok(Descriptor::Page(_))
+        )
+    };
+
+    let base_desc_val = load_desc(descs, 0);
+    if !is_leaf(base_desc_val) {
+        return None;
+    }
+
+    let base_desc = Stage1LastLevelDescriptor::new(base_desc_val);
+    let base_paddr = parse_output_address(&base_desc, level);
+    if !base_paddr.is_aligned(entry_span * NUM_TABLE_DESC_ENTRIES) {
+        return None;
+    }
+
+    let base_access_perms = parse_access_perms(&base_desc);
+    let base_memory_kind = parse_memory_kind(&base_desc);
+    let base_af = base_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::AF);
+    let base_dbm = base_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::DBM);
+
+    for idx in 1..NUM_TABLE_DESC_ENTRIES {
+        let desc_val = load_desc(descs, idx);
+        if !is_leaf(desc_val) {
+            return None;
+        }
+
+        let ll_desc = Stage1LastLevelDescriptor::new(desc_val);
+        if parse_output_address(&ll_desc, level) != base_paddr + idx * entry_span
+            || parse_access_perms(&ll_desc) != base_access_perms
+            || parse_memory_kind(&ll_desc) != base_memory_kind
+            || ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::AF) != base_af
+            || ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::DBM) != base_dbm
+        {
+            return None;
+        }
+    }
+
+    let attributes = parse_map_attrs(&base_access_perms, base_memory_kind, !base_af, base_dbm);
+    Some((base_paddr, attributes))
+}
+
 fn install_contigious_mappings<F: Fn(u64, u64) -> u64>(
     map: &mut ParsedMemoryMap,
     idx: usize,
@@ -871,17 +1828,68 @@ fn install_contigious_mappings<F: Fn(u64, u64) -> u64>(
 ) {
     let mut paddr = map.phy_addr.as_raw_ptr() as u64;
     let num_mapped_pages = core::cmp::min(map.num_pages, NUM_TABLE_DESC_ENTRIES - idx);
-    for i in 0..num_mapped_pages {
-        assert_eq!(load_desc(descs, idx + i), INVALID_DESCRIPTOR);
-        let desc = new_stage1_descriptor(paddr, map.attributes);
-        *load_desc_mut(descs, idx + i) = desc;
-        paddr += page_size as u64;
+    let contiguous_span = (CONTIGUOUS_GROUP_SIZE * page_size) as u64;
+
+    let mut i = 0;
+    while i < num_mapped_pages {
+        // A group earns the Contiguous hint only if it starts on a
+        // 16-entry boundary in the table, has 16 full entries left to
+        // install, and its physical output address is aligned to the
+        // whole group's span -- all 16 members then share identical
+        // attributes (`map.attributes` is constant across this call) and
+        // naturally-contiguous output addresses.
+        let is_contiguous_group = (idx + i) % CONTIGUOUS_GROUP_SIZE == 0
+            && num_mapped_pages - i >= CONTIGUOUS_GROUP_SIZE
+            && paddr % contiguous_span == 0;
+        let group_len = if is_contiguous_group { CONTIGUOUS_GROUP_SIZE } else { 1 };
+
+        for j in 0..group_len {
+            assert_eq!(load_desc(descs, idx + i + j), INVALID_DESCRIPTOR);
+            let desc = new_stage1_descriptor(paddr, map.attributes);
+            *load_desc_mut(descs, idx + i + j) = if is_contiguous_group {
+                set_contiguous(desc)
+            } else {
+                desc
+            };
+            paddr += page_size as u64;
+        }
+        i += group_len;
     }
+
     map.phy_addr += num_mapped_pages * page_size;
     map.virt_addr += num_mapped_pages * page_size;
     map.num_pages -= num_mapped_pages;
 }
 
+fn set_contiguous(desc: u64) -> u64 {
+    let reg = Stage1LastLevelDescriptor::new(desc);
+    reg.modify(STAGE1_LAST_LEVEL_DESCRIPTOR::CONTIGUOUS::True);
+    reg.get()
+}
+
+/// Clears the Contiguous hint across the whole 16-entry group `idx` belongs
+/// to, if the group is marked Contiguous. The architecture forbids a
+/// partially-contiguous group (UNPREDICTABLE), so every unmap/split/modify
+/// path that's about to invalidate or rewrite a single member must call this
+/// first, before touching that member.
+fn clear_contiguous_group(descs: &DescriptorTable, idx: usize) {
+    let desc = Stage1LastLevelDescriptor::new(load_desc(descs, idx));
+    if !desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::VALID)
+        || !desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::CONTIGUOUS)
+    {
+        return;
+    }
+
+    let group_start = idx - (idx % CONTIGUOUS_GROUP_SIZE);
+    for member_idx in group_start..group_start + CONTIGUOUS_GROUP_SIZE {
+        let member = Stage1LastLevelDescriptor::new(load_desc(descs, member_idx));
+        if member.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::VALID) {
+            member.modify(STAGE1_LAST_LEVEL_DESCRIPTOR::CONTIGUOUS::False);
+            *load_desc_mut(descs, member_idx) = member.get();
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum ContiguousSpan {
     /// Number of Pages in 4KiB boundary
@@ -976,11 +1984,56 @@ fn find_best_mapping_scheme(
     scheme
 }
 
+/// [`TranslationTable::virt2phy`]'s result: either an existing translation,
+/// or -- for a `Descriptor::Reserved` range -- the permissions/kind a
+/// [`TranslationTable::resolve_fault`] call should install once a frame is
+/// allocated for it.
+pub enum VirtToPhyResult {
+    Mapped(TranslationDesc),
+    Faultable {
+        perms: AccessPermissions,
+        kind: MemoryKind,
+    },
+}
+
 pub struct TranslationDesc {
     virt_addr: VirtualAddress,
     phy_addr: PhysicalAddress,
     access_perms: AccessPermissions,
     memory_kind: MemoryKind,
+    accessed: bool,
+    dirty: bool,
+}
+
+impl TranslationDesc {
+    pub fn virt_addr(&self) -> VirtualAddress {
+        self.virt_addr
+    }
+
+    pub fn phy_addr(&self) -> PhysicalAddress {
+        self.phy_addr
+    }
+
+    pub fn access_perms(&self) -> AccessPermissions {
+        self.access_perms
+    }
+
+    pub fn memory_kind(&self) -> MemoryKind {
+        self.memory_kind
+    }
+
+    /// Whether this mapping's Access Flag is set, i.e. it's been touched
+    /// since it was installed or last had its AF cleared (see
+    /// `MapDesc::set_lazy_access` and `TranslationTable::clear_access_flags`).
+    pub fn accessed(&self) -> bool {
+        self.accessed
+    }
+
+    /// Whether this mapping has been written to, per the Dirty Bit Modifier
+    /// decode in `parse_dirty`. Always `false` for a read-only mapping.
+    pub fn dirty(&self) -> bool {
+        self.dirty
+    }
 }
 
 struct ParsedMemoryMap {
@@ -990,46 +2043,87 @@ struct ParsedMemoryMap {
     virt_addr: VirtualAddress,
     num_pages: usize,
     attributes: u64,
+    reserved: bool,
 }
 
 fn parse_memory_map(map: &MemoryMap) -> ParsedMemoryMap {
-    match map {
-        MemoryMap::Normal(desc) => ParsedMemoryMap {
-            phy_addr: desc.physical_address(),
-            virt_addr: desc.virtual_address(),
-            num_pages: desc.num_pages(),
-            attributes: parse_map_attrs(&desc.access_permissions(), MemoryKind::Normal),
-        },
-        MemoryMap::Device(desc) => ParsedMemoryMap {
-            phy_addr: desc.physical_address(),
-            virt_addr: desc.virtual_address(),
-            num_pages: desc.num_pages(),
-            attributes: parse_map_attrs(&desc.access_permissions(), MemoryKind::Device),
-        },
+    let desc = map.desc();
+
+    ParsedMemoryMap {
+        phy_addr: desc.physical_address(),
+        virt_addr: desc.virtual_address(),
+        num_pages: desc.num_pages(),
+        attributes: parse_map_attrs(
+            &desc.access_permissions(),
+            map.kind(),
+            desc.lazy_access(),
+            desc.track_dirty(),
+        ),
+        reserved: desc.reserved(),
     }
 }
 
-fn parse_map_attrs(ap: &AccessPermissions, device: MemoryKind) -> u64 {
+/// Decodes an existing leaf descriptor's `AttrIndx` back into the
+/// `MemoryKind` it was installed with -- the reverse of the `AttrIndx`
+/// assignment `parse_map_attrs` makes.
+fn parse_memory_kind(ll_desc: &Stage1LastLevelDescriptor) -> MemoryKind {
+    match ll_desc.read(STAGE1_LAST_LEVEL_DESCRIPTOR::AttrIndx) {
+        MAIR_IDX_NORMAL_CACHEABLE => MemoryKind::NormalCacheable,
+        MAIR_IDX_NORMAL_NONCACHEABLE => MemoryKind::NormalNonCacheable,
+        MAIR_IDX_DEVICE_NGNRNE => MemoryKind::DeviceNonGatheringNonReorderingNonEarlyAck,
+        MAIR_IDX_DEVICE_GRE => MemoryKind::DeviceGatheringReorderingEarlyAck,
+        _ => MemoryKind::DeviceNonGatheringNonReorderingEarlyAck,
+    }
+}
+
+/// Full MAIR-indexed model: every `MemoryKind` variant (`NormalCacheable`,
+/// `NormalNonCacheable`, and the three Device orderings) gets its own
+/// `AttrIndx` slot (`MAIR_IDX_*` above, programmed into MAIR_EL1 by
+/// `mmu::config_el1_memory_attributes`), with shareability following memory
+/// type rather than a binary Normal-vs-Device split. `parse_memory_kind`
+/// round-trips this back from an existing descriptor's `AttrIndx`.
+fn parse_map_attrs(
+    ap: &AccessPermissions,
+    memory_kind: MemoryKind,
+    lazy_access: bool,
+    track_dirty: bool,
+) -> u64 {
     let page_desc = Stage1PageDescriptor::new(0);
     let el1_rw = ap.contains(AccessPermissions::EL1_READ | AccessPermissions::EL1_WRITE);
     let el0_rw = ap.contains(AccessPermissions::EL0_READ | AccessPermissions::EL0_WRITE);
     let el1_ro = ap.contains(AccessPermissions::EL1_READ);
     let el0_ro = ap.contains(AccessPermissions::EL0_READ);
+    // A DBM-tracked writable mapping is installed "clean" -- encoded as
+    // read-only, with DBM set, so the first write demotes it to the
+    // matching RW encoding (see `parse_dirty`) instead of being writable
+    // up front.
+    let dbm = track_dirty && (el1_rw || el0_rw);
 
-    if el1_rw {
+    if el1_rw && !dbm {
         if el0_rw {
             page_desc.modify(STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1_EL0)
         } else {
             page_desc.modify(STAGE1_PAGE_DESCRIPTOR::AP::RW_EL1)
         }
-    } else if el1_ro {
-        if el0_ro {
+    } else if el1_ro || dbm {
+        if el0_ro || (dbm && el0_rw) {
             page_desc.modify(STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1_EL0)
         } else {
             page_desc.modify(STAGE1_PAGE_DESCRIPTOR::AP::RO_EL1)
         }
     }
 
+    if dbm {
+        page_desc.modify(STAGE1_PAGE_DESCRIPTOR::DBM::True);
+    }
+
+    // Leaving AF clear makes the first access raise an AF fault instead of
+    // the mapping being resident immediately -- used for lazily populating
+    // working-set information (see `MapDesc::set_lazy_access`).
+    if !lazy_access {
+        page_desc.modify(STAGE1_PAGE_DESCRIPTOR::AF::True);
+    }
+
     if ap.contains(AccessPermissions::EL1_WRITE) || !ap.contains(AccessPermissions::EL1_EXECUTE) {
         page_desc.modify(STAGE1_PAGE_DESCRIPTOR::PXN::SET);
     }
@@ -1037,10 +2131,28 @@ fn parse_map_attrs(ap: &AccessPermissions, device: MemoryKind) -> u64 {
         page_desc.modify(STAGE1_PAGE_DESCRIPTOR::UXN::SET);
     }
 
-    match device {
-        MemoryKind::Normal => page_desc.modify(STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable),
-        MemoryKind::Device => page_desc.modify(STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable),
-    }
+    // Normal memory (cacheable or not) is Inner Shareable; every Device
+    // flavor is Outer Shareable. `parse_memory_kind` decodes the kind back
+    // from `AttrIndx` alone, not from this split.
+    let (sh, mair_idx) = match memory_kind {
+        MemoryKind::NormalCacheable => {
+            (STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable, MAIR_IDX_NORMAL_CACHEABLE)
+        }
+        MemoryKind::NormalNonCacheable => {
+            (STAGE1_PAGE_DESCRIPTOR::SH::InnerShareable, MAIR_IDX_NORMAL_NONCACHEABLE)
+        }
+        MemoryKind::DeviceNonGatheringNonReorderingEarlyAck => {
+            (STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable, MAIR_IDX_DEVICE_NGNRE)
+        }
+        MemoryKind::DeviceNonGatheringNonReorderingNonEarlyAck => {
+            (STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable, MAIR_IDX_DEVICE_NGNRNE)
+        }
+        MemoryKind::DeviceGatheringReorderingEarlyAck => {
+            (STAGE1_PAGE_DESCRIPTOR::SH::OuterShareable, MAIR_IDX_DEVICE_GRE)
+        }
+    };
+    page_desc.modify(sh);
+    page_desc.modify(STAGE1_PAGE_DESCRIPTOR::AttrIndx.val(mair_idx));
 
     page_desc.get()
 }
@@ -1049,12 +2161,20 @@ enum Descriptor {
     Table(Stage1TableDescriptor),
     Block(Stage1BlockDescriptor),
     Page(Stage1PageDescriptor),
+    /// Reserved but not present -- `VALID` is clear (so a hardware walker
+    /// still takes a Translation fault), but `SWUSE` carries
+    /// `RESERVED_SWUSE_MARKER`, so software can tell it apart from a
+    /// genuinely unmapped (all-zero) descriptor. Only valid at level Three,
+    /// the same restriction `Page` has, since every use case (demand-zero,
+    /// stack-growth, file-backed) is page-granularity.
+    Reserved(Stage1LastLevelDescriptor),
     Invalid,
 }
 
 enum RawDescriptor {
     TableOrPage(u64),
     Block(Stage1BlockDescriptor),
+    Reserved(u64),
     Invalid,
 }
 
@@ -1080,6 +2200,19 @@ fn parse_desc(
                 Err(Descriptor::Block(block_desc))
             }
         }
+        RawDescriptor::Reserved(desc_val) => {
+            // Reserved descriptors can be present only in last level (3),
+            // same restriction as Page.
+            if level == &AddressTranslationLevel::Three {
+                Ok(Descriptor::Reserved(Stage1LastLevelDescriptor::new(
+                    desc_val,
+                )))
+            } else {
+                Err(Descriptor::Reserved(Stage1LastLevelDescriptor::new(
+                    desc_val,
+                )))
+            }
+        }
         // Invalid Descriptors can appear anywhere.
         RawDescriptor::Invalid => Ok(Descriptor::Invalid),
     }
@@ -1103,6 +2236,14 @@ fn to_raw_desc(value: u64) -> RawDescriptor {
         return RawDescriptor::Block(block_desc);
     }
 
+    // Neither Table/Page nor Block matched, so `VALID` is clear -- tell a
+    // software-reserved placeholder apart from a genuinely unmapped
+    // all-zero descriptor via the otherwise hardware-ignored `SWUSE` field.
+    let ll_desc = Stage1LastLevelDescriptor::new(value);
+    if ll_desc.read(STAGE1_LAST_LEVEL_DESCRIPTOR::SWUSE) == RESERVED_SWUSE_MARKER {
+        return RawDescriptor::Reserved(value);
+    }
+
     RawDescriptor::Invalid
 }
 
@@ -1138,6 +2279,11 @@ fn parse_output_address(
 
 fn parse_access_perms(ll_desc: &Stage1LastLevelDescriptor) -> AccessPermissions {
     let ap = ll_desc.read(STAGE1_LAST_LEVEL_DESCRIPTOR::AP);
+    // A Dirty Bit Modifier mapping is logically writable the moment it's
+    // installed -- an `AP` of RO_EL1(_EL0) only means "not yet
+    // written" here, not "read-only", so treat it the same as the matching
+    // RW encoding. `parse_dirty` is what tells clean and dirty apart.
+    let dbm = ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::DBM);
 
     let mut access_perms = if ap == STAGE1_LAST_LEVEL_DESCRIPTOR::AP::RW_EL1_EL0.value {
         AccessPermissions::EL1_READ
@@ -1147,9 +2293,20 @@ fn parse_access_perms(ll_desc: &Stage1LastLevelDescriptor) -> AccessPermissions
     } else if ap == STAGE1_LAST_LEVEL_DESCRIPTOR::AP::RW_EL1.value {
         AccessPermissions::EL1_READ | AccessPermissions::EL1_WRITE
     } else if ap == STAGE1_LAST_LEVEL_DESCRIPTOR::AP::RO_EL1_EL0.value {
-        AccessPermissions::EL1_READ | AccessPermissions::EL0_READ
+        if dbm {
+            AccessPermissions::EL1_READ
+                | AccessPermissions::EL1_WRITE
+                | AccessPermissions::EL0_READ
+                | AccessPermissions::EL0_WRITE
+        } else {
+            AccessPermissions::EL1_READ | AccessPermissions::EL0_READ
+        }
     } else if ap == STAGE1_LAST_LEVEL_DESCRIPTOR::AP::RO_EL1.value {
-        AccessPermissions::EL1_READ
+        if dbm {
+            AccessPermissions::EL1_READ | AccessPermissions::EL1_WRITE
+        } else {
+            AccessPermissions::EL1_READ
+        }
     } else {
         bug!("Invalid Access Permissions on page");
     };
@@ -1168,6 +2325,23 @@ fn parse_access_perms(ll_desc: &Stage1LastLevelDescriptor) -> AccessPermissions
     access_perms
 }
 
+/// Decodes whether a DBM-tracked mapping has actually been written to.
+/// Hardware (or, lacking `TCR_EL1.HD`, the permission-fault handler) demotes
+/// `AP[2]` from 1 (clean) to 0 (dirty) on the first write, so a DBM entry
+/// whose `AP` reads as one of the RW encodings has been written; a DBM
+/// entry still at a RO encoding hasn't. Mappings without DBM set have no
+/// lazy dirty tracking to report, so they read as clean here regardless of
+/// whether they're writable.
+fn parse_dirty(ll_desc: &Stage1LastLevelDescriptor) -> bool {
+    if !ll_desc.is_set(STAGE1_LAST_LEVEL_DESCRIPTOR::DBM) {
+        return false;
+    }
+
+    let ap = ll_desc.read(STAGE1_LAST_LEVEL_DESCRIPTOR::AP);
+    ap == STAGE1_LAST_LEVEL_DESCRIPTOR::AP::RW_EL1_EL0.value
+        || ap == STAGE1_LAST_LEVEL_DESCRIPTOR::AP::RW_EL1.value
+}
+
 fn new_stage1_table_desc(next_level_addr: u64) -> u64 {
     let table_desc = Stage1TableDescriptor::new(0);
 
@@ -1199,6 +2373,22 @@ fn new_stage1_page_desc(output_address: u64, attributes: u64) -> u64 {
     page_desc.get()
 }
 
+/// Builds a reserved-but-not-present leaf descriptor: `attributes` still
+/// carries the `AP`/`SH`/`AttrIndx`/`PXN`/`UXN` bits `parse_map_attrs`
+/// encoded (so `parse_access_perms`/`parse_memory_kind` can decode the
+/// reserved permissions/kind back out once `resolve_fault` needs them), but
+/// `VALID` is left clear and `output_address` is ignored -- there's no
+/// frame to point at yet. Matches `install_contigious_mappings`'s
+/// `Fn(u64, u64) -> u64` builder signature so callers can swap this in for
+/// `new_stage1_page_desc` without any other change.
+fn new_stage1_reserved_desc(_output_address: u64, attributes: u64) -> u64 {
+    let page_desc = Stage1PageDescriptor::new(attributes);
+
+    page_desc.modify(STAGE1_PAGE_DESCRIPTOR::SWUSE.val(RESERVED_SWUSE_MARKER));
+
+    page_desc.get()
+}
+
 enum BlockDescLevel {
     One,
     Two,
@@ -1271,15 +2461,15 @@ mod tests {
         thread_rng, Rng,
     };
     use rayon::prelude::*;
-    use std::{collections::HashMap, vec, vec::Vec};
+    use std::{boxed::Box, collections::HashMap, vec, vec::Vec};
 
     use crate::{
-        address::{PhysicalAddress, VirtualAddress},
+        address::{Address, PhysicalAddress, VirtualAddress},
         bug,
         mmu::{
             translation_table::{
-                ContiguousSpan, DescriptorTable, TranslationTable, TraverseYield,
-                NUM_TABLE_DESC_ENTRIES,
+                ContiguousSpan, DescriptorTable, TranslationListener, TranslationTable,
+                TraverseYield, NUM_TABLE_DESC_ENTRIES,
             },
             GRANULE_SIZE, OUTPUT_ADDR_BITS, TRANSLATION_TABLE_DESC_ALIGN,
         },
@@ -1358,33 +2548,42 @@ mod tests {
                 for (i, two_mib_ind) in rand_2MiB_ranges.iter().enumerate() {
                     if i == NUM_TABLE_DESC_ENTRIES - 1 {
                         for four_kib_ind in &rand_4KiB_ranges {
-                            memory_maps.push(MemoryMap::Normal(MapDesc::new(
-                                form_phy_addr(*one_gib_ind, *two_mib_ind, *four_kib_ind),
-                                virt_addr,
-                                FOUR_KIB / GRANULE_SIZE,
-                                access_perms,
-                            )));
+                            memory_maps.push(MemoryMap::new(
+                                MapDesc::new(
+                                    form_phy_addr(*one_gib_ind, *two_mib_ind, *four_kib_ind),
+                                    virt_addr,
+                                    FOUR_KIB / GRANULE_SIZE,
+                                    access_perms,
+                                ),
+                                MemoryKind::NormalCacheable,
+                            ));
 
                             virt_addr += FOUR_KIB;
                         }
                     } else {
-                        memory_maps.push(MemoryMap::Normal(MapDesc::new(
-                            form_phy_addr(*one_gib_ind, *two_mib_ind, 0),
-                            virt_addr,
-                            TWO_MIB / GRANULE_SIZE,
-                            access_perms,
-                        )));
+                        memory_maps.push(MemoryMap::new(
+                            MapDesc::new(
+                                form_phy_addr(*one_gib_ind, *two_mib_ind, 0),
+                                virt_addr,
+                                TWO_MIB / GRANULE_SIZE,
+                                access_perms,
+                            ),
+                            MemoryKind::NormalCacheable,
+                        ));
 
                         virt_addr += TWO_MIB;
                     }
                 }
             } else {
-                memory_maps.push(MemoryMap::Normal(MapDesc::new(
-                    form_phy_addr(*one_gib_ind, 0, 0),
-                    virt_addr,
-                    ONE_GIB / GRANULE_SIZE,
-                    access_perms,
-                )));
+                memory_maps.push(MemoryMap::new(
+                    MapDesc::new(
+                        form_phy_addr(*one_gib_ind, 0, 0),
+                        virt_addr,
+                        ONE_GIB / GRANULE_SIZE,
+                        access_perms,
+                    ),
+                    MemoryKind::NormalCacheable,
+                ));
             }
 
             virt_addr += ONE_GIB;
@@ -1394,6 +2593,13 @@ mod tests {
         memory_maps
     }
 
+    fn expect_mapped(result: Option<VirtToPhyResult>) -> TranslationDesc {
+        match result.expect("expected a translation") {
+            VirtToPhyResult::Mapped(translation) => translation,
+            VirtToPhyResult::Faultable { .. } => panic!("expected a resident mapping"),
+        }
+    }
+
     fn insert_test_using_vaddr(vaddr: VirtualAddress) {
         let page_alloc = TestAllocator::default();
         let memory_maps = generate_memory_maps(vaddr);
@@ -1404,20 +2610,13 @@ mod tests {
         let translation_table = translation_table.unwrap();
 
         for map in &memory_maps {
-            match map {
-                MemoryMap::Normal(desc) => {
-                    let vaddr = desc.virtual_address();
-                    let translation = translation_table.virt2phy(vaddr);
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let translation = expect_mapped(translation_table.virt2phy(vaddr));
 
-                    assert!(translation.is_some());
-                    let translation = translation.unwrap();
-
-                    assert_eq!(translation.phy_addr, desc.physical_address());
-                    assert_eq!(translation.access_perms, desc.access_permissions());
-                    assert_eq!(translation.memory_kind, MemoryKind::Normal);
-                }
-                MemoryMap::Device(_) => assert!(false, "Failure"),
-            }
+            assert_eq!(translation.phy_addr, desc.physical_address());
+            assert_eq!(translation.access_perms, desc.access_permissions());
+            assert_eq!(translation.memory_kind, MemoryKind::NormalCacheable);
         }
     }
 
@@ -1434,37 +2633,33 @@ mod tests {
         let translation_table = translation_table.unwrap();
 
         for map in &memory_maps {
-            match map {
-                MemoryMap::Normal(desc) => {
-                    let vaddr = desc.virtual_address();
-                    let paddr = desc.physical_address();
-                    let map_size = desc.num_pages() * FOUR_KIB;
-                    let mut size = 0;
-
-                    for res in translation_table.traverse(vaddr..vaddr + map_size, true) {
-                        assert!(res.is_ok());
-
-                        match res.unwrap() {
-                            TraverseYield::PhysicalBlock(mut pbo_info) => {
-                                assert_eq!(pbo_info.phy_block().start, paddr + size);
-                                assert_eq!(pbo_info.vaddr(), vaddr + size);
-                                let overlap = pbo_info.phy_block();
-                                size += (overlap.end - overlap.start) as usize;
-
-                                let remove = pbo_info
-                                    .remove_overlapping_range(&translation_table, &page_alloc);
-                                assert!(remove.is_ok());
-                            }
-                            TraverseYield::UnusedMemory(mem) => unsafe {
-                                page_alloc.deallocate(mem, layout)
-                            },
-                        }
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let paddr = desc.physical_address();
+            let map_size = desc.num_pages() * FOUR_KIB;
+            let mut size = 0;
+
+            for res in translation_table.traverse(vaddr..vaddr + map_size, true) {
+                assert!(res.is_ok());
+
+                match res.unwrap() {
+                    TraverseYield::PhysicalBlock(mut pbo_info) => {
+                        assert_eq!(pbo_info.phy_block().start, paddr + size);
+                        assert_eq!(pbo_info.vaddr(), vaddr + size);
+                        let overlap = pbo_info.phy_block();
+                        size += (overlap.end - overlap.start) as usize;
+
+                        let remove =
+                            pbo_info.remove_overlapping_range(&translation_table, &page_alloc);
+                        assert!(remove.is_ok());
                     }
-
-                    assert_eq!(size, map_size);
+                    TraverseYield::UnusedMemory(mem) => unsafe {
+                        page_alloc.deallocate(mem, layout)
+                    },
                 }
-                MemoryMap::Device(_) => assert!(false, "Failure"),
             }
+
+            assert_eq!(size, map_size);
         }
     }
 
@@ -1482,45 +2677,39 @@ mod tests {
         let mut rng = thread_rng();
 
         for map in &memory_maps {
-            match map {
-                MemoryMap::Normal(desc) => {
-                    let vaddr = desc.virtual_address();
-                    let paddr = desc.physical_address();
-                    let map_size = desc.num_pages() * FOUR_KIB;
-                    let mut traversed_size = 0;
-                    let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
-                    let unmap_end =
-                        Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
-                    let unmap_rng =
-                        vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
-
-                    for res in translation_table.traverse(unmap_rng.clone(), true) {
-                        assert!(res.is_ok());
-
-                        match res.unwrap() {
-                            TraverseYield::PhysicalBlock(pbo_info) => {
-                                let pblock = pbo_info.phy_block();
-                                let overlap = pbo_info.overlapping_range();
-                                assert_eq!(
-                                    pblock.start + overlap.start as usize,
-                                    paddr + unmap_start * GRANULE_SIZE + traversed_size
-                                );
-                                assert_eq!(
-                                    pbo_info.vaddr() + overlap.start as usize,
-                                    vaddr + unmap_start * GRANULE_SIZE + traversed_size
-                                );
-                                traversed_size += (overlap.end - overlap.start) as usize;
-                            }
-                            TraverseYield::UnusedMemory(mem) => unsafe {
-                                page_alloc.deallocate(mem, layout)
-                            },
-                        }
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let paddr = desc.physical_address();
+            let map_size = desc.num_pages() * FOUR_KIB;
+            let mut traversed_size = 0;
+            let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
+            let unmap_end = Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
+            let unmap_rng = vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
+
+            for res in translation_table.traverse(unmap_rng.clone(), true) {
+                assert!(res.is_ok());
+
+                match res.unwrap() {
+                    TraverseYield::PhysicalBlock(pbo_info) => {
+                        let pblock = pbo_info.phy_block();
+                        let overlap = pbo_info.overlapping_range();
+                        assert_eq!(
+                            pblock.start + overlap.start as usize,
+                            paddr + unmap_start * GRANULE_SIZE + traversed_size
+                        );
+                        assert_eq!(
+                            pbo_info.vaddr() + overlap.start as usize,
+                            vaddr + unmap_start * GRANULE_SIZE + traversed_size
+                        );
+                        traversed_size += (overlap.end - overlap.start) as usize;
                     }
-
-                    assert_eq!(traversed_size, (unmap_rng.end - unmap_rng.start) as usize);
+                    TraverseYield::UnusedMemory(mem) => unsafe {
+                        page_alloc.deallocate(mem, layout)
+                    },
                 }
-                MemoryMap::Device(_) => assert!(false, "Failure"),
             }
+
+            assert_eq!(traversed_size, (unmap_rng.end - unmap_rng.start) as usize);
         }
     }
 
@@ -1538,42 +2727,35 @@ mod tests {
         let mut rng = thread_rng();
 
         for map in &memory_maps {
-            match map {
-                MemoryMap::Normal(desc) => {
-                    let vaddr = desc.virtual_address();
-                    let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
-                    let unmap_end =
-                        Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
-                    let unmap_rng =
-                        vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
-                    let mut traversed_size = 0;
-
-                    for res in translation_table.traverse(unmap_rng.clone(), true) {
-                        assert!(res.is_ok());
-
-                        match res.unwrap() {
-                            TraverseYield::PhysicalBlock(mut pbo_info) => {
-                                let overlap = pbo_info.overlapping_range();
-                                traversed_size += (overlap.end - overlap.start) as usize;
-
-                                let remove = pbo_info
-                                    .remove_overlapping_range(&translation_table, &page_alloc);
-                                assert!(remove.is_ok());
-                            }
-                            TraverseYield::UnusedMemory(mem) => unsafe {
-                                page_alloc.deallocate(mem, layout)
-                            },
-                        }
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
+            let unmap_end = Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
+            let unmap_rng = vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
+            let mut traversed_size = 0;
+
+            for res in translation_table.traverse(unmap_rng.clone(), true) {
+                assert!(res.is_ok());
+
+                match res.unwrap() {
+                    TraverseYield::PhysicalBlock(mut pbo_info) => {
+                        let overlap = pbo_info.overlapping_range();
+                        traversed_size += (overlap.end - overlap.start) as usize;
+
+                        let remove =
+                            pbo_info.remove_overlapping_range(&translation_table, &page_alloc);
+                        assert!(remove.is_ok());
                     }
-
-                    assert_eq!(traversed_size, (unmap_rng.end - unmap_rng.start) as usize);
-
-                    let count_after_removal =
-                        translation_table.traverse(unmap_rng.clone(), true).count();
-                    assert_eq!(count_after_removal, 0);
+                    TraverseYield::UnusedMemory(mem) => unsafe {
+                        page_alloc.deallocate(mem, layout)
+                    },
                 }
-                MemoryMap::Device(_) => assert!(false, "Failure"),
             }
+
+            assert_eq!(traversed_size, (unmap_rng.end - unmap_rng.start) as usize);
+
+            let count_after_removal = translation_table.traverse(unmap_rng.clone(), true).count();
+            assert_eq!(count_after_removal, 0);
         }
     }
 
@@ -1582,26 +2764,22 @@ mod tests {
         let memory_maps = generate_memory_maps(vaddr);
 
         for map in &memory_maps {
-            match map {
-                MemoryMap::Normal(desc) => {
-                    let vaddr = desc.virtual_address();
-                    let paddr = desc.physical_address();
-                    let size = desc.num_pages() * GRANULE_SIZE;
-                    let scheme = find_best_mapping_scheme(vaddr, paddr, size);
-                    let mut mapped_size = 0;
-
-                    for scheme in scheme.spans {
-                        mapped_size += match scheme {
-                            ContiguousSpan::FourKiB(num_pages) => num_pages * FOUR_KIB,
-                            ContiguousSpan::TwoMiB(num_pages) => num_pages * TWO_MIB,
-                            ContiguousSpan::OneGiB(num_pages) => num_pages * ONE_GIB,
-                        }
-                    }
-
-                    assert_eq!(mapped_size, size);
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let paddr = desc.physical_address();
+            let size = desc.num_pages() * GRANULE_SIZE;
+            let scheme = find_best_mapping_scheme(vaddr, paddr, size);
+            let mut mapped_size = 0;
+
+            for scheme in scheme.spans {
+                mapped_size += match scheme {
+                    ContiguousSpan::FourKiB(num_pages) => num_pages * FOUR_KIB,
+                    ContiguousSpan::TwoMiB(num_pages) => num_pages * TWO_MIB,
+                    ContiguousSpan::OneGiB(num_pages) => num_pages * ONE_GIB,
                 }
-                MemoryMap::Device(_) => assert!(false, "Failure"),
             }
+
+            assert_eq!(mapped_size, size);
         }
     }
 
@@ -1649,6 +2827,90 @@ mod tests {
         lookup_test_using_vaddr(vaddr + 3 * FOUR_KIB);
     }
 
+    #[test]
+    fn identity_map_sanity_test() {
+        let page_alloc = TestAllocator::default();
+        // `new_identity` forces vaddr == paddr regardless of the vaddr
+        // `generate_memory_maps` filled in, so any base works here.
+        let memory_maps = generate_memory_maps(get_random_virt_addr());
+        let translation_table = TranslationTable::new_identity(&memory_maps, &page_alloc);
+
+        assert!(translation_table.is_ok());
+        let translation_table = translation_table.unwrap();
+
+        for map in &memory_maps {
+            let desc = map.desc();
+            let vaddr = VirtualAddress::new(desc.physical_address().as_raw_ptr() as usize).unwrap();
+            let translation = expect_mapped(translation_table.virt2phy(vaddr));
+
+            assert_eq!(translation.phy_addr, desc.physical_address());
+            assert_eq!(translation.access_perms, desc.access_permissions());
+            assert_eq!(translation.memory_kind, MemoryKind::NormalCacheable);
+        }
+    }
+
+    #[test]
+    fn access_flag_dirty_bit_sanity_test() {
+        let page_alloc = TestAllocator::default();
+        let vaddr = get_random_virt_addr();
+
+        let eager_desc = MapDesc::new(
+            PhysicalAddress::new(32229031936),
+            vaddr,
+            1,
+            AccessPermissions::normal_memory_default(),
+        );
+        let mut lazy_desc = MapDesc::new(
+            PhysicalAddress::new(32229036032),
+            vaddr + FOUR_KIB,
+            1,
+            AccessPermissions::normal_memory_default(),
+        );
+        lazy_desc.set_lazy_access(true);
+        let mut dirty_tracked_desc = MapDesc::new(
+            PhysicalAddress::new(32229040128),
+            vaddr + 2 * FOUR_KIB,
+            1,
+            AccessPermissions::normal_memory_default(),
+        );
+        dirty_tracked_desc.set_track_dirty(true);
+
+        let memory_maps = vec![
+            MemoryMap::new(eager_desc, MemoryKind::NormalCacheable),
+            MemoryMap::new(lazy_desc, MemoryKind::NormalCacheable),
+            MemoryMap::new(dirty_tracked_desc, MemoryKind::NormalCacheable),
+        ];
+        let translation_table = TranslationTable::new(&memory_maps, &page_alloc);
+
+        assert!(translation_table.is_ok());
+        let translation_table = translation_table.unwrap();
+
+        // Installed normally: accessed right away, and not dirty-tracked.
+        let eager = expect_mapped(translation_table.virt2phy(vaddr));
+        assert!(eager.accessed());
+        assert!(!eager.dirty());
+
+        // Installed lazily: the Access Flag starts clear.
+        let lazy = expect_mapped(translation_table.virt2phy(vaddr + FOUR_KIB));
+        assert!(!lazy.accessed());
+
+        // Installed dirty-tracked: still logically writable, but clean
+        // (not yet written) until hardware -- or a permission-fault
+        // handler -- demotes AP[2] on the first write.
+        let dirty_tracked = expect_mapped(translation_table.virt2phy(vaddr + 2 * FOUR_KIB));
+        assert!(dirty_tracked
+            .access_perms()
+            .contains(AccessPermissions::EL1_WRITE));
+        assert!(!dirty_tracked.dirty());
+
+        // A pager resets working-set information by clearing AF across the
+        // range -- the eager mapping should read as unaccessed afterwards.
+        let clear = translation_table.clear_access_flags(vaddr..vaddr + FOUR_KIB);
+        assert!(clear.is_ok());
+        let eager_after_clear = expect_mapped(translation_table.virt2phy(vaddr));
+        assert!(!eager_after_clear.accessed());
+    }
+
     #[test]
     fn remove_sanity_test() {
         // let vaddr = get_random_virt_addr();
@@ -1658,12 +2920,15 @@ mod tests {
         // remove_test_using_vaddr(vaddr + 3 * FOUR_KIB);
 
         let page_alloc = TestAllocator::default();
-        let memory_maps = vec![MemoryMap::Normal(MapDesc::new(
-            PhysicalAddress::new(32229031936),
-            VirtualAddress::new(205722300186624).unwrap(),
-            512,
-            AccessPermissions::normal_memory_default(),
-        ))];
+        let memory_maps = vec![MemoryMap::new(
+            MapDesc::new(
+                PhysicalAddress::new(32229031936),
+                VirtualAddress::new(205722300186624).unwrap(),
+                512,
+                AccessPermissions::normal_memory_default(),
+            ),
+            MemoryKind::NormalCacheable,
+        )];
         let layout =
             Layout::from_size_align(size_of::<DescriptorTable>(), TRANSLATION_TABLE_DESC_ALIGN)
                 .unwrap_or_else(|_| bug!("Descriptor Layout Mismatch"));
@@ -1675,45 +2940,224 @@ mod tests {
         let mut rng = thread_rng();
 
         for map in &memory_maps {
-            match map {
-                MemoryMap::Normal(desc) => {
-                    let vaddr = desc.virtual_address();
-                    let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
-                    let unmap_end =
-                        Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
-                    let unmap_rng =
-                        vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
-                    let mut traversed_size = 0;
-
-                    for res in translation_table.traverse(unmap_rng.clone(), true) {
-                        assert!(res.is_ok());
-
-                        match res.unwrap() {
-                            TraverseYield::PhysicalBlock(mut pbo_info) => {
-                                let overlap = pbo_info.overlapping_range();
-                                traversed_size += (overlap.end - overlap.start) as usize;
-
-                                let remove = pbo_info
-                                    .remove_overlapping_range(&translation_table, &page_alloc);
-                                assert!(remove.is_ok());
-                            }
-                            TraverseYield::UnusedMemory(mem) => unsafe {
-                                page_alloc.deallocate(mem, layout)
-                            },
-                        }
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
+            let unmap_end = Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
+            let unmap_rng = vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
+            let mut traversed_size = 0;
+
+            for res in translation_table.traverse(unmap_rng.clone(), true) {
+                assert!(res.is_ok());
+
+                match res.unwrap() {
+                    TraverseYield::PhysicalBlock(mut pbo_info) => {
+                        let overlap = pbo_info.overlapping_range();
+                        traversed_size += (overlap.end - overlap.start) as usize;
+
+                        let remove =
+                            pbo_info.remove_overlapping_range(&translation_table, &page_alloc);
+                        assert!(remove.is_ok());
                     }
+                    TraverseYield::UnusedMemory(mem) => unsafe {
+                        page_alloc.deallocate(mem, layout)
+                    },
+                }
+            }
 
-                    assert_eq!(traversed_size, (unmap_rng.end - unmap_rng.start) as usize);
+            assert_eq!(traversed_size, (unmap_rng.end - unmap_rng.start) as usize);
 
-                    let count_after_removal =
-                        translation_table.traverse(unmap_rng.clone(), true).count();
-                    assert_eq!(count_after_removal, 0);
-                }
-                MemoryMap::Device(_) => assert!(false, "Failure"),
+            let count_after_removal = translation_table.traverse(unmap_rng.clone(), true).count();
+            assert_eq!(count_after_removal, 0);
+        }
+    }
+
+    #[test]
+    fn unmap_range_sanity_test() {
+        let page_alloc = TestAllocator::default();
+        let memory_maps = vec![MemoryMap::new(
+            MapDesc::new(
+                PhysicalAddress::new(32229031936),
+                VirtualAddress::new(205722300186624).unwrap(),
+                512,
+                AccessPermissions::normal_memory_default(),
+            ),
+            MemoryKind::NormalCacheable,
+        )];
+        let translation_table = TranslationTable::new(&memory_maps, &page_alloc);
+
+        assert!(translation_table.is_ok());
+
+        let translation_table = translation_table.unwrap();
+        let mut rng = thread_rng();
+
+        for map in &memory_maps {
+            let desc = map.desc();
+            let vaddr = desc.virtual_address();
+            let unmap_start = Uniform::from(0..desc.num_pages()).sample(&mut rng);
+            let unmap_end = Uniform::from(unmap_start + 1..=desc.num_pages()).sample(&mut rng);
+            let unmap_rng = vaddr + unmap_start * GRANULE_SIZE..vaddr + unmap_end * GRANULE_SIZE;
+
+            let unmap = translation_table.unmap_range(unmap_rng.clone(), &page_alloc);
+            assert!(unmap.is_ok());
+
+            let count_after_removal = translation_table.traverse(unmap_rng.clone(), true).count();
+            assert_eq!(count_after_removal, 0);
+        }
+    }
+
+    #[test]
+    fn coalesce_sanity_test() {
+        let page_alloc = TestAllocator::default();
+        let vaddr = get_random_virt_addr();
+        let phy_base = 7 * TWO_MIB;
+        let vaddr_rng = vaddr..vaddr + NUM_TABLE_DESC_ENTRIES * FOUR_KIB;
+
+        let translation_table = TranslationTable::new(&[], &page_alloc).unwrap();
+        for i in 0..NUM_TABLE_DESC_ENTRIES {
+            let desc = MapDesc::new(
+                PhysicalAddress::new(phy_base + i * FOUR_KIB),
+                vaddr + i * FOUR_KIB,
+                1,
+                AccessPermissions::normal_memory_default(),
+            );
+            let map = MemoryMap::new(desc, MemoryKind::NormalCacheable);
+            translation_table.map(&map, &page_alloc).unwrap();
+        }
+
+        // Fully fragmented into 512 Page descriptors -- one per 4 KiB page.
+        let before = translation_table.traverse(vaddr_rng.clone(), false).count();
+        assert_eq!(before, NUM_TABLE_DESC_ENTRIES);
+
+        let translations_before: Vec<_> = (0..NUM_TABLE_DESC_ENTRIES)
+            .step_by(64)
+            .map(|i| expect_mapped(translation_table.virt2phy(vaddr + i * FOUR_KIB)))
+            .collect();
+
+        let coalesce = translation_table.coalesce(vaddr_rng.clone(), &page_alloc);
+        assert!(coalesce.is_ok());
+
+        // Physically contiguous, identically attributed, and naturally
+        // 2 MiB-aligned -- the whole table folds into a single block.
+        let after = translation_table.traverse(vaddr_rng.clone(), false).count();
+        assert_eq!(after, 1);
+
+        for (i, before) in (0..NUM_TABLE_DESC_ENTRIES)
+            .step_by(64)
+            .zip(translations_before)
+        {
+            let after = expect_mapped(translation_table.virt2phy(vaddr + i * FOUR_KIB));
+            assert_eq!(after.phy_addr(), before.phy_addr());
+            assert_eq!(after.access_perms(), before.access_perms());
+            assert_eq!(after.memory_kind(), before.memory_kind());
+        }
+    }
+
+    #[test]
+    fn reserved_fault_sanity_test() {
+        let page_alloc = TestAllocator::default();
+        let vaddr = get_random_virt_addr();
+
+        let mut reserved_desc = MapDesc::new(
+            PhysicalAddress::new(0),
+            vaddr,
+            1,
+            AccessPermissions::normal_memory_default(),
+        );
+        reserved_desc.set_reserved(true);
+        let memory_maps = vec![MemoryMap::new(reserved_desc, MemoryKind::NormalCacheable)];
+        let translation_table = TranslationTable::new(&memory_maps, &page_alloc);
+
+        assert!(translation_table.is_ok());
+        let translation_table = translation_table.unwrap();
+
+        // Reserved but not present: nothing for `traverse` to hand back yet.
+        assert_eq!(translation_table.traverse(vaddr..vaddr + FOUR_KIB, true).count(), 0);
+
+        match translation_table.virt2phy(vaddr) {
+            Some(VirtToPhyResult::Faultable { perms, kind }) => {
+                assert_eq!(perms, AccessPermissions::normal_memory_default());
+                assert_eq!(kind, MemoryKind::NormalCacheable);
+            }
+            _ => panic!("expected a faultable range"),
+        }
+
+        let resolved = translation_table.resolve_fault(vaddr, &page_alloc);
+        assert!(resolved.is_ok());
+        let resolved = resolved.unwrap();
+        assert_eq!(resolved.access_perms, AccessPermissions::normal_memory_default());
+        assert_eq!(resolved.memory_kind, MemoryKind::NormalCacheable);
+        assert!(resolved.accessed);
+        assert!(!resolved.dirty);
+
+        let translation = expect_mapped(translation_table.virt2phy(vaddr));
+        assert_eq!(translation.phy_addr, resolved.phy_addr);
+        assert!(translation.accessed);
+        assert!(!translation.dirty);
+
+        // Now resident: `traverse` yields the single faulted-in page.
+        let mut traversed = translation_table.traverse(vaddr..vaddr + FOUR_KIB, true);
+        match traversed.next() {
+            Some(Ok(TraverseYield::PhysicalBlock(pbo_info))) => {
+                assert_eq!(pbo_info.phy_block().start, resolved.phy_addr);
             }
+            _ => panic!("expected a single resolved block"),
+        }
+        assert!(traversed.next().is_none());
+    }
+
+    #[derive(Default)]
+    struct RecordingListener {
+        maps: RefCell<Vec<(VirtualAddress, usize)>>,
+        unmaps: RefCell<Vec<(VirtualAddress, usize)>>,
+    }
+
+    impl TranslationListener for RecordingListener {
+        fn on_map(
+            &self,
+            vaddr: VirtualAddress,
+            _paddr: PhysicalAddress,
+            size: usize,
+            _perms: AccessPermissions,
+        ) {
+            self.maps.borrow_mut().push((vaddr, size));
+        }
+
+        fn on_unmap(&self, vaddr: VirtualAddress, size: usize) {
+            self.unmaps.borrow_mut().push((vaddr, size));
         }
     }
 
+    #[test]
+    fn listener_notified_at_coalesced_granularity_test() {
+        let page_alloc = TestAllocator::default();
+        let vaddr = get_random_virt_addr();
+        let listener: &'static RecordingListener = Box::leak(Box::default());
+
+        let translation_table = TranslationTable::new(&[], &page_alloc).unwrap();
+        translation_table.add_listener(listener);
+
+        let desc = MapDesc::new(
+            PhysicalAddress::new(3 * ONE_GIB),
+            vaddr,
+            NUM_TABLE_DESC_ENTRIES * NUM_TABLE_DESC_ENTRIES,
+            AccessPermissions::normal_memory_default(),
+        );
+        let map = MemoryMap::new(desc, MemoryKind::NormalCacheable);
+        translation_table.map(&map, &page_alloc).unwrap();
+
+        // A single 1 GiB span, mapped in one call -- one `on_map`, not one
+        // per 4 KiB page underneath it.
+        assert_eq!(*listener.maps.borrow(), vec![(vaddr, ONE_GIB)]);
+
+        let vaddr_rng = vaddr..vaddr + ONE_GIB;
+        translation_table
+            .unmap_range(vaddr_rng.clone(), &page_alloc)
+            .unwrap();
+
+        assert_eq!(*listener.unmaps.borrow(), vec![(vaddr, ONE_GIB)]);
+    }
+
     #[test]
     #[ignore]
     fn insert_long_test() {
@@ -14,12 +14,27 @@ pub mod consts {
     pub const VIRTUAL_ADDRESS_LEVEL_IDX_BITS: u32 = address::VIRTUAL_ADDRESS_LEVEL_IDX_BITS;
 
     pub const VIRTUAL_ADDRESS_NBITS: u32 = u64::BITS - VIRTUAL_ADDRESS_IGNORE_MSB;
-    pub const VIRTUAL_ADDRESS_PAGE_OFFSET_NBITS: u32 = FOUR_KIB.ilog2();
+    /// Bits of page offset within the active `va.*` feature's granule --
+    /// mirrors `mmu::GRANULE_SIZE_BITS` rather than hardcoding the default
+    /// 4 KiB granule's `FOUR_KIB.ilog2()`, so `MAX_TRANSLATION_LEVELS` below
+    /// comes out right for the 16 KiB/64 KiB granules too. That's still only
+    /// half of what a non-default granule needs, though --
+    /// `get_vaddr_spacing_per_entry` below and `translation_table`'s block/page
+    /// descriptor installation stay 4 KiB-only, which is why `mmu::mod` has a
+    /// `compile_error!` blocking either `va.*` feature until both halves agree.
+    pub const VIRTUAL_ADDRESS_PAGE_OFFSET_NBITS: u32 = crate::mmu::GRANULE_SIZE_BITS;
     pub const MAX_TRANSLATION_LEVELS: usize = ((VIRTUAL_ADDRESS_NBITS
         - VIRTUAL_ADDRESS_PAGE_OFFSET_NBITS)
         / VIRTUAL_ADDRESS_LEVEL_IDX_BITS) as usize;
 }
 
+/// Still the default 4 KiB-granule block/page sizes regardless of which
+/// `va.*` feature is active -- the 16 KiB granule's 32 MiB level-2 blocks and
+/// the 64 KiB granule's 512 MiB level-2 blocks (and which levels even support
+/// a block descriptor, which differs per granule) aren't wired up here yet,
+/// matching the gap called out in `translation_table`'s module doc. Harmless
+/// today only because `mmu::mod`'s `compile_error!` refuses to build with
+/// either non-default `va.*` feature enabled until that gap closes.
 pub const fn get_vaddr_spacing_per_entry(level: &AddressTranslationLevel) -> usize {
     match level {
         AddressTranslationLevel::Zero => 512 * consts::ONE_GIB,
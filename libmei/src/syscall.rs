@@ -0,0 +1,39 @@
+//! The EL0 side of the SVC-based syscall ABI: typed wrappers that trap into EL1
+//! via `svc #imm`. The kernel's `syscall::dispatch` is the EL1 side -- this module
+//! only needs to agree with it on numbering and calling convention (arguments in
+//! `x0..=x5`, return value back in `x0`).
+
+/// Writes `len` bytes starting at `buf` to `fd`, returning the number of bytes
+/// written (or `u64::MAX` on failure).
+pub fn write(fd: u64, buf: *const u8, len: u64) -> u64 {
+    let ret: u64;
+    unsafe {
+        core::arch::asm!(
+            "svc #0",
+            in("x0") fd,
+            in("x1") buf as u64,
+            in("x2") len,
+            lateout("x0") ret,
+            options(nostack),
+        );
+    }
+    ret
+}
+
+/// Terminates the calling task with `code`. Never returns.
+pub fn exit(code: u64) -> ! {
+    unsafe {
+        core::arch::asm!(
+            "svc #1",
+            in("x0") code,
+            options(noreturn, nostack),
+        );
+    }
+}
+
+/// Yields the remainder of the current timeslice back to the scheduler.
+pub fn yield_now() {
+    unsafe {
+        core::arch::asm!("svc #2", options(nostack));
+    }
+}
@@ -1,4 +1,12 @@
-use crate::address::{Address, PhysicalAddress, VirtualAddress};
+use crate::{
+    address::{Address, PhysicalAddress, VirtualAddress, TTBR},
+    arch::{self, Arch},
+};
+
+pub mod buddy;
+pub mod early_alloc;
+pub mod slab;
+pub mod tlsf;
 
 // From https://lwn.net/Articles/718895/
 //
@@ -24,19 +32,66 @@ use crate::address::{Address, PhysicalAddress, VirtualAddress};
 //  +-------------------------------------------------> [63] TTBR0/1
 
 lazy_static! {
+    // Backend-selected: `arch::Current::KERNEL_VIRT_BASE` is AArch64's
+    // 0xFFFF_FFFF_0000_0000 today, and whichever Sv39/Sv48 higher half
+    // `arch.riscv64` selects once that feature is enabled.
     static ref EL1_VIRT_ADDRESS_BASE: VirtualAddress =
-        VirtualAddress::new(0xFFFF_FFFF_0000_0000).unwrap();
+        VirtualAddress::new(arch::Current::KERNEL_VIRT_BASE).unwrap();
     static ref EL0_VIRT_ADDRESS_BASE: VirtualAddress =
         VirtualAddress::new(0x0000_0000_0000_0000).unwrap();
 }
 
 /// Works only for statically mapped physical addresses
 pub fn phy2virt(paddr: PhysicalAddress) -> VirtualAddress {
-    *EL1_VIRT_ADDRESS_BASE + paddr.as_raw_ptr()
+    let vaddr = *EL1_VIRT_ADDRESS_BASE + paddr.as_raw_ptr();
+
+    debug_assert!(
+        matches!(vaddr.get_ttbr_select(), TTBR::One),
+        "phy2virt: {paddr} maps outside the kernel's TTBR1 linear region"
+    );
+
+    vaddr
+}
+
+/// Inverse of `phy2virt`: recovers the physical address a statically mapped
+/// linear-map virtual address was built from.
+pub fn virt2phy(vaddr: VirtualAddress) -> PhysicalAddress {
+    debug_assert!(
+        matches!(vaddr.get_ttbr_select(), TTBR::One) && vaddr >= *EL1_VIRT_ADDRESS_BASE,
+        "virt2phy: {vaddr} is not in the kernel's TTBR1 linear map"
+    );
+
+    PhysicalAddress::new((vaddr - *EL1_VIRT_ADDRESS_BASE) as usize)
 }
 
+/// Marker for allocators the MMU's translation-table walker can use to back
+/// intermediate table pages. `TranslationTable` only ever needs page-aligned,
+/// zeroed allocations, which is exactly `core::alloc::Allocator::allocate_zeroed`,
+/// so implementing that is all a caller needs to do to satisfy this bound.
+pub trait PhysicalPageAllocator: core::alloc::Allocator {}
+
+/// `BuddyAllocator` already hands out page-aligned, zeroed-on-request
+/// physical memory through `core::alloc::Allocator`, which is exactly what
+/// `TranslationTable` needs to back its intermediate table pages.
+impl PhysicalPageAllocator for buddy::BuddyAllocator {}
+
+/// `EarlyFrameAllocator` hands out the same page-aligned, zeroed frames
+/// through `core::alloc::Allocator`, just via a bump cursor instead of a free
+/// list -- good enough for the handful of table pages `setup_mmu` needs
+/// before any of the above allocators exist.
+impl PhysicalPageAllocator for early_alloc::EarlyFrameAllocator {}
+
 pub mod physical_page_alloc {
-    use crate::{address::PhysicalAddress, error::Result};
+    use spin::Mutex;
+
+    use crate::{
+        address::{Address, PhysicalAddress},
+        error::{Error, Result},
+        vm::phy2virt,
+    };
+
+    /// Page size this allocator hands pages out in.
+    const PAGE_SIZE: usize = 4096;
 
     pub struct AllocationLayout {
         num_pages: usize,
@@ -88,24 +143,451 @@ pub mod physical_page_alloc {
             num_pages: usize,
         ) -> Result<()>;
     }
+
+    /// Number of 4KiB frames the allocator manages (a 4MiB pool).
+    const NUM_FRAMES: usize = 1024;
+
+    /// `NUM_FRAMES` is a power of two, so this is its log2: the largest order a
+    /// single free list can hold (one block spanning the whole pool).
+    const MAX_ORDER: usize = 10;
+
+    /// Sentinel meaning "no frame", used instead of `Option<usize>` so the
+    /// free-list link can be written directly into the frame's own backing bytes.
+    const NONE_FRAME: usize = usize::MAX;
+
+    struct FreeLists {
+        /// `heads[order]` is the frame index at the head of that order's free
+        /// list, or `NONE_FRAME` if empty.
+        heads: [usize; MAX_ORDER + 1],
+        initialized: bool,
+    }
+
+    /// Binary buddy allocator handing out runs of physical pages.
+    ///
+    /// Pages are tracked in power-of-two runs: free list `k` holds aligned blocks
+    /// of `2^k` frames. Allocating `num_pages` rounds up to the smallest order
+    /// that fits both the request and `align`, splits a larger free block down to
+    /// that order (handing the unused buddy halves back to the lower-order
+    /// lists), and frees merge a block with its buddy (`frame XOR block_size`)
+    /// whenever that buddy is free at the same order, propagating the merge
+    /// upward.
+    ///
+    /// There's no heap this early in boot, so free-list links live intra-block:
+    /// a free frame's first `usize` holds the next frame in its order's list,
+    /// accessed through `phy2virt` like any other statically-mapped physical
+    /// page.
+    ///
+    /// `is_contigious = false` is satisfied the same way `true` is -- every block
+    /// this allocator hands out is already physically contiguous by
+    /// construction, so the flag has no effect on the returned [`PhysicalPages`]
+    /// today. It exists so a future allocator backing onto non-contiguous
+    /// physical memory (e.g. one stitching frames from several discontiguous
+    /// regions) has somewhere to plug in a scatter/gather path without changing
+    /// this trait.
+    pub struct BuddyPageAllocator {
+        pool: [u8; NUM_FRAMES * PAGE_SIZE],
+        free_lists: Mutex<FreeLists>,
+    }
+
+    unsafe impl Sync for BuddyPageAllocator {}
+
+    impl BuddyPageAllocator {
+        pub const fn new() -> Self {
+            Self {
+                pool: [0; NUM_FRAMES * PAGE_SIZE],
+                free_lists: Mutex::new(FreeLists {
+                    heads: [NONE_FRAME; MAX_ORDER + 1],
+                    initialized: false,
+                }),
+            }
+        }
+
+        fn pool_base(&self) -> usize {
+            self.pool.as_ptr() as usize
+        }
+
+        fn frame_to_paddr(&self, frame: usize) -> PhysicalAddress {
+            PhysicalAddress::new(self.pool_base() + frame * PAGE_SIZE)
+        }
+
+        fn paddr_to_frame(&self, paddr: PhysicalAddress) -> usize {
+            (paddr.as_raw_ptr() - self.pool_base()) / PAGE_SIZE
+        }
+
+        /// Reads the free-list link stored in a free frame's first bytes.
+        fn read_link(&self, frame: usize) -> usize {
+            unsafe { *phy2virt(self.frame_to_paddr(frame)).as_ptr::<usize>() }
+        }
+
+        fn write_link(&self, frame: usize, next: usize) {
+            unsafe {
+                *phy2virt(self.frame_to_paddr(frame)).as_mut_ptr::<usize>() = next;
+            }
+        }
+
+        /// The whole pool is a single `NUM_FRAMES`-frame block the first time
+        /// it's touched.
+        fn ensure_init(&self, lists: &mut FreeLists) {
+            if lists.initialized {
+                return;
+            }
+
+            lists.heads[MAX_ORDER] = 0;
+            self.write_link(0, NONE_FRAME);
+            lists.initialized = true;
+        }
+
+        /// Smallest order whose `2^order`-frame blocks cover `num_pages`.
+        fn order_for(num_pages: usize) -> usize {
+            let mut order = 0;
+            while (1usize << order) < num_pages {
+                order += 1;
+            }
+            order
+        }
+
+        fn push_free(&self, lists: &mut FreeLists, order: usize, frame: usize) {
+            self.write_link(frame, lists.heads[order]);
+            lists.heads[order] = frame;
+        }
+
+        fn pop_free(&self, lists: &mut FreeLists, order: usize) -> Option<usize> {
+            let frame = lists.heads[order];
+            if frame == NONE_FRAME {
+                return None;
+            }
+
+            lists.heads[order] = self.read_link(frame);
+            Some(frame)
+        }
+
+        /// Removes `frame` from order `order`'s free list, if it's on it.
+        fn remove_free(&self, lists: &mut FreeLists, order: usize, frame: usize) -> bool {
+            let mut cur = lists.heads[order];
+            let mut prev: Option<usize> = None;
+
+            while cur != NONE_FRAME {
+                let next = self.read_link(cur);
+
+                if cur == frame {
+                    match prev {
+                        Some(p) => self.write_link(p, next),
+                        None => lists.heads[order] = next,
+                    }
+                    return true;
+                }
+
+                prev = Some(cur);
+                cur = next;
+            }
+
+            false
+        }
+
+        /// Pops a free block of at least `target_order`, splitting higher-order
+        /// blocks down as needed and pushing the unused buddy halves back onto
+        /// their own lists.
+        fn pop_split(&self, lists: &mut FreeLists, target_order: usize) -> Option<usize> {
+            let mut order = target_order;
+            while order <= MAX_ORDER && lists.heads[order] == NONE_FRAME {
+                order += 1;
+            }
+            if order > MAX_ORDER {
+                return None;
+            }
+
+            let mut frame = self.pop_free(lists, order)?;
+            while order > target_order {
+                order -= 1;
+                let buddy = frame + (1 << order);
+                self.push_free(lists, order, buddy);
+            }
+
+            Some(frame)
+        }
+
+        /// Frees a block of `order` starting at `frame`, coalescing with its
+        /// buddy for as long as the buddy is free at the same order.
+        fn push_coalesce(&self, lists: &mut FreeLists, mut order: usize, mut frame: usize) {
+            while order < MAX_ORDER {
+                let buddy = frame ^ (1 << order);
+                if !self.remove_free(lists, order, buddy) {
+                    break;
+                }
+
+                frame = frame.min(buddy);
+                order += 1;
+            }
+
+            self.push_free(lists, order, frame);
+        }
+    }
+
+    impl Default for BuddyPageAllocator {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Allocator for BuddyPageAllocator {
+        fn allocate_phy_pages(&mut self, layout: &AllocationLayout) -> Result<PhysicalPages> {
+            let align_pages = (layout.align().max(PAGE_SIZE)).div_ceil(PAGE_SIZE);
+            let order = Self::order_for(layout.num_pages()).max(Self::order_for(align_pages));
+
+            if order > MAX_ORDER {
+                return Err(Error::ContigiousPhysicalRangeUnavailable(
+                    layout.num_pages() as u64,
+                ));
+            }
+
+            let mut lists = self.free_lists.lock();
+            self.ensure_init(&mut lists);
+
+            let frame = self
+                .pop_split(&mut lists, order)
+                .ok_or(Error::PhysicalOOM)?;
+
+            Ok(PhysicalPages {
+                phy_page_start: self.frame_to_paddr(frame),
+                num_allocated_pages: layout.num_pages(),
+            })
+        }
+
+        fn free_phy_pages(
+            &mut self,
+            phy_page_start: PhysicalAddress,
+            num_pages: usize,
+        ) -> Result<()> {
+            let frame = self.paddr_to_frame(phy_page_start);
+            let order = Self::order_for(num_pages);
+
+            let mut lists = self.free_lists.lock();
+            self.ensure_init(&mut lists);
+            self.push_coalesce(&mut lists, order, frame);
+
+            Ok(())
+        }
+    }
+}
+
+/// seL4-style capability/untyped-memory layer built on top of
+/// [`physical_page_alloc`].
+///
+/// Where `physical_page_alloc::Allocator` hands out anonymous pages,
+/// `cap::Untyped` hands out *typed* kernel objects (page-table nodes, frames,
+/// CNodes, TCBs) by bumping a watermark through a region it already owns.
+/// This is the allocation scheme the rest of early boot should prefer: it is
+/// deterministic and fragmentation-free, since every object size is a power
+/// of two and the watermark is always re-aligned before a carve-out.
+pub mod cap {
+    use heapless::Vec;
+
+    use crate::{
+        address::{Address, PhysicalAddress},
+        error::{Error, Result},
+    };
+
+    /// Maximum objects a single [`Untyped::retype`] call can carve out.
+    const MAX_RETYPE_COUNT: usize = 64;
+
+    /// Kernel object kinds that can be retyped out of an [`Untyped`] region.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ObjectType {
+        /// One MMU translation-table node (see [`crate::mmu::translation_table`]).
+        PageTable,
+        /// One physical page frame.
+        Frame,
+        /// A capability node: a table of [`Capability`] slots.
+        CNode,
+        /// A thread control block.
+        Tcb,
+    }
+
+    impl ObjectType {
+        /// `log2` of this object's size in bytes. Every object this layer
+        /// hands out is a power-of-two run so `retype` can align the
+        /// watermark without any extra bookkeeping.
+        const fn size_bits(self) -> u32 {
+            match self {
+                ObjectType::PageTable => 12,
+                ObjectType::Frame => 12,
+                ObjectType::CNode => 12,
+                ObjectType::Tcb => 9,
+            }
+        }
+    }
+
+    /// A capability to a kernel object retyped out of an [`Untyped`] region.
+    ///
+    /// Records just enough to find the object again: where it lives and what
+    /// it was retyped as.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Capability {
+        phy_addr: PhysicalAddress,
+        object_type: ObjectType,
+    }
+
+    impl Capability {
+        pub fn physical_address(&self) -> PhysicalAddress {
+            self.phy_addr
+        }
+
+        pub fn object_type(&self) -> ObjectType {
+            self.object_type
+        }
+    }
+
+    /// A contiguous physical region, `2^size_bits` bytes starting at `base`,
+    /// that kernel objects are carved out of.
+    ///
+    /// `retype` never returns memory it has already handed out: it tracks a
+    /// `watermark` byte offset from `base` and only ever moves it forward,
+    /// aligning up to each object's natural size first. `free` resets the
+    /// watermark to zero, which is how this region's children are freed --
+    /// all at once, by construction, since nothing below the watermark is
+    /// individually tracked. Callers are responsible for not using a
+    /// `Capability` after the `Untyped` it came from has been freed.
+    pub struct Untyped {
+        base: PhysicalAddress,
+        size_bits: u32,
+        watermark: u64,
+        num_children: usize,
+    }
+
+    impl Untyped {
+        pub const fn new(base: PhysicalAddress, size_bits: u32) -> Self {
+            Self {
+                base,
+                size_bits,
+                watermark: 0,
+                num_children: 0,
+            }
+        }
+
+        pub fn physical_address(&self) -> PhysicalAddress {
+            self.base
+        }
+
+        pub fn size_bits(&self) -> u32 {
+            self.size_bits
+        }
+
+        pub fn watermark(&self) -> u64 {
+            self.watermark
+        }
+
+        pub fn num_children(&self) -> usize {
+            self.num_children
+        }
+
+        fn capacity(&self) -> u64 {
+            1u64 << self.size_bits
+        }
+
+        /// Carves `count` `object_type` objects out of this region.
+        ///
+        /// Each object is handed out at a `watermark` aligned up to its
+        /// `size_bits`, which is then bumped past it, so every returned
+        /// capability is both correctly aligned and disjoint from every
+        /// other live object in this `Untyped`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Error::UntypedExhausted` if the remaining space can't fit
+        /// `count` aligned objects of `object_type`.
+        pub fn retype(
+            &mut self,
+            object_type: ObjectType,
+            count: usize,
+        ) -> Result<Vec<Capability, MAX_RETYPE_COUNT>> {
+            let obj_size_bits = object_type.size_bits();
+            let obj_size = 1u64 << obj_size_bits;
+            let align = obj_size;
+
+            let mut watermark = self.watermark;
+            let mut caps = Vec::new();
+
+            for _ in 0..count {
+                let aligned = (watermark + align - 1) & !(align - 1);
+
+                if aligned + obj_size > self.capacity() {
+                    return Err(Error::UntypedExhausted(self.size_bits));
+                }
+
+                let phy_addr = PhysicalAddress::new(self.base.as_raw_ptr() + aligned as usize);
+                caps.push(Capability {
+                    phy_addr,
+                    object_type,
+                })
+                .map_err(|_| Error::UntypedExhausted(self.size_bits))?;
+
+                watermark = aligned + obj_size;
+            }
+
+            self.watermark = watermark;
+            self.num_children += count;
+
+            Ok(caps)
+        }
+
+        /// Resets the watermark, freeing every object previously retyped
+        /// from this region.
+        pub fn free(&mut self) {
+            self.watermark = 0;
+            self.num_children = 0;
+        }
+    }
 }
 
+/// MAIR-indexed memory type of a mapping, in place of a bare Normal/Device
+/// split. Each variant names one `AttrIndx` slot `mmu::translation_table`
+/// programs into a leaf descriptor and `mmu::config_el1_memory_attributes`
+/// installs into `MAIR_EL1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemoryKind {
-    /// DRAM memory: always cache-able.
-    Normal,
+    /// Cacheable (write-back, read/write allocate) DRAM -- the default for
+    /// ordinary kernel/user memory.
+    NormalCacheable,
+
+    /// Non-cacheable DRAM. Useful for write-combining buffers (e.g. a
+    /// framebuffer) where cache coherency traffic isn't worth paying for.
+    NormalNonCacheable,
+
+    /// Device-nGnRE: no gathering, no reordering, early write ack. The
+    /// usual default for MMIO peripherals.
+    DeviceNonGatheringNonReorderingEarlyAck,
+
+    /// Device-nGnRnE: no gathering, no reordering, no early write ack --
+    /// the most conservative device memory type, for peripherals that
+    /// can't tolerate a write being acknowledged before it lands.
+    DeviceNonGatheringNonReorderingNonEarlyAck,
 
-    /// Device (Peripherals) memory: always non cache-able.
-    Device,
+    /// Device-GRE: gathering, reordering, early write ack -- the least
+    /// restrictive device memory type, for peripherals that tolerate
+    /// merged/reordered accesses.
+    DeviceGatheringReorderingEarlyAck,
 }
 
-/// Memory Map description of either a Normal or Device memory region
+/// Memory Map description of a mapped region, tagged with the `MemoryKind`
+/// its leaf descriptors should (or do) carry.
 #[derive(Debug, Clone, Copy)]
-pub enum MemoryMap {
-    /// Normal (DRAM) Memory Region is always cache-able.
-    Normal(MapDesc),
+pub struct MemoryMap {
+    desc: MapDesc,
+    kind: MemoryKind,
+}
+
+impl MemoryMap {
+    pub fn new(desc: MapDesc, kind: MemoryKind) -> Self {
+        Self { desc, kind }
+    }
+
+    pub fn desc(&self) -> &MapDesc {
+        &self.desc
+    }
 
-    /// Device Memory Region is always non cache-able.
-    Device(MapDesc),
+    pub fn kind(&self) -> MemoryKind {
+        self.kind
+    }
 }
 
 /// Describes a phy_addr -> virt_addr mapping of `num_pages` page count.
@@ -118,9 +600,43 @@ pub struct MapDesc {
     virt_addr: VirtualAddress,
     num_pages: usize,
     access_perms: AccessPermissions,
+    /// Install leaf descriptors with the Access Flag cleared, so the first
+    /// access traps an AF fault instead of being resident up front. Off by
+    /// default -- ordinary mappings stay immediately accessible.
+    lazy_access: bool,
+    /// Install writable leaf descriptors with the Dirty Bit Modifier set,
+    /// so hardware (or, lacking `TCR_EL1.HD`, the permission-fault handler)
+    /// can tell a written page apart from one that's merely been mapped.
+    /// Off by default. No effect on read-only mappings.
+    track_dirty: bool,
+    /// Install a reserved-but-not-present descriptor instead of a resident
+    /// one: `phy_addr` is ignored (there's no backing frame yet), and
+    /// `access_perms`/the owning `MemoryMap`'s kind are stashed in the
+    /// otherwise-unused leaf bits for `TranslationTable::resolve_fault` to
+    /// read back once a fault allocates the real frame. Off by default.
+    /// Only meaningful at 4 KiB granularity -- see
+    /// `TranslationTable::map_impl`.
+    reserved: bool,
 }
 
 impl MapDesc {
+    pub fn new(
+        phy_addr: PhysicalAddress,
+        virt_addr: VirtualAddress,
+        num_pages: usize,
+        access_perms: AccessPermissions,
+    ) -> Self {
+        Self {
+            phy_addr,
+            virt_addr,
+            num_pages,
+            access_perms,
+            lazy_access: false,
+            track_dirty: false,
+            reserved: false,
+        }
+    }
+
     pub fn physical_address(&self) -> PhysicalAddress {
         self.phy_addr
     }
@@ -136,6 +652,34 @@ impl MapDesc {
     pub fn access_permissions(&self) -> AccessPermissions {
         self.access_perms
     }
+
+    pub fn set_access_permissions(&mut self, access_perms: AccessPermissions) {
+        self.access_perms = access_perms;
+    }
+
+    pub fn lazy_access(&self) -> bool {
+        self.lazy_access
+    }
+
+    pub fn set_lazy_access(&mut self, lazy_access: bool) {
+        self.lazy_access = lazy_access;
+    }
+
+    pub fn track_dirty(&self) -> bool {
+        self.track_dirty
+    }
+
+    pub fn set_track_dirty(&mut self, track_dirty: bool) {
+        self.track_dirty = track_dirty;
+    }
+
+    pub fn reserved(&self) -> bool {
+        self.reserved
+    }
+
+    pub fn set_reserved(&mut self, reserved: bool) {
+        self.reserved = reserved;
+    }
 }
 
 // AccessPermissions of a Mapped Region
@@ -171,10 +715,7 @@ impl AccessPermissions {
 
 impl core::fmt::Display for MemoryMap {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        match self {
-            MemoryMap::Normal(desc) => write!(f, "Normal Memory Map: {desc}"),
-            MemoryMap::Device(desc) => write!(f, "Device Memory Map: {desc}"),
-        }
+        write!(f, "{:?} Memory Map: {}", self.kind, self.desc)
     }
 }
 
@@ -3,8 +3,9 @@ use core::{
     cmp::{max, min},
     mem::size_of,
     ops::Range,
-    ptr::NonNull,
+    ptr::{copy_nonoverlapping, NonNull},
     slice,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use spin::{Mutex, MutexGuard};
@@ -26,8 +27,14 @@ pub struct BuddyAllocator {
 }
 
 impl BuddyAllocator {
-    /// Creates a `BuddyAllocator` which manages the provided physical address
-    /// range.
+    /// Creates a `BuddyAllocator` whose bitmaps and free-list levels cover the
+    /// entire `mem` span, but that does not yet have any memory to hand out.
+    ///
+    /// Real firmware memory maps (E820/device-tree) are fragmented: a handful of
+    /// usable stripes punctuated by reserved/MMIO holes. `mem` should be the
+    /// full bounding span those stripes fall within (so the bitmaps are sized to
+    /// cover all of them); call [`Self::add_region`] once per usable stripe to
+    /// actually fold memory in.
     ///
     /// # Safety
     ///
@@ -45,6 +52,31 @@ impl BuddyAllocator {
         Some(Self { storage })
     }
 
+    /// Folds `mem` into the arena as free, usable memory, recursing into
+    /// `Storage::add` the same way the original single-range `manage` used to.
+    ///
+    /// Calling this more than once lets discontiguous stripes (or memory
+    /// discovered only after an early boot stage, i.e. hotplug) be folded into
+    /// the same allocator one at a time; `mem` only needs to fall within the
+    /// span originally given to `manage`, not be contiguous with any
+    /// previously-added region.
+    ///
+    /// # Safety
+    ///
+    /// `mem` must point to a valid physical range that falls within the span
+    /// passed to `manage`, is not currently in use, and has not already been
+    /// folded in via a previous `add_region` or `reserve` call.
+    pub unsafe fn add_region(&self, mem: Range<PhysicalAddress>) {
+        let mem_start = max(mem.start, self.storage.start_page);
+        let mem_end = min(mem.end, self.storage.end_page);
+
+        if mem_start >= mem_end {
+            return;
+        }
+
+        self.storage.add(self.storage.max_level, mem_start..mem_end);
+    }
+
     /// Return an Unique physical address range of the desired size.
     ///
     /// # Safety
@@ -56,32 +88,32 @@ impl BuddyAllocator {
         }
 
         let start_level = max(size.ilog2(), self.storage.min_level);
-        for level in start_level..=self.storage.max_level {
-            let mut free_area = self.storage.get_free_area(level);
-
-            if !free_area.free_list.is_empty() {
-                let block = free_area.free_list.pop_back().unwrap();
-                free_area.mark_used(level, block, self.storage.zero_page);
-                drop(free_area);
+        loop {
+            let level = match self.storage.smallest_nonempty_level(start_level) {
+                Some(level) => level,
+                None => return Err(Error::PhysicalOOM),
+            };
 
-                let mut level = level;
-                while level != start_level {
-                    level -= 1;
-
-                    let buddy = self.storage.get_buddy(block, level);
-                    FreeBlock::init(buddy as *const FreeBlock as usize as *mut FreeBlock);
+            let mut free_area = self.storage.get_free_area(level);
+            match free_area.free_list.pop_back() {
+                Some(block) => {
+                    free_area.mark_used(level, block, self.storage.zero_page);
+                    if free_area.free_list.is_empty() {
+                        self.storage.clear_level_bit(level);
+                    }
+                    drop(free_area);
 
-                    let mut free_area = self.storage.get_free_area(level);
-                    free_area.free_list.push_back(buddy);
+                    self.storage.split_down(block, level, start_level);
 
-                    free_area.mark_used(level, block, self.storage.zero_page);
+                    return Ok(PhysicalAddress::new(block as *const FreeBlock as usize));
+                }
+                None => {
+                    // The summary bit was stale (cleared concurrently since
+                    // we read it); retry with the now-accurate picture.
+                    self.storage.clear_level_bit(level);
                 }
-
-                return Ok(PhysicalAddress::new(block as *const FreeBlock as usize));
             }
         }
-
-        Err(Error::PhysicalOOM)
     }
 
     /// Frees the `ptr`. `ptr` will be reused for future allocations.
@@ -109,6 +141,7 @@ impl BuddyAllocator {
             if !buddy_free {
                 FreeBlock::init(block as *const FreeBlock as usize as *mut FreeBlock);
                 free_area.free_list.push_back(block);
+                self.storage.mark_level_nonempty(level);
                 break;
             }
 
@@ -118,6 +151,9 @@ impl BuddyAllocator {
                 .free_list
                 .cursor_mut_from_ptr(buddy as *const FreeBlock);
             cursor.remove().unwrap();
+            if free_area.free_list.is_empty() {
+                self.storage.clear_level_bit(level);
+            }
 
             block = Storage::get_smaller(block, buddy);
         }
@@ -125,6 +161,27 @@ impl BuddyAllocator {
         Ok(())
     }
 
+    /// Marks `range` permanently occupied so `alloc` will never hand any of it
+    /// out, without requiring `range` to be power-of-two-sized or aligned.
+    /// Needed for carve-outs that overlap the managed arena: the kernel image,
+    /// DMA-coherent pools, a framebuffer, MMIO windows.
+    ///
+    /// # Safety
+    ///
+    /// `self` must have been created using `manage`, and `range` must not
+    /// overlap any range already returned by `alloc` or previously passed to
+    /// `reserve`.
+    pub unsafe fn reserve(&self, range: Range<PhysicalAddress>) -> Result<()> {
+        let start = max(range.start, self.storage.start_page);
+        let end = min(range.end, self.storage.end_page);
+
+        if start >= end {
+            return Ok(());
+        }
+
+        self.storage.reserve(self.storage.max_level, start..end)
+    }
+
     #[cfg(test)]
     /// Returns a list of # of blocks for each size that are free
     /// (size, count)
@@ -137,6 +194,11 @@ impl BuddyAllocator {
         }
         return free_area_info;
     }
+
+    fn level_for(&self, layout: Layout) -> u32 {
+        let size = max(layout.size().next_power_of_two(), layout.align());
+        max(size.ilog2(), self.storage.min_level)
+    }
 }
 
 #[repr(C)]
@@ -148,6 +210,10 @@ struct Storage {
     min_level: u32,
     max_level: u32,
     free_areas: &'static [FreeAreaMutex],
+    // Bit `level - min_level` is set iff that level's free list is
+    // non-empty. Lets `alloc` jump straight to the smallest satisfiable
+    // level instead of locking and probing each one in turn.
+    summary: AtomicUsize,
     // FreeArea and FreeMap memory
 }
 
@@ -189,6 +255,10 @@ impl Storage {
 
                 count += 1;
             }
+
+            if count > 0 {
+                self.mark_level_nonempty(level);
+            }
         }
 
         let mem_start = mem_start + count * level_size;
@@ -198,11 +268,185 @@ impl Storage {
         }
     }
 
+    /// Marks every maximal aligned buddy block fully contained in `mem` as
+    /// used, recursing into smaller levels for the unaligned head/tail the
+    /// same way `add` splits an incoming range to link it.
+    unsafe fn reserve(&self, level: u32, mem: Range<PhysicalAddress>) -> Result<()> {
+        let mem_start = mem.start;
+        let mem_end = mem.end;
+        let level_size = 1usize << level;
+        let offset = mem_start.align_offset(level_size);
+        let mem_start_cur_level = min(
+            PhysicalAddress::new(mem_start.align_up(level_size)),
+            mem_end,
+        );
+
+        if offset != 0 {
+            self.reserve(level - 1, mem_start..mem_start_cur_level)?;
+        }
+
+        let mem_start = mem_start_cur_level;
+        if mem_start >= mem_end {
+            return Ok(());
+        }
+
+        assert!(mem_start.is_aligned(level_size));
+
+        let mut count = 0;
+        while mem_start + (count + 1) * level_size <= mem_end {
+            let block_addr = (mem_start + count * level_size).as_raw_ptr();
+            self.reserve_block(level, block_addr)?;
+            count += 1;
+        }
+
+        let mem_start = mem_start + count * level_size;
+        assert!(mem_start <= mem_end);
+        if mem_start != mem_end {
+            self.reserve(level - 1, mem_start..mem_end)?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks the single block of size `1 << level` starting at `block_addr`
+    /// (rounded down to its level's alignment) used. If it isn't directly on
+    /// the level's free list, the containing ancestor block is found and
+    /// split down one level at a time until we reach it, pushing each
+    /// not-taken sibling back onto its own level's free list as we go.
+    unsafe fn reserve_block(&self, level: u32, block_addr: usize) -> Result<()> {
+        let block_size = 1usize << level;
+        let aligned = block_addr & !(block_size - 1);
+
+        if level > self.max_level {
+            return Err(Error::PhysicalRangeAlreadyReserved(PhysicalAddress::new(
+                aligned,
+            )));
+        }
+
+        let block = &*(aligned as *const FreeBlock);
+
+        {
+            let mut free_area = self.get_free_area(level);
+            if block.link.is_linked() {
+                let mut cursor = free_area
+                    .free_list
+                    .cursor_mut_from_ptr(block as *const FreeBlock);
+                cursor.remove().unwrap();
+                free_area.mark_used(level, block, self.zero_page);
+                if free_area.free_list.is_empty() {
+                    self.clear_level_bit(level);
+                }
+                return Ok(());
+            }
+        }
+
+        self.reserve_block(level + 1, aligned)?;
+
+        let sibling_addr = aligned ^ block_size;
+        let sibling = FreeBlock::init(sibling_addr as *mut FreeBlock);
+
+        let mut free_area = self.get_free_area(level);
+        free_area.free_list.push_back(sibling);
+        free_area.mark_used(level, block, self.zero_page);
+        self.mark_level_nonempty(level);
+
+        Ok(())
+    }
+
+    /// Splits `block` down from `from_level` to the smaller `to_level`,
+    /// pushing the freed sibling at each level onto its own free list and
+    /// leaving `block` marked used at `to_level`. `block` must already be
+    /// marked used at `from_level` (e.g. just popped off its free list).
+    unsafe fn split_down(&self, block: &'static FreeBlock, from_level: u32, to_level: u32) {
+        let mut level = from_level;
+        while level != to_level {
+            level -= 1;
+
+            let buddy = self.get_buddy(block, level);
+            FreeBlock::init(buddy as *const FreeBlock as usize as *mut FreeBlock);
+
+            let mut free_area = self.get_free_area(level);
+            free_area.free_list.push_back(buddy);
+
+            free_area.mark_used(level, block, self.zero_page);
+            self.mark_level_nonempty(level);
+        }
+    }
+
+    /// Attempts to grow `block` from `old_level` to the bigger `new_level`
+    /// without moving it, by absorbing its buddy one level at a time. This
+    /// only works while `block` is the lower half of every pair along the
+    /// way (so its address doesn't change) and each buddy is entirely free;
+    /// the first level that fails either check rolls back any levels already
+    /// absorbed (via `split_down`) and aborts, leaving `block` exactly as it
+    /// was found.
+    unsafe fn try_grow_in_place(
+        &self,
+        block: &'static FreeBlock,
+        old_level: u32,
+        new_level: u32,
+    ) -> bool {
+        let mut level = old_level;
+        while level < new_level {
+            if (block as *const FreeBlock as usize) & (1usize << level) != 0 {
+                break;
+            }
+
+            let buddy = self.get_buddy(block, level);
+            let mut free_area = self.get_free_area(level);
+            if !buddy.link.is_linked() {
+                break;
+            }
+
+            let mut cursor = free_area
+                .free_list
+                .cursor_mut_from_ptr(buddy as *const FreeBlock);
+            cursor.remove().unwrap();
+            free_area.mark_used(level, buddy, self.zero_page);
+            if free_area.free_list.is_empty() {
+                self.clear_level_bit(level);
+            }
+
+            level += 1;
+        }
+
+        if level == new_level {
+            true
+        } else {
+            self.split_down(block, level, old_level);
+            false
+        }
+    }
+
     unsafe fn get_free_area(&self, level: u32) -> MutexGuard<FreeArea> {
         let level = level - self.min_level;
         self.free_areas[level as usize].lock()
     }
 
+    fn mark_level_nonempty(&self, level: u32) {
+        self.summary
+            .fetch_or(1usize << (level - self.min_level), Ordering::Relaxed);
+    }
+
+    fn clear_level_bit(&self, level: u32) {
+        self.summary
+            .fetch_and(!(1usize << (level - self.min_level)), Ordering::Relaxed);
+    }
+
+    /// Returns the smallest level `>= start_level` whose free list was
+    /// non-empty as of the last bitmap update, or `None` if none are. The
+    /// caller must still re-check the free list under its lock: the bit
+    /// only summarizes, it doesn't substitute for the lock.
+    fn smallest_nonempty_level(&self, start_level: u32) -> Option<u32> {
+        let low = start_level - self.min_level;
+        let candidates = self.summary.load(Ordering::Relaxed) & (usize::MAX << low);
+        if candidates == 0 {
+            None
+        } else {
+            Some(self.min_level + candidates.trailing_zeros())
+        }
+    }
+
     unsafe fn get_buddy(&self, block: &FreeBlock, level: u32) -> &FreeBlock {
         &*(self.get_buddy_ptr(block, level) as *const FreeBlock)
     }
@@ -240,6 +484,10 @@ impl Storage {
         let min_level = min_alloc_size.ilog2();
         let max_level = max_alloc_size.ilog2();
         let num_levels = max_alloc_size.ilog2() - min_level + 1;
+        assert!(
+            num_levels <= usize::BITS,
+            "too many levels for the summary bitmap"
+        );
         let end_page = PhysicalAddress::new(mem.end.align_down(min_alloc_size));
         let mut alloc_start = mem.start;
 
@@ -268,12 +516,10 @@ impl Storage {
             min_level,
             max_level,
             free_areas: slice::from_raw_parts_mut(free_areas, num_levels as usize),
+            summary: AtomicUsize::new(0),
         });
 
-        let this = &mut *this;
-        this.add(max_level, this.start_page..this.end_page);
-
-        Some(this)
+        Some(&mut *this)
     }
 
     fn claim_memory<T: Sized>(
@@ -400,6 +646,45 @@ unsafe impl Allocator for BuddyAllocator {
         self.free(PhysicalAddress::new(ptr.as_ptr() as usize), size)
             .unwrap()
     }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> core::result::Result<NonNull<[u8]>, AllocError> {
+        let old_level = self.level_for(old_layout);
+        let new_level = self.level_for(new_layout);
+
+        if new_level > old_level {
+            let block = &*(ptr.as_ptr() as *const FreeBlock);
+            if !self.storage.try_grow_in_place(block, old_level, new_level) {
+                let new_ptr = self.allocate(new_layout)?;
+                copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_mut_ptr(), old_layout.size());
+                self.deallocate(ptr, old_layout);
+                return Ok(new_ptr);
+            }
+        }
+
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> core::result::Result<NonNull<[u8]>, AllocError> {
+        let old_level = self.level_for(old_layout);
+        let new_level = self.level_for(new_layout);
+
+        if new_level < old_level {
+            let block = &*(ptr.as_ptr() as *const FreeBlock);
+            self.storage.split_down(block, old_level, new_level);
+        }
+
+        Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+    }
 }
 
 #[cfg(test)]
@@ -446,6 +731,7 @@ mod tests {
         assert!(allocator.is_some());
 
         let allocator = allocator.unwrap();
+        unsafe { allocator.add_region(mem_start..mem_end) };
         let alloc_sizes = get_alloc_sizes(&allocator);
 
         // Verify if all the blocks can be allocated.
@@ -496,6 +782,7 @@ mod tests {
         assert!(allocator.is_some());
 
         let allocator = allocator.unwrap();
+        unsafe { allocator.add_region(mem_start..mem_end) };
         let free_area_info = unsafe { allocator.get_free_area_information() };
         let alloc_count = free_area_info.iter().fold(0usize, |sum, (size, count)| {
             sum + (size / min_alloc_size) * count
@@ -529,6 +816,56 @@ mod tests {
         assert_eq!(free_area_info, free_area_info_later);
     }
 
+    #[test]
+    fn buddy_grow_shrink_test() {
+        const min_alloc_size: usize = 16;
+        const max_alloc_size: usize = 64 * 1024;
+
+        let chunk = Box::new([0xfeu8; max_alloc_size]);
+        let mem_start = PhysicalAddress::new(chunk.as_ptr() as usize);
+        let mem_end = mem_start + max_alloc_size;
+        let allocator =
+            unsafe { BuddyAllocator::manage(mem_start..mem_end, min_alloc_size, max_alloc_size) }
+                .unwrap();
+        unsafe { allocator.add_region(mem_start..mem_end) };
+
+        let free_area_info = unsafe { allocator.get_free_area_information() };
+
+        let small = Layout::from_size_align(min_alloc_size, min_alloc_size).unwrap();
+        let big = Layout::from_size_align(min_alloc_size * 4, min_alloc_size * 4).unwrap();
+
+        // Growing in place reuses the same address when nothing else has
+        // carved up the surrounding buddies.
+        let mem = unsafe { allocator.allocate(small) }.unwrap();
+        let ptr = mem.as_ptr() as *mut u8;
+        let grown = unsafe { allocator.grow(NonNull::new(ptr).unwrap(), small, big) }.unwrap();
+        assert_eq!(grown.as_ptr() as *mut u8, ptr);
+
+        // Shrinking back splits the block down and releases the freed
+        // buddies, restoring the original free-area layout.
+        let shrunk = unsafe { allocator.shrink(NonNull::new(ptr).unwrap(), big, small) }.unwrap();
+        assert_eq!(shrunk.as_ptr() as *mut u8, ptr);
+        unsafe { allocator.deallocate(NonNull::new(ptr).unwrap(), small) };
+
+        let free_area_info_later = unsafe { allocator.get_free_area_information() };
+        assert_eq!(free_area_info, free_area_info_later);
+
+        // A block that isn't the lower half of its pair can't grow in place
+        // and must be relocated instead.
+        let low = unsafe { allocator.allocate(small) }.unwrap();
+        let low_ptr = low.as_ptr() as *mut u8;
+        let high = unsafe { allocator.allocate(small) }.unwrap();
+        let high_ptr = high.as_ptr() as *mut u8;
+        assert!(high_ptr > low_ptr);
+
+        let relocated =
+            unsafe { allocator.grow(NonNull::new(high_ptr).unwrap(), small, big) }.unwrap();
+        assert_ne!(relocated.as_ptr() as *mut u8, high_ptr);
+
+        unsafe { allocator.deallocate(NonNull::new(low_ptr).unwrap(), small) };
+        unsafe { allocator.deallocate(NonNull::new(relocated.as_ptr() as *mut u8).unwrap(), big) };
+    }
+
     fn randomize_mem(mut mem: NonNull<[u8]>) {
         let mem = unsafe { mem.as_mut() };
         for v in mem.iter_mut() {
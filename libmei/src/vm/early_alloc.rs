@@ -0,0 +1,143 @@
+//! Early, pre-heap physical frame allocator.
+//!
+//! `TranslationTable::map` needs fresh physical pages to back new
+//! intermediate table levels, but `setup_mmu` runs before either real
+//! physical allocator (`vm::buddy::BuddyAllocator`,
+//! `vm::physical_page_alloc::BuddyPageAllocator`) has anywhere to get its own
+//! memory from -- standing one of those up needs a region to `manage()`,
+//! which is exactly the chicken-and-egg problem the MMU setup is trying to
+//! solve. `EarlyFrameAllocator` only needs arithmetic: it bumps a cursor
+//! through a span of already-known-usable DRAM and hands out
+//! `GRANULE_SIZE`-aligned, zeroed frames one at a time, with no free list and
+//! no bookkeeping besides the cursor itself.
+//!
+//! It never reclaims memory -- `deallocate` is a no-op, and
+//! [`EarlyFrameAllocator::free_cursor`] exposes the high-water mark so
+//! whatever allocator eventually takes over can treat everything above it as
+//! free.
+
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cmp::max,
+    ptr::NonNull,
+};
+
+use macros::ctor;
+use spin::Mutex;
+
+use crate::{
+    address::{Address, PhysicalAddress},
+    address_map, bug, kimage,
+    mmu::GRANULE_SIZE,
+    vm::phy2virt,
+};
+
+/// A span of physical memory known to be usable DRAM, as reported by
+/// firmware/the device tree -- or, this early in boot, a hardcoded board
+/// description (see `address_map::DRAM_BASE`/`DRAM_SIZE`).
+#[derive(Debug, Clone, Copy)]
+pub struct RamBlock {
+    pub base: PhysicalAddress,
+    pub size: usize,
+}
+
+impl RamBlock {
+    fn end(&self) -> PhysicalAddress {
+        self.base + self.size as isize
+    }
+}
+
+/// Bump allocator handing out `GRANULE_SIZE`-aligned, zeroed physical frames
+/// out of the first `RamBlock` with room left once the kernel image and its
+/// boot stack are carved out.
+pub struct EarlyFrameAllocator {
+    cursor: Mutex<PhysicalAddress>,
+    end: PhysicalAddress,
+}
+
+impl EarlyFrameAllocator {
+    /// Builds an allocator over `blocks`, after subtracting the kernel's own
+    /// footprint -- `kimage::kernel_stack_base()` plus
+    /// `kimage::kernel_image_size()`, i.e. the boot stack and the image
+    /// stacked right after it, both counted from physical address `0` -- from
+    /// whichever block it falls in.
+    pub fn new(blocks: &[RamBlock]) -> Self {
+        let reserved_end =
+            PhysicalAddress::new(kimage::kernel_stack_base() + kimage::kernel_image_size());
+
+        for block in blocks {
+            let block_end = block.end();
+            if block_end <= reserved_end {
+                // Entirely inside the kernel's own footprint -- nothing
+                // usable here.
+                continue;
+            }
+
+            let start =
+                PhysicalAddress::new(max(block.base, reserved_end).align_up(GRANULE_SIZE));
+            if start < block_end {
+                return Self {
+                    cursor: Mutex::new(start),
+                    end: block_end,
+                };
+            }
+        }
+
+        bug!("EarlyFrameAllocator: no usable DRAM left once the kernel image/stack are carved out")
+    }
+
+    /// Hands out the next `GRANULE_SIZE`-aligned frame, zeroed, advancing the
+    /// cursor past it.
+    pub fn alloc_frame(&self) -> PhysicalAddress {
+        let mut cursor = self.cursor.lock();
+        let frame = *cursor;
+
+        if frame + GRANULE_SIZE as isize > self.end {
+            bug!("EarlyFrameAllocator: out of pre-heap DRAM");
+        }
+
+        unsafe {
+            core::ptr::write_bytes(phy2virt(frame).as_mut_ptr::<u8>(), 0, GRANULE_SIZE);
+        }
+
+        *cursor = frame + GRANULE_SIZE as isize;
+        frame
+    }
+
+    /// High-water mark: every frame below this has already been handed out.
+    /// Everything from here to the end of the seeding `RamBlock` is still
+    /// free, for whatever allocator takes over once there's a heap to build
+    /// one in.
+    pub fn free_cursor(&self) -> PhysicalAddress {
+        *self.cursor.lock()
+    }
+}
+
+unsafe impl Allocator for EarlyFrameAllocator {
+    fn allocate(&self, layout: Layout) -> core::result::Result<NonNull<[u8]>, AllocError> {
+        if layout.size() > GRANULE_SIZE || layout.align() > GRANULE_SIZE {
+            return Err(AllocError);
+        }
+
+        let frame = self.alloc_frame();
+        let ptr = NonNull::new(phy2virt(frame).as_mut_ptr::<u8>()).ok_or(AllocError)?;
+
+        Ok(NonNull::slice_from_raw_parts(ptr, GRANULE_SIZE))
+    }
+
+    unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {
+        // Bump allocator: individual frames are never reclaimed. Once a real
+        // allocator takes over, it starts fresh from `free_cursor()`.
+    }
+}
+
+/// Seeded from the board's hardcoded DRAM description (`address_map`) rather
+/// than the device tree: this runs as a default-priority (`0`) constructor,
+/// before `DeviceTree::parse` has anywhere to get its blob pointer from. A
+/// `setup_mmu`-driving `StaticInitialized` added later should pick a higher
+/// `#[ctor(N)]` priority so it runs after this one.
+#[ctor]
+pub static EARLY_FRAME_ALLOC: EarlyFrameAllocator = EarlyFrameAllocator::new(&[RamBlock {
+    base: address_map::DRAM_BASE,
+    size: address_map::DRAM_SIZE,
+}]);
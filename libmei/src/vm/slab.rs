@@ -72,11 +72,18 @@
 use core::{
     alloc::{AllocError, Allocator, GlobalAlloc, Layout},
     fmt::Debug,
+    mem::size_of,
     ops::Range,
     ptr::{null, null_mut, NonNull},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
-use crate::{address::PhysicalAddress, error::Result, mmu};
+use crate::{
+    address::{Address, PhysicalAddress},
+    arch::{self, Arch},
+    error::{Error, Result},
+    vm::buddy,
+};
 use heapless::Vec;
 use macros::ctor;
 use modular_bitfield::prelude::*;
@@ -88,14 +95,138 @@ struct Page {
     hdr: PageHdr,
 }
 
+/// Sentinel stored in an object's inline next-free link (and in
+/// `PageHdr::free_list`) to mean "no more free objects" -- `free_list` is
+/// only 12 bits wide, so this is the largest value it can hold.
+const FREE_LIST_END: u16 = 0xFFF;
+
+impl Page {
+    /// Number of objects `bin` carves out of one page, after the `PageHdr`.
+    fn object_count(bin: &BinDesc) -> usize {
+        (bin.page_size as usize - size_of::<PageHdr>()) / bin.obj_size as usize
+    }
+
+    fn object_base(&self) -> *mut u8 {
+        unsafe { (self as *const Page as *mut u8).add(size_of::<PageHdr>()) }
+    }
+
+    fn object_ptr(&self, bin: &BinDesc, index: u16) -> *mut u8 {
+        unsafe { self.object_base().add(index as usize * bin.obj_size as usize) }
+    }
+
+    fn index_of(&self, bin: &BinDesc, ptr: *mut u8) -> u16 {
+        let offset = ptr as usize - self.object_base() as usize;
+        (offset / bin.obj_size as usize) as u16
+    }
+
+    /// Reads the inline next-free link stored in a free object's own bytes.
+    fn read_next_free(&self, bin: &BinDesc, index: u16) -> u16 {
+        unsafe { (self.object_ptr(bin, index) as *const u16).read_unaligned() }
+    }
+
+    fn write_next_free(&self, bin: &BinDesc, index: u16, next: u16) {
+        unsafe { (self.object_ptr(bin, index) as *mut u16).write_unaligned(next) }
+    }
+
+    /// Lays down a fresh intra-page free list threading every object
+    /// together, and marks the page as belonging to `bin`. `zeroed` must be
+    /// `true` only if every byte of the underlying memory is already known
+    /// to be zero (e.g. the caller just `memset` the whole page), and seeds
+    /// `PageHdr::clean` accordingly.
+    fn init(&mut self, bin: &BinDesc, zeroed: bool) {
+        let count = Page::object_count(bin);
+        debug_assert!(count > 0 && count < FREE_LIST_END as usize);
+
+        for i in 0..count {
+            let next = if i + 1 == count {
+                FREE_LIST_END
+            } else {
+                (i + 1) as u16
+            };
+            self.write_next_free(bin, i as u16, next);
+        }
+
+        self.hdr.set_next_link(null());
+        self.hdr.set_prev_link(null());
+        self.hdr.set_free_list(0);
+        self.hdr.set_num_free(count as u16);
+        self.hdr.set_slab_bin_id(bin.bin_id);
+        self.hdr.set_clean(zeroed);
+    }
+
+    fn is_full(&self) -> bool {
+        self.hdr.num_free() == 0
+    }
+
+    /// Whether every object currently on this page's free list is known to
+    /// be all-zero past its inline free-list link (see `PageHdr::clean`).
+    fn is_clean(&self) -> bool {
+        self.hdr.clean()
+    }
+
+    fn alloc_one(&mut self, bin: &BinDesc) -> u16 {
+        debug_assert!(!self.is_full());
+
+        let index = self.hdr.free_list();
+        let next = self.read_next_free(bin, index);
+
+        self.hdr.set_free_list(next);
+        self.hdr.set_num_free(self.hdr.num_free() - 1);
+
+        index
+    }
+
+    fn free_one(&mut self, bin: &BinDesc, index: u16) {
+        let head = self.hdr.free_list();
+
+        self.write_next_free(bin, index, head);
+        self.hdr.set_free_list(index);
+        self.hdr.set_num_free(self.hdr.num_free() + 1);
+        // The caller may have written anything into the object before
+        // freeing it; without per-object tracking we can't tell, so the
+        // whole page's `clean` guarantee is conservatively given up.
+        self.hdr.set_clean(false);
+    }
+
+    /// Like `free_one`, but scrubs the object to zero first. Unlike
+    /// `free_one`, this doesn't clear `clean`: every currently-free slot on
+    /// a page that was clean is still known-zero afterwards.
+    fn free_one_zeroed(&mut self, bin: &BinDesc, index: u16) {
+        unsafe { core::ptr::write_bytes(self.object_ptr(bin, index), 0, bin.obj_size as usize) };
+
+        let head = self.hdr.free_list();
+        self.write_next_free(bin, index, head);
+        self.hdr.set_free_list(index);
+        self.hdr.set_num_free(self.hdr.num_free() + 1);
+    }
+
+    /// Which of the `NUM_PARTIAL_PAGES_LEVELS` approximate-sort buckets this
+    /// (non-full) page currently belongs in, by percentage of objects free:
+    /// bucket 0 is (0%, 20%] free, bucket 4 is (80%, 100%] free.
+    fn free_ratio_bucket(&self, bin: &BinDesc) -> usize {
+        let count = Page::object_count(bin);
+        let free = self.hdr.num_free() as usize;
+        debug_assert!(free > 0 && free <= count);
+
+        ((free * NUM_PARTIAL_PAGES_LEVELS - 1) / count).min(NUM_PARTIAL_PAGES_LEVELS - 1)
+    }
+}
+
 #[derive(Clone, Copy)]
-#[bitfield(bits = 128)]
+#[bitfield(bits = 136)]
 struct PageHdr {
     prev: B48,
     next: B48,
     num_free: B12,
     free_list: B12,
     slab_bin_id: B8,
+    /// Whether every object on this page's free list is known to be zero past
+    /// its inline free-list link: set when the page was zeroed as it was
+    /// carved (`Pool::new_page_zeroed`), cleared by any free that doesn't
+    /// scrub the object first. Lets `alloc_zeroed` skip a redundant `memset`.
+    clean: B1,
+    #[skip]
+    __: B7,
 }
 
 impl PageHdr {
@@ -163,14 +294,18 @@ impl PageHdrList {
     fn push_back(&mut self, page: *mut Page) {
         unsafe {
             let hdr = &mut (*page).hdr;
-
             hdr.set_next_link(null());
-            hdr.set_prev_link(&(*self.tail).hdr);
+
+            if self.tail.is_null() {
+                hdr.set_prev_link(null());
+                self.head = page;
+            } else {
+                hdr.set_prev_link(&(*self.tail).hdr);
+                (*self.tail).hdr.set_next_link(&(*page).hdr);
+            }
         }
+
         self.tail = page;
-        if self.head.is_null() {
-            self.head = page;
-        }
     }
 
     fn pop_back(&mut self) -> Option<*mut Page> {
@@ -179,14 +314,17 @@ impl PageHdrList {
         }
 
         let page = self.tail;
-        self.tail = unsafe {
-            let prev = (*page).hdr.get_prev_link() as *mut Page;
-            (*prev).hdr.set_next_link(null());
+        let prev = unsafe { (*page).hdr.get_prev_link() as *mut Page };
+
+        unsafe {
+            if !prev.is_null() {
+                (*prev).hdr.set_next_link(null());
+            }
+            (*page).hdr.set_next_link(null());
             (*page).hdr.set_prev_link(null());
-            debug_assert!((*page).hdr.get_next_link().is_null());
-            prev
-        };
+        }
 
+        self.tail = prev;
         if self.tail.is_null() {
             self.head = null_mut();
         }
@@ -200,14 +338,17 @@ impl PageHdrList {
         }
 
         let page = self.head;
-        self.head = unsafe {
-            let next = (*page).hdr.get_next_link() as *mut Page;
-            (*next).hdr.set_prev_link(null());
+        let next = unsafe { (*page).hdr.get_next_link() as *mut Page };
+
+        unsafe {
+            if !next.is_null() {
+                (*next).hdr.set_prev_link(null());
+            }
             (*page).hdr.set_next_link(null());
-            debug_assert!((*page).hdr.get_prev_link().is_null());
-            next
-        };
+            (*page).hdr.set_prev_link(null());
+        }
 
+        self.head = next;
         if self.head.is_null() {
             self.tail = null_mut();
         }
@@ -215,6 +356,30 @@ impl PageHdrList {
         Some(page)
     }
 
+    /// Unlinks `page` from wherever it currently sits in this list (head,
+    /// tail, or mid-list). `page` must already be linked into this list.
+    fn remove(&mut self, page: *mut Page) {
+        unsafe {
+            let prev = (*page).hdr.get_prev_link() as *mut Page;
+            let next = (*page).hdr.get_next_link() as *mut Page;
+
+            if prev.is_null() {
+                self.head = next;
+            } else {
+                (*prev).hdr.set_next_link(next as *const PageHdr);
+            }
+
+            if next.is_null() {
+                self.tail = prev;
+            } else {
+                (*next).hdr.set_prev_link(prev as *const PageHdr);
+            }
+
+            (*page).hdr.set_next_link(null());
+            (*page).hdr.set_prev_link(null());
+        }
+    }
+
     fn is_empty(&self) -> bool {
         if self.head.is_null() {
             debug_assert!(self.tail.is_null());
@@ -226,10 +391,28 @@ impl PageHdrList {
     }
 }
 
+/// Which partially-filled page a bin hands out next, once `cur_page` is
+/// exhausted and no fresh page is needed yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SelectionPolicy {
+    /// Pull from the highest (most-free) non-empty bucket: allocations keep
+    /// landing on the same handful of pages, which is best for cache
+    /// locality and `Pool`'s own metadata cache-hit rate.
+    MostFreeFirst,
+    /// Pull from the lowest (least-free) non-empty bucket: drains pages
+    /// towards fully-free sooner, so they can be released back to the
+    /// upstream allocator earlier.
+    LeastFreeFirst,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 struct BinDesc {
     obj_size: u16,
     page_size: u16,
+    /// Written into every `Page`'s `PageHdr::slab_bin_id` so a pointer can be
+    /// mapped back to its bin on free.
+    bin_id: u8,
+    policy: SelectionPolicy,
 }
 
 const NUM_PARTIAL_PAGES_LEVELS: usize = 5;
@@ -260,29 +443,529 @@ impl Pool {
         }
     }
 
-    fn alloc(&mut self) -> Option<&'static [u8]> {
-        todo!()
+    fn page_layout(&self) -> Layout {
+        let page_size = self.bin_desc.page_size as usize;
+        Layout::from_size_align(page_size, page_size).expect("page_size is a power of two")
+    }
+
+    /// Pulls a page out of the partial-page buckets per `self.bin_desc.policy`,
+    /// preferring the bucket the policy names and falling back to the next
+    /// one if it's empty.
+    fn take_partial_page(&mut self) -> Option<&'static mut Page> {
+        let scan_order: [usize; NUM_PARTIAL_PAGES_LEVELS] = match self.bin_desc.policy {
+            SelectionPolicy::MostFreeFirst => [4, 3, 2, 1, 0],
+            SelectionPolicy::LeastFreeFirst => [0, 1, 2, 3, 4],
+        };
+
+        for bucket in scan_order {
+            if let Some(page) = self.partial_pages[bucket].pop_front() {
+                return Some(unsafe { &mut *page });
+            }
+        }
+
+        None
     }
 
-    fn free(&mut self, ptr: *mut u8) -> bool {
-        todo!()
+    fn new_page(&self, page_source: &dyn Allocator) -> Option<&'static mut Page> {
+        let mem = page_source.allocate(self.page_layout()).ok()?;
+        let page = mem.as_ptr() as *mut Page;
+
+        unsafe {
+            (*page).init(&self.bin_desc, false);
+            Some(&mut *page)
+        }
+    }
+
+    /// Like `new_page`, but `memset`s the page to zero before threading the
+    /// free list through it, so it starts out `clean`.
+    fn new_page_zeroed(&self, page_source: &dyn Allocator) -> Option<&'static mut Page> {
+        let mem = page_source.allocate(self.page_layout()).ok()?;
+        let page = mem.as_ptr() as *mut Page;
+
+        unsafe {
+            core::ptr::write_bytes(mem.as_mut_ptr(), 0, self.page_layout().size());
+            (*page).init(&self.bin_desc, true);
+            Some(&mut *page)
+        }
+    }
+
+    /// Allocates one object of this bin's size, carving a fresh page from
+    /// `page_source` if `cur_page` is exhausted and no partial page is
+    /// available.
+    fn alloc(&mut self, page_source: &dyn Allocator) -> Option<NonNull<u8>> {
+        if self.cur_page.as_deref().map_or(true, Page::is_full) {
+            if let Some(exhausted) = self.cur_page.take() {
+                self.full_pages.push_back(exhausted as *mut Page);
+            }
+
+            self.cur_page = self
+                .take_partial_page()
+                .or_else(|| self.new_page(page_source))?;
+        }
+
+        let page = self.cur_page.as_mut().unwrap();
+        let index = page.alloc_one(&self.bin_desc);
+
+        Some(unsafe { NonNull::new_unchecked(page.object_ptr(&self.bin_desc, index)) })
+    }
+
+    /// Like `alloc`, but guarantees the returned object is all-zero. Skips
+    /// re-zeroing past the free-list link bytes when the page handing it out
+    /// is still `clean`; otherwise zeroes the whole object, same cost as a
+    /// generic zeroing allocator built on top of `alloc` would pay.
+    fn alloc_zeroed(&mut self, page_source: &dyn Allocator) -> Option<NonNull<u8>> {
+        if self.cur_page.as_deref().map_or(true, Page::is_full) {
+            if let Some(exhausted) = self.cur_page.take() {
+                self.full_pages.push_back(exhausted as *mut Page);
+            }
+
+            self.cur_page = self
+                .take_partial_page()
+                .or_else(|| self.new_page_zeroed(page_source))?;
+        }
+
+        let page = self.cur_page.as_mut().unwrap();
+        let clean = page.is_clean();
+        let index = page.alloc_one(&self.bin_desc);
+        let ptr = page.object_ptr(&self.bin_desc, index);
+
+        let obj_size = self.bin_desc.obj_size as usize;
+        let zero_len = if clean {
+            size_of::<u16>().min(obj_size)
+        } else {
+            obj_size
+        };
+        unsafe { core::ptr::write_bytes(ptr, 0, zero_len) };
+
+        Some(unsafe { NonNull::new_unchecked(ptr) })
+    }
+
+    /// Frees an object previously returned by `alloc`, moving its page
+    /// between buckets (or back to `page_source`) as its fill ratio crosses
+    /// a watermark.
+    fn free(&mut self, ptr: *mut u8, page_source: &dyn Allocator) {
+        let page = self.page_of(ptr);
+
+        let was_full = page.is_full();
+        let old_bucket = if was_full {
+            None
+        } else {
+            Some(page.free_ratio_bucket(&self.bin_desc))
+        };
+
+        let index = page.index_of(&self.bin_desc, ptr);
+        page.free_one(&self.bin_desc, index);
+
+        self.rebucket_after_free(page, was_full, old_bucket, page_source);
+    }
+
+    /// Like `free`, but scrubs the object to zero before linking it back
+    /// into its page's free list, so a later `alloc_zeroed` can skip
+    /// re-zeroing it as long as the page stays `clean`.
+    fn free_zeroed(&mut self, ptr: *mut u8, page_source: &dyn Allocator) {
+        let page = self.page_of(ptr);
+
+        let was_full = page.is_full();
+        let old_bucket = if was_full {
+            None
+        } else {
+            Some(page.free_ratio_bucket(&self.bin_desc))
+        };
+
+        let index = page.index_of(&self.bin_desc, ptr);
+        page.free_one_zeroed(&self.bin_desc, index);
+
+        self.rebucket_after_free(page, was_full, old_bucket, page_source);
+    }
+
+    fn page_of(&self, ptr: *mut u8) -> &'static mut Page {
+        let page_addr = ptr as usize & !(self.bin_desc.page_size as usize - 1);
+        unsafe { &mut *(page_addr as *mut Page) }
+    }
+
+    /// Moves `page` between `full_pages`/`partial_pages[bucket]`/back to
+    /// `page_source` after one of its objects was just freed, per the bucket
+    /// (or full-release) transition its new fill ratio crosses. Shared by
+    /// `free` and `free_zeroed`, which differ only in how the freed object's
+    /// bytes are (or aren't) scrubbed before this runs.
+    fn rebucket_after_free(
+        &mut self,
+        page: &mut Page,
+        was_full: bool,
+        old_bucket: Option<usize>,
+        page_source: &dyn Allocator,
+    ) {
+        let is_cur_page = matches!(&self.cur_page, Some(cur) if core::ptr::eq(*cur, page));
+        if is_cur_page {
+            // `cur_page` isn't tracked in any PageHdrList -- nothing to move.
+            return;
+        }
+
+        if page.hdr.num_free() as usize == Page::object_count(&self.bin_desc) {
+            if was_full {
+                self.full_pages.remove(page as *mut Page);
+            } else {
+                self.partial_pages[old_bucket.unwrap()].remove(page as *mut Page);
+            }
+
+            unsafe {
+                let page_ptr = NonNull::new_unchecked(page as *mut Page as *mut u8);
+                page_source.deallocate(page_ptr, self.page_layout());
+            }
+            return;
+        }
+
+        let new_bucket = page.free_ratio_bucket(&self.bin_desc);
+
+        if was_full {
+            self.full_pages.remove(page as *mut Page);
+            self.partial_pages[new_bucket].push_back(page as *mut Page);
+        } else if new_bucket != old_bucket.unwrap() {
+            self.partial_pages[old_bucket.unwrap()].remove(page as *mut Page);
+            self.partial_pages[new_bucket].push_back(page as *mut Page);
+        }
+    }
+}
+
+/// Objects a `Magazine` can hold before a free-time flush is needed.
+const MAGAZINE_CAPACITY: usize = 16;
+
+/// Objects moved between a magazine and its bin's shared `Pool` on a
+/// refill (magazine empty, allocate-time) or flush (magazine full,
+/// free-time): big enough that a core only takes the shared lock once per
+/// `MAGAZINE_BATCH` allocations/frees, small enough that a burst of
+/// cross-core frees doesn't strand too many objects in one core's magazine.
+const MAGAZINE_BATCH: usize = 8;
+
+/// Upper bound on cores a `MagazineCache` keeps a dedicated magazine for.
+/// Mirrors `kernel::smp::NUM_CORES` (4, the rpi3's core count); a core id at
+/// or past this just shares the last slot with its neighbours rather than
+/// panicking, trading a little contention for not needing to know the real
+/// core count here.
+const MAX_CORES: usize = 4;
+
+/// A small LIFO stack of free objects, caching one core's worth of
+/// allocate/free traffic for one bin without touching that bin's shared
+/// `Pool` lock.
+struct Magazine {
+    objects: Vec<NonNull<u8>, MAGAZINE_CAPACITY>,
+}
+
+unsafe impl Send for Magazine {}
+
+impl Magazine {
+    const fn new() -> Self {
+        Magazine {
+            objects: Vec::new(),
+        }
+    }
+
+    fn pop(&mut self) -> Option<NonNull<u8>> {
+        self.objects.pop()
+    }
+
+    /// Returns `false` (without touching `self`) if the magazine is full.
+    fn push(&mut self, ptr: NonNull<u8>) -> bool {
+        self.objects.push(ptr).is_ok()
+    }
+
+    fn len(&self) -> usize {
+        self.objects.len()
+    }
+}
+
+/// Per-core magazine cache fronting one bin's shared `Pool`.
+///
+/// Every bin is otherwise guarded by a single `Mutex<Pool>`, so on a
+/// multi-core target all cores contend on the same lock per size class.
+/// Each core keeps its own `Magazine` here, filled/drained from the shared
+/// `Pool` `MAGAZINE_BATCH` objects at a time, so the common allocate/free
+/// case touches no cross-core lock at all. The caller supplies `core_id`
+/// (from `arch::Current::current_core_id()`) rather than this type reading
+/// it itself, the same way `Pool` takes `page_source` as a parameter
+/// instead of looking up the buddy allocator on its own.
+pub struct MagazineCache {
+    bin_desc: BinDesc,
+    pool: Mutex<Pool>,
+    magazines: [Mutex<Magazine>; MAX_CORES],
+    #[cfg(test)]
+    pool_lock_count: AtomicUsize,
+}
+
+impl MagazineCache {
+    fn new(bin_desc: BinDesc) -> Self {
+        Self {
+            bin_desc,
+            pool: Mutex::new(Pool::new(bin_desc)),
+            magazines: core::array::from_fn(|_| Mutex::new(Magazine::new())),
+            #[cfg(test)]
+            pool_lock_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// The bin this cache fronts -- read without touching `pool`'s lock, so
+    /// `Slab::allocate` can pick a bin by size before ever locking anything.
+    fn bin_desc(&self) -> BinDesc {
+        self.bin_desc
+    }
+
+    #[cfg(test)]
+    fn note_pool_lock(&self) {
+        self.pool_lock_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Allocates one object, preferring `core_id`'s magazine. On a miss,
+    /// refills it with up to `MAGAZINE_BATCH` objects from the shared `Pool`
+    /// in one locked operation, then retries.
+    pub fn alloc(&self, core_id: usize, page_source: &dyn Allocator) -> Option<NonNull<u8>> {
+        let mut magazine = self.magazines[core_id % MAX_CORES].lock();
+
+        if let Some(ptr) = magazine.pop() {
+            return Some(ptr);
+        }
+
+        #[cfg(test)]
+        self.note_pool_lock();
+        let mut pool = self.pool.lock();
+        for _ in 0..MAGAZINE_BATCH {
+            let Some(ptr) = pool.alloc(page_source) else {
+                break;
+            };
+
+            if !magazine.push(ptr) {
+                // Capacity < a batch shouldn't happen given the constants
+                // above, but don't leak the object if it ever does.
+                return Some(ptr);
+            }
+        }
+
+        magazine.pop()
+    }
+
+    /// Frees an object, pushing it onto `core_id`'s magazine -- valid
+    /// regardless of which core originally allocated it, since a magazine's
+    /// contents are scoped to this bin, not to the core that filled it.
+    /// Flushes `MAGAZINE_BATCH` objects back to the shared `Pool` first if
+    /// that magazine is already full.
+    pub fn free(&self, core_id: usize, ptr: *mut u8, page_source: &dyn Allocator) {
+        let mut magazine = self.magazines[core_id % MAX_CORES].lock();
+
+        if magazine.len() == MAGAZINE_CAPACITY {
+            #[cfg(test)]
+            self.note_pool_lock();
+            let mut pool = self.pool.lock();
+            for _ in 0..MAGAZINE_BATCH {
+                let Some(flushed) = magazine.pop() else {
+                    break;
+                };
+                pool.free(flushed.as_ptr(), page_source);
+            }
+        }
+
+        let pushed = magazine.push(unsafe { NonNull::new_unchecked(ptr) });
+        debug_assert!(pushed, "magazine was just drained below capacity");
     }
 }
 
 const MAX_BINS: usize = 100;
 
 struct Slab {
-    bins: Vec<Mutex<Pool>, MAX_BINS>,
+    bins: Vec<MagazineCache, MAX_BINS>,
 }
 
 const MIN_ALIGN_SIZE: usize = 16;
 const MAX_OBJECT_SIZE: usize = 2048;
 
-const fn bin_count(max_alloc_size: usize) -> usize {}
+/// Bins below `MIN_ALIGN_SIZE << BIN_SHIFT` bytes are spaced every
+/// `MIN_ALIGN_SIZE` bytes; bins at or above it are spaced `1 / (1 <<
+/// BIN_SHIFT)` of their octave apart. `BIN_SHIFT = 3` is exactly the file's
+/// own "at worst 12.5 %" fragmentation budget (`1/8 == 12.5%`).
+const BIN_SHIFT: u32 = 3;
+
+/// Smallest multiple of `step` that is `>= size`.
+const fn round_up(size: usize, step: usize) -> usize {
+    ((size + step - 1) / step) * step
+}
+
+/// The exact object size the bin covering `size` hands out: every size in
+/// `(prev_bin_size, this_bin_size]` is served by this bin, so rounding a
+/// requested size up to one of these values is how both `bin_count` and the
+/// allocation path pick a bin.
+const fn round_up_to_bin_size(size: usize) -> usize {
+    let size = if size < MIN_ALIGN_SIZE {
+        MIN_ALIGN_SIZE
+    } else {
+        size
+    };
+    let linear_limit = MIN_ALIGN_SIZE << BIN_SHIFT;
+
+    if size <= linear_limit {
+        round_up(size, MIN_ALIGN_SIZE)
+    } else {
+        let octave = usize::BITS - 1 - size.leading_zeros();
+        let step = 1usize << (octave - BIN_SHIFT);
+        round_up(size, step)
+    }
+}
+
+/// Number of bins needed to cover every object size from `MIN_ALIGN_SIZE` up
+/// to `max_alloc_size`, at this file's size-class scheme (see
+/// `round_up_to_bin_size`). Used both to size `Slab::new`'s bin table and as
+/// a sanity check (`MAX_BINS` must stay comfortably above this) that the
+/// scheme hasn't grown past the table built for it.
+const fn bin_count(max_alloc_size: usize) -> usize {
+    let mut count = 0;
+    let mut size = MIN_ALIGN_SIZE;
+
+    while size <= max_alloc_size {
+        count += 1;
+        size = round_up_to_bin_size(size + 1);
+    }
+
+    count
+}
+
+/// `PageHdr::slab_bin_id` value marking a page as the head of a large
+/// (`layout.size() > MAX_OBJECT_SIZE`) allocation served straight from the
+/// buddy allocator rather than any slab bin. No real bin ever gets this id,
+/// since `MAX_BINS` is well under `u8::MAX`.
+const LARGE_ALLOC_BIN_ID: u8 = u8::MAX;
+
+/// Page size `Slab`'s large-allocation path rounds up to; set once from
+/// `init`'s `min_page_size` argument. Defaults to 4 KiB so the fallback has
+/// a sane unit even for allocations made before `init` runs.
+static MIN_PAGE_SIZE: AtomicUsize = AtomicUsize::new(4096);
+
+fn min_page_size() -> usize {
+    MIN_PAGE_SIZE.load(Ordering::Relaxed)
+}
+
+/// Holds the buddy allocator backing every `Pool` and the large-allocation
+/// fallback, once `init_buddy_allocator` has one to give it. A plain
+/// `Mutex<Option<_>>` rather than a `BuddyAllocator` field directly, since
+/// `BuddyAllocator::manage` needs a real physical range that isn't known at
+/// `Slab::new`/`#[ctor]` time -- the same deferred-initialization problem
+/// `phys_alloc::PhysicalFrameAllocator`/`heap::HeapAllocator` solve with
+/// their own `ensure_init`, except here there's no sensible default to lazily
+/// seed: before `init` runs, there simply is no memory to hand out.
+struct PageSource {
+    inner: Mutex<Option<buddy::BuddyAllocator>>,
+}
+
+unsafe impl Allocator for PageSource {
+    fn allocate(&self, layout: Layout) -> core::result::Result<NonNull<[u8]>, AllocError> {
+        self.inner.lock().as_ref().ok_or(AllocError)?.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.inner
+            .lock()
+            .as_ref()
+            .expect("page_source used before vm::slab::init")
+            .deallocate(ptr, layout)
+    }
+}
+
+static PAGE_SOURCE: PageSource = PageSource {
+    inner: Mutex::new(None),
+};
+
+/// The buddy allocator backing every `Pool` and the large-allocation
+/// fallback. Populated by `init_buddy_allocator`.
+fn page_source() -> &'static dyn Allocator {
+    &PAGE_SOURCE
+}
 
 impl Slab {
+    /// Builds the bin table eagerly, using `min_page_size()`'s boot-time
+    /// default (see its doc comment) rather than waiting on `init`: `Slab`
+    /// is constructed once, by `#[ctor]`, before any real physical memory is
+    /// known, so there's no later point to rebuild it from.
     fn new() -> Self {
-        Self { bins: Vec::new() }
+        let mut bins = Vec::new();
+        let page_size = min_page_size() as u16;
+
+        let mut bin_id: u8 = 0;
+        let mut size = MIN_ALIGN_SIZE;
+        while size <= MAX_OBJECT_SIZE {
+            let bin_desc = BinDesc {
+                obj_size: size as u16,
+                page_size,
+                bin_id,
+                policy: SelectionPolicy::MostFreeFirst,
+            };
+
+            bins.push(MagazineCache::new(bin_desc))
+                .map_err(|_| ())
+                .expect("MAX_BINS must cover bin_count(MAX_OBJECT_SIZE)");
+
+            bin_id += 1;
+            size = round_up_to_bin_size(size + 1);
+        }
+
+        debug_assert_eq!(bins.len(), bin_count(MAX_OBJECT_SIZE));
+
+        Self { bins }
+    }
+
+    /// The bin that serves `size`-byte objects, or `None` if `size` is
+    /// larger than the biggest bin (callers above `MAX_OBJECT_SIZE` should
+    /// already have routed to `allocate_large` instead).
+    fn bin_for(&self, size: usize) -> Option<&MagazineCache> {
+        let size = round_up_to_bin_size(size);
+        let index = self
+            .bins
+            .partition_point(|cache| (cache.bin_desc().obj_size as usize) < size);
+        self.bins.get(index)
+    }
+
+    /// Serves a `Layout` too large for any slab bin directly from the buddy
+    /// allocator: `layout.size()` is rounded up to whole `min_page_size()`
+    /// pages, and the page count is stashed in the first page's `PageHdr`
+    /// (tagged with `LARGE_ALLOC_BIN_ID`) so `deallocate_large` can recover
+    /// how much to hand back without consulting any bin.
+    fn allocate_large(&self, layout: Layout) -> core::result::Result<NonNull<[u8]>, AllocError> {
+        let page_size = min_page_size();
+        debug_assert!(layout.align() <= page_size);
+
+        let page_count = (layout.size() + page_size - 1) / page_size;
+        debug_assert!(page_count > 0 && (page_count as u16) < FREE_LIST_END);
+
+        let span = Layout::from_size_align(page_count * page_size, page_size).map_err(|_| AllocError)?;
+        let mem = page_source().allocate(span)?;
+
+        let page = mem.as_ptr() as *mut Page;
+        unsafe {
+            (*page).hdr.set_slab_bin_id(LARGE_ALLOC_BIN_ID);
+            (*page).hdr.set_free_list(page_count as u16);
+        }
+
+        Ok(mem)
+    }
+
+    /// Down-rounds `ptr` to the page holding its `PageHdr`; if that page is
+    /// tagged `LARGE_ALLOC_BIN_ID`, releases all of its pages back to the
+    /// buddy allocator and returns `true`. Returns `false` when `ptr` isn't a
+    /// large allocation, so the caller can fall back to the slab bin path.
+    fn deallocate_large(&self, ptr: NonNull<u8>) -> bool {
+        let page_size = min_page_size();
+        let page_addr = ptr.as_ptr() as usize & !(page_size - 1);
+        let page = unsafe { &mut *(page_addr as *mut Page) };
+
+        if page.hdr.slab_bin_id() != LARGE_ALLOC_BIN_ID {
+            return false;
+        }
+
+        let page_count = page.hdr.free_list() as usize;
+        let span = Layout::from_size_align(page_count * page_size, page_size)
+            .expect("page_count/page_size were validated by allocate_large");
+
+        unsafe {
+            let span_ptr = NonNull::new_unchecked(page_addr as *mut u8);
+            page_source().deallocate(span_ptr, span);
+        }
+
+        true
     }
 }
 
@@ -300,16 +983,52 @@ static ALLOCATOR_IMPL: Slab = Slab::new();
 
 /// Global Allocator used by `alloc` crate.
 /// Relies on Slab for allocation/deallocation.
+///
+/// Gated behind `slab_global_alloc`: a binary that links `libmei` already
+/// picks its own `#[global_allocator]` (`kernel::heap::HeapAllocator`, for
+/// the one in this tree today) and only one can exist per binary, so this
+/// crate must not claim the role unconditionally. A binary that wants this
+/// allocator instead enables the feature and drops its own.
+#[cfg(feature = "slab_global_alloc")]
 #[global_allocator]
 static ALLOCATOR: GlobalSlab = GlobalSlab::new();
 
 unsafe impl Allocator for Slab {
     fn allocate(&self, layout: Layout) -> core::result::Result<NonNull<[u8]>, AllocError> {
-        todo!()
+        if layout.size() > MAX_OBJECT_SIZE {
+            return self.allocate_large(layout);
+        }
+
+        let bin = self
+            .bin_for(layout.size().max(layout.align()))
+            .ok_or(AllocError)?;
+
+        let core_id = arch::Current::current_core_id();
+        let ptr = bin.alloc(core_id, page_source()).ok_or(AllocError)?;
+
+        Ok(NonNull::slice_from_raw_parts(
+            ptr,
+            bin.bin_desc().obj_size as usize,
+        ))
     }
 
-    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
-        todo!()
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _layout: Layout) {
+        if self.deallocate_large(ptr) {
+            return;
+        }
+
+        // Read `PageHdr::slab_bin_id` off `ptr`'s page to find the owning
+        // bin and route to its `MagazineCache::free` -- this works even when
+        // `ptr` was allocated on a different core, since a magazine is
+        // scoped to the bin, not the core that filled it. `bin_id` is
+        // exactly that bin's index into `self.bins` (see `Slab::new`), so no
+        // lookup by size is needed here.
+        let page_addr = ptr.as_ptr() as usize & !(min_page_size() - 1);
+        let page = &*(page_addr as *const Page);
+        let bin_id = page.hdr.slab_bin_id() as usize;
+
+        let core_id = arch::Current::current_core_id();
+        self.bins[bin_id].free(core_id, ptr.as_ptr(), page_source());
     }
 }
 
@@ -328,6 +1047,8 @@ unsafe impl GlobalAlloc for GlobalSlab {
 }
 
 pub fn init(mem: Range<PhysicalAddress>, min_page_size: usize) -> Result<()> {
+    MIN_PAGE_SIZE.store(min_page_size, Ordering::Relaxed);
+
     let usable_mem = init_metadata(mem, min_page_size)?;
     init_buddy_allocator(usable_mem, min_page_size)
 }
@@ -342,16 +1063,36 @@ fn init_metadata(
     mem: Range<PhysicalAddress>,
     min_page_size: usize,
 ) -> Result<Range<PhysicalAddress>> {
-    todo!()
+    let start = PhysicalAddress::new(mem.start.align_up(min_page_size));
+    let end = PhysicalAddress::new(mem.end.align_down(min_page_size));
+
+    if start >= end {
+        return Err(Error::PhysicalOOM);
+    }
+
+    Ok(start..end)
 }
 
+/// Hands `mem` to a fresh `vm::buddy::BuddyAllocator` and installs it as
+/// `page_source`'s backing allocator.
 fn init_buddy_allocator(mem: Range<PhysicalAddress>, min_page_size: usize) -> Result<()> {
-    todo!()
-}
+    let span = mem.end.as_raw_ptr() - mem.start.as_raw_ptr();
+    if span < min_page_size {
+        return Err(Error::PhysicalOOM);
+    }
 
-// pub fn get_page_allocator() -> &'static impl Allocator {
-//     todo!()
-// }
+    // The largest power-of-two run `mem` can possibly back.
+    let max_alloc_size = 1usize << span.ilog2();
+
+    let allocator =
+        unsafe { buddy::BuddyAllocator::manage(mem.clone(), min_page_size, max_alloc_size) }
+            .ok_or(Error::PhysicalOOM)?;
+    unsafe { allocator.add_region(mem) };
+
+    *PAGE_SOURCE.inner.lock() = Some(allocator);
+
+    Ok(())
+}
 
 impl Debug for PageHdr {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -360,6 +1101,7 @@ impl Debug for PageHdr {
         let num_free = self.num_free();
         let free_list = self.free_list();
         let slab_bin_id = self.slab_bin_id();
+        let clean = self.clean();
 
         f.debug_struct("PageHdr")
             .field("prev", &prev)
@@ -367,6 +1109,276 @@ impl Debug for PageHdr {
             .field("num_free", &num_free)
             .field("free_list", &free_list)
             .field("slab_bin_id", &slab_bin_id)
+            .field("clean", &clean)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::alloc::System;
+    use std::vec::Vec;
+
+    use super::*;
+
+    // Chosen so a page holds exactly 5 objects: (4096 - size_of::<PageHdr>()) / 815 == 5.
+    const PAGE_SIZE: u16 = 4096;
+    const OBJ_SIZE: u16 = 815;
+
+    fn bin(policy: SelectionPolicy) -> BinDesc {
+        BinDesc {
+            obj_size: OBJ_SIZE,
+            page_size: PAGE_SIZE,
+            bin_id: 0,
+            policy,
+        }
+    }
+
+    fn page_addr_of(ptr: *mut u8) -> usize {
+        ptr as usize & !(PAGE_SIZE as usize - 1)
+    }
+
+    fn list_len(list: &PageHdrList) -> usize {
+        let mut count = 0;
+        let mut cur = list.head;
+        while !cur.is_null() {
+            count += 1;
+            cur = unsafe { (*cur).hdr.get_next_link() as *mut Page };
+        }
+        count
+    }
+
+    #[test]
+    fn alloc_free_roundtrip() {
+        let mut pool = Pool::new(bin(SelectionPolicy::MostFreeFirst));
+
+        let a = pool.alloc(&System).unwrap();
+        let b = pool.alloc(&System).unwrap();
+        assert_ne!(a.as_ptr(), b.as_ptr());
+
+        unsafe {
+            core::ptr::write_bytes(a.as_ptr(), 0xAA, OBJ_SIZE as usize);
+            core::ptr::write_bytes(b.as_ptr(), 0xBB, OBJ_SIZE as usize);
+        }
+
+        pool.free(a.as_ptr(), &System);
+        pool.free(b.as_ptr(), &System);
+
+        // cur_page is retained fully-free rather than handed back to
+        // `page_source` -- the next allocation should reuse it.
+        let c = pool.alloc(&System).unwrap();
+        assert_eq!(page_addr_of(a.as_ptr()), page_addr_of(c.as_ptr()));
+    }
+
+    #[test]
+    fn bucket_transitions_move_between_lists_and_release_when_fully_free() {
+        let mut pool = Pool::new(bin(SelectionPolicy::MostFreeFirst));
+        let object_count = Page::object_count(&pool.bin_desc);
+        assert_eq!(object_count, 5);
+
+        // Fill the first page (stays `cur_page`, full but untracked).
+        let mut page1_ptrs = Vec::new();
+        for _ in 0..object_count {
+            page1_ptrs.push(pool.alloc(&System).unwrap().as_ptr());
+        }
+        let page1_addr = page_addr_of(page1_ptrs[0]);
+
+        // One more alloc pushes the exhausted page into `full_pages` and
+        // carves a fresh `cur_page`.
+        let spill = pool.alloc(&System).unwrap();
+        assert_eq!(list_len(&pool.full_pages), 1);
+        assert_ne!(page_addr_of(spill.as_ptr()), page1_addr);
+
+        // Freeing one object moves page1 from `full_pages` into bucket 0
+        // (at least 1, up to 20%, free).
+        pool.free(page1_ptrs[0], &System);
+        assert_eq!(list_len(&pool.full_pages), 0);
+        assert_eq!(list_len(&pool.partial_pages[0]), 1);
+
+        // Freeing the next three objects should walk page1 through buckets
+        // 1, 2 and 3 in turn, leaving it there (one object, #4, still live).
+        for (bucket, ptr) in page1_ptrs[1..4].iter().enumerate() {
+            pool.free(*ptr, &System);
+            assert_eq!(list_len(&pool.partial_pages[bucket]), 0);
+            assert_eq!(list_len(&pool.partial_pages[bucket + 1]), 1);
+        }
+
+        // Freeing the last object empties page1 and releases it back to
+        // `page_source` instead of sitting in bucket 4.
+        pool.free(page1_ptrs[4], &System);
+        assert_eq!(list_len(&pool.partial_pages[3]), 0);
+        assert_eq!(list_len(&pool.partial_pages[4]), 0);
+    }
+
+    /// Leaves `pool` with two non-`cur_page` partial pages -- one in bucket 0
+    /// (1 of 5 free), one in bucket 3 (4 of 5 free) -- and a freshly-started
+    /// `cur_page` with one object already allocated out of it. Returns
+    /// `(sparse_page_addr, dense_page_addr)`.
+    fn setup_two_partial_pages(policy: SelectionPolicy) -> (Pool, usize, usize) {
+        let mut pool = Pool::new(bin(policy));
+        let object_count = Page::object_count(&pool.bin_desc);
+
+        let mut page_a = Vec::new();
+        for _ in 0..object_count {
+            page_a.push(pool.alloc(&System).unwrap().as_ptr());
+        }
+        let page_a_addr = page_addr_of(page_a[0]);
+
+        let mut page_b = Vec::new();
+        page_b.push(pool.alloc(&System).unwrap().as_ptr()); // spills A into full_pages
+        for _ in 1..object_count {
+            page_b.push(pool.alloc(&System).unwrap().as_ptr());
+        }
+        let page_b_addr = page_addr_of(page_b[0]);
+        assert_ne!(page_a_addr, page_b_addr);
+
+        pool.alloc(&System).unwrap(); // spills B into full_pages, starts a new cur_page
+        assert_eq!(list_len(&pool.full_pages), 2);
+
+        // A ends up at 4/5 free (bucket 3), B at 1/5 free (bucket 0).
+        for ptr in &page_a[0..4] {
+            pool.free(*ptr, &System);
+        }
+        pool.free(page_b[0], &System);
+
+        assert_eq!(list_len(&pool.full_pages), 0);
+        assert_eq!(list_len(&pool.partial_pages[3]), 1);
+        assert_eq!(list_len(&pool.partial_pages[0]), 1);
+
+        (pool, page_b_addr, page_a_addr)
+    }
+
+    #[test]
+    fn most_free_first_prefers_the_emptiest_partial_page() {
+        let (mut pool, _sparse, dense) = setup_two_partial_pages(SelectionPolicy::MostFreeFirst);
+
+        // Exhaust `cur_page`'s four remaining free objects (it holds 1
+        // allocated, 4 free) so the next `alloc` must pull from a bucket.
+        for _ in 0..4 {
+            pool.alloc(&System).unwrap();
+        }
+
+        let picked = pool.alloc(&System).unwrap();
+        assert_eq!(page_addr_of(picked.as_ptr()), dense);
+    }
+
+    #[test]
+    fn least_free_first_prefers_the_fullest_partial_page() {
+        let (mut pool, sparse, _dense) = setup_two_partial_pages(SelectionPolicy::LeastFreeFirst);
+
+        for _ in 0..4 {
+            pool.alloc(&System).unwrap();
+        }
+
+        let picked = pool.alloc(&System).unwrap();
+        assert_eq!(page_addr_of(picked.as_ptr()), sparse);
+    }
+
+    #[test]
+    fn alloc_zeroed_returns_zero_filled_memory() {
+        let mut pool = Pool::new(bin(SelectionPolicy::MostFreeFirst));
+
+        let ptr = pool.alloc_zeroed(&System).unwrap();
+        let bytes = unsafe { core::slice::from_raw_parts(ptr.as_ptr(), OBJ_SIZE as usize) };
+        assert!(bytes.iter().all(|&b| b == 0));
+
+        // A freshly carved page starts fully `clean`; only the first
+        // object's free-list link prefix needed zeroing.
+        assert!(pool.cur_page.as_ref().unwrap().is_clean());
+    }
+
+    #[test]
+    fn plain_free_gives_up_clean_but_free_zeroed_preserves_it() {
+        let mut pool = Pool::new(bin(SelectionPolicy::MostFreeFirst));
+
+        let a = pool.alloc_zeroed(&System).unwrap();
+        assert!(pool.cur_page.as_ref().unwrap().is_clean());
+
+        // Scrub-on-free: the page stays clean since every free slot is
+        // still known-zero.
+        pool.free_zeroed(a.as_ptr(), &System);
+        assert!(pool.cur_page.as_ref().unwrap().is_clean());
+
+        // A plain alloc/free cycle can leave caller-written garbage behind,
+        // so clean must be given up.
+        let b = pool.alloc(&System).unwrap();
+        unsafe { core::ptr::write_bytes(b.as_ptr(), 0xCC, OBJ_SIZE as usize) };
+        pool.free(b.as_ptr(), &System);
+        assert!(!pool.cur_page.as_ref().unwrap().is_clean());
+    }
+
+    #[test]
+    fn magazine_cache_round_trips_allocations_on_one_core() {
+        let cache = MagazineCache::new(bin(SelectionPolicy::MostFreeFirst));
+
+        let a = cache.alloc(0, &System).unwrap();
+        let b = cache.alloc(0, &System).unwrap();
+        assert_ne!(a.as_ptr(), b.as_ptr());
+
+        cache.free(0, a.as_ptr(), &System);
+        cache.free(0, b.as_ptr(), &System);
+
+        // Both should be servable again out of core 0's magazine.
+        let c = cache.alloc(0, &System).unwrap();
+        let d = cache.alloc(0, &System).unwrap();
+        assert!([a.as_ptr(), b.as_ptr()].contains(&c.as_ptr()));
+        assert!([a.as_ptr(), b.as_ptr()].contains(&d.as_ptr()));
+        assert_ne!(c.as_ptr(), d.as_ptr());
+    }
+
+    #[test]
+    fn magazine_cache_allows_cross_core_free() {
+        let cache = MagazineCache::new(bin(SelectionPolicy::MostFreeFirst));
+
+        let ptr = cache.alloc(0, &System).unwrap();
+        // Freed on a different core than it was allocated on.
+        cache.free(1, ptr.as_ptr(), &System);
+
+        let reused = cache.alloc(1, &System).unwrap();
+        assert_eq!(ptr.as_ptr(), reused.as_ptr());
+    }
+
+    #[test]
+    fn magazine_refill_batches_shared_pool_locks() {
+        let cache = MagazineCache::new(bin(SelectionPolicy::MostFreeFirst));
+
+        const ALLOCS: usize = MAGAZINE_BATCH * 5;
+        for _ in 0..ALLOCS {
+            cache.alloc(0, &System).unwrap();
+        }
+
+        // Every MAGAZINE_BATCH allocations should cost exactly one shared
+        // `Pool` lock, not one lock per allocation.
+        let lock_count = cache.pool_lock_count.load(Ordering::Relaxed);
+        assert_eq!(lock_count, ALLOCS.div_ceil(MAGAZINE_BATCH));
+    }
+
+    #[test]
+    fn magazine_flush_batches_shared_pool_locks_on_free() {
+        let cache = MagazineCache::new(bin(SelectionPolicy::MostFreeFirst));
+
+        // Obtain objects straight from the shared `Pool`, bypassing the
+        // magazine entirely, so its state stays empty going into the fill
+        // below regardless of how `Pool` itself carves pages.
+        let mut ptrs = Vec::new();
+        {
+            let mut pool = cache.pool.lock();
+            for _ in 0..(MAGAZINE_CAPACITY + 1) {
+                ptrs.push(pool.alloc(&System).unwrap());
+            }
+        }
+
+        // Filling the magazine to exactly capacity needs no flush.
+        for &ptr in &ptrs[..MAGAZINE_CAPACITY] {
+            cache.free(0, ptr.as_ptr(), &System);
+        }
+        assert_eq!(cache.pool_lock_count.load(Ordering::Relaxed), 0);
+
+        // Freeing one more forces exactly one flush of MAGAZINE_BATCH
+        // objects back to the shared `Pool` to make room.
+        cache.free(0, ptrs[MAGAZINE_CAPACITY].as_ptr(), &System);
+        assert_eq!(cache.pool_lock_count.load(Ordering::Relaxed), 1);
+    }
+}
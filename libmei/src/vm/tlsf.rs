@@ -0,0 +1,532 @@
+use core::{cmp::max, mem::size_of, ops::Range};
+
+use spin::Mutex;
+
+#[cfg(test)]
+use std::vec::Vec;
+
+use crate::{
+    address::{Address, PhysicalAddress},
+    error::{Error, Result},
+};
+
+use intrusive_collections::{intrusive_adapter, LinkedList, LinkedListLink};
+
+/// log2 of the number of linear second-level buckets `SL_COUNT` subdivides
+/// each first-level (power of two) class into.
+const SLI: u32 = 4;
+const SL_COUNT: usize = 1 << SLI;
+
+const FREE_BIT: usize = 1;
+
+/// All block sizes are rounded up to a multiple of this, which guarantees
+/// bit 0 (used as the free flag) is always free for `size_and_free` to use.
+const ALIGN: usize = size_of::<usize>();
+
+fn align_up(size: usize) -> usize {
+    (size + (ALIGN - 1)) & !(ALIGN - 1)
+}
+
+/// `TlsfAllocator` is a Two-Level Segregated Fit companion to `BuddyAllocator`:
+/// it manages a region of physical memory the same way, but hands out
+/// near-exact-fit blocks of arbitrary size instead of always rounding up to
+/// the next power of two.
+///
+/// Free blocks of size `s` live in `free[fl][sl]`, where `fl = floor(log2(s))`
+/// and `sl` linearly subdivides `[2^fl, 2^(fl+1))` into `SL_COUNT` buckets.
+/// A first-level bitmap (one bit per `fl`) and a per-`fl` second-level bitmap
+/// let `alloc` find the smallest non-empty suitable bucket in O(1) via
+/// `trailing_zeros`, no scanning. Every block (free or allocated) carries a
+/// boundary-tag header recording its size, a free flag, and a pointer to the
+/// physically-preceding block's header, so `free` can merge with whichever
+/// physically-adjacent neighbours are themselves free.
+pub struct TlsfAllocator {
+    storage: &'static Storage,
+}
+
+impl TlsfAllocator {
+    /// Creates a `TlsfAllocator` that manages the entirety of `mem` as a
+    /// single free block.
+    ///
+    /// # Safety
+    ///
+    /// `mem` range should be pointing to a valid Physical Address range,
+    /// that is *NOT* currently in use.
+    /// After this call, the provided physical memory range is OWNED by
+    /// the allocator and any direct use of the memory range, that is not
+    /// obtained by `alloc` is Undefined
+    pub unsafe fn manage(
+        mem: Range<PhysicalAddress>,
+        min_alloc_size: usize,
+        max_alloc_size: usize,
+    ) -> Option<Self> {
+        let storage = Storage::init(&mem, min_alloc_size, max_alloc_size)?;
+        Some(Self { storage })
+    }
+
+    /// Return a unique physical address range of at least `size` bytes.
+    ///
+    /// # Safety
+    ///
+    /// `self` must have been created using `manage`.
+    pub unsafe fn alloc(&self, size: usize) -> Result<PhysicalAddress> {
+        let size = align_up(max(size, self.storage.min_block_size));
+        self.storage.alloc(size)
+    }
+
+    /// Frees `ptr`, merging it with any physically-adjacent free neighbours.
+    ///
+    /// # Safety
+    ///
+    /// `self` must have been created using `manage`.
+    /// `ptr` must have been returned by a prior call to this allocator's `alloc`.
+    pub unsafe fn free(&self, ptr: PhysicalAddress) -> Result<()> {
+        self.storage.free(ptr)
+    }
+
+    #[cfg(test)]
+    /// Returns the list of (`2^fl`, block count) for every non-empty
+    /// `(fl, sl)` bucket; the first value is the lower bound of the bucket's
+    /// first-level class, shared by all of that class's `sl` buckets.
+    unsafe fn get_free_area_information(&self) -> Vec<(usize, usize)> {
+        self.storage.get_free_area_information()
+    }
+}
+
+#[repr(C)]
+struct BlockHeader {
+    /// Size of the block's payload (the bytes available to the caller, or to
+    /// a split), with bit 0 used as the free flag.
+    size_and_free: usize,
+    /// Address of the physically-preceding block's header, or `0` if this
+    /// block starts the arena.
+    prev_phys: usize,
+    /// Free-list link. Only meaningful while the block is free: once handed
+    /// out, this space is part of the caller's payload.
+    link: LinkedListLink,
+}
+
+impl BlockHeader {
+    unsafe fn init(this: *mut Self, prev_phys: usize, size: usize) -> &'static mut Self {
+        debug_assert_eq!(size & (ALIGN - 1), 0);
+        this.write(BlockHeader {
+            size_and_free: size | FREE_BIT,
+            prev_phys,
+            link: LinkedListLink::new(),
+        });
+        &mut *this
+    }
+
+    fn size(&self) -> usize {
+        self.size_and_free & !FREE_BIT
+    }
+
+    fn is_free(&self) -> bool {
+        self.size_and_free & FREE_BIT != 0
+    }
+
+    fn set_size(&mut self, size: usize) {
+        debug_assert_eq!(size & (ALIGN - 1), 0);
+        self.size_and_free = size | (self.size_and_free & FREE_BIT);
+    }
+
+    fn set_free(&mut self, free: bool) {
+        self.size_and_free = self.size() | if free { FREE_BIT } else { 0 };
+    }
+
+    fn addr(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn payload(&self) -> PhysicalAddress {
+        PhysicalAddress::new(self.addr() + size_of::<Self>())
+    }
+
+    unsafe fn from_payload(ptr: PhysicalAddress) -> &'static mut BlockHeader {
+        &mut *((ptr.as_raw_ptr() - size_of::<Self>()) as *mut BlockHeader)
+    }
+
+    unsafe fn next_phys(&self, end: usize) -> Option<&'static mut BlockHeader> {
+        let next = self.addr() + size_of::<Self>() + self.size();
+        (next < end).then(|| &mut *(next as *mut BlockHeader))
+    }
+
+    unsafe fn prev_phys(&self) -> Option<&'static mut BlockHeader> {
+        (self.prev_phys != 0).then(|| &mut *(self.prev_phys as *mut BlockHeader))
+    }
+
+    /// Fixes up the physically-following block's back-pointer after `self`
+    /// moved or was resized, so its `prev_phys` keeps pointing at `self`.
+    unsafe fn relink_next(&self, end: usize) {
+        if let Some(next) = self.next_phys(end) {
+            next.prev_phys = self.addr();
+        }
+    }
+}
+
+intrusive_adapter!(BlockAdapter<'a> = &'a BlockHeader: BlockHeader { link: LinkedListLink });
+
+type FreeList<'a> = LinkedList<BlockAdapter<'a>>;
+
+#[repr(C)]
+#[repr(align(64))]
+struct Storage {
+    start: PhysicalAddress,
+    end: PhysicalAddress,
+    min_fl: u32,
+    max_fl: u32,
+    min_block_size: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    min_fl: u32,
+    fl_bitmap: usize,
+    sl_bitmap: &'static mut [usize],
+    free_lists: &'static mut [FreeList<'static>],
+}
+
+impl Storage {
+    unsafe fn alloc(&self, size: usize) -> Result<PhysicalAddress> {
+        let (fl, sl) = Self::mapping_search(size, self.min_fl, self.max_fl);
+
+        let mut state = self.state.lock();
+        let (fl, sl) = match state.find_suitable(fl, sl) {
+            Some(index) => index,
+            None => return Err(Error::PhysicalOOM),
+        };
+
+        let block = state.remove_block(self.idx(fl, sl));
+        self.split_and_use(&mut state, block, size);
+
+        Ok(block.payload())
+    }
+
+    unsafe fn free(&self, ptr: PhysicalAddress) -> Result<()> {
+        if self.start > ptr || self.end < ptr {
+            return Err(Error::AllocError);
+        }
+
+        let mut block = BlockHeader::from_payload(ptr);
+        let mut state = self.state.lock();
+
+        if let Some(prev) = block.prev_phys() {
+            if prev.is_free() {
+                let idx = self.idx_of(prev);
+                state.unlink(idx, prev);
+                prev.set_size(prev.size() + size_of::<BlockHeader>() + block.size());
+                block = prev;
+            }
+        }
+
+        if let Some(next) = block.next_phys(self.end.as_raw_ptr()) {
+            if next.is_free() {
+                let idx = self.idx_of(next);
+                state.unlink(idx, next);
+                block.set_size(block.size() + size_of::<BlockHeader>() + next.size());
+            }
+        }
+
+        block.set_free(true);
+        block.relink_next(self.end.as_raw_ptr());
+        let idx = self.idx_of(block);
+        state.insert(idx, block);
+
+        Ok(())
+    }
+
+    /// Splits `block` (already removed from its free list) so that only
+    /// `size` bytes remain allocated to the caller, returning the remainder
+    /// (if large enough to be worth keeping) to its bucket.
+    unsafe fn split_and_use(&self, state: &mut State, block: &'static BlockHeader, size: usize) {
+        let block = &mut *(block as *const BlockHeader as *mut BlockHeader);
+        let remainder_size = block.size().saturating_sub(size + size_of::<BlockHeader>());
+
+        if remainder_size >= self.min_block_size {
+            block.set_size(size);
+
+            let remainder_addr = block.addr() + size_of::<BlockHeader>() + size;
+            let remainder = BlockHeader::init(
+                remainder_addr as *mut BlockHeader,
+                block.addr(),
+                remainder_size,
+            );
+            remainder.relink_next(self.end.as_raw_ptr());
+
+            let idx = self.idx_of(remainder);
+            state.insert(idx, remainder);
+        }
+
+        block.set_free(false);
+        block.relink_next(self.end.as_raw_ptr());
+    }
+
+    fn idx(&self, fl: u32, sl: usize) -> usize {
+        ((fl - self.min_fl) as usize) * SL_COUNT + sl
+    }
+
+    fn idx_of(&self, block: &BlockHeader) -> usize {
+        let (fl, sl) = Self::mapping_insert(block.size(), self.min_fl, self.max_fl);
+        self.idx(fl, sl)
+    }
+
+    /// Maps an exact block `size` (already on hand, e.g. being freed) to the
+    /// bucket it belongs in: `fl = floor(log2(size))`, `sl` the linear
+    /// sub-bucket within `[2^fl, 2^(fl+1))`. Sizes at or above `2^max_fl`
+    /// (e.g. the single free block spanning a freshly-`manage`d arena) all
+    /// land in the top bucket, same as `BuddyAllocator`'s top level.
+    fn mapping_insert(size: usize, min_fl: u32, max_fl: u32) -> (u32, usize) {
+        let size = max(size, 1usize << min_fl);
+        let fl = max(size.ilog2(), min_fl).min(max_fl);
+        let sl = if fl >= SLI {
+            (size.min((2usize << max_fl) - 1) - (1usize << fl)) >> (fl - SLI)
+        } else {
+            0
+        };
+
+        (fl, sl)
+    }
+
+    /// Maps a requested allocation `size` to the smallest bucket guaranteed
+    /// to only contain blocks `>= size`, by rounding `size` up to the next
+    /// `sl` granularity boundary before mapping it (the floor mapping
+    /// `mapping_insert` uses would instead find a bucket whose blocks can be
+    /// smaller than `size`).
+    fn mapping_search(size: usize, min_fl: u32, max_fl: u32) -> (u32, usize) {
+        let size = max(size, 1usize << min_fl);
+        let fl = size.ilog2();
+        let round = if fl >= SLI {
+            (1usize << (fl - SLI)) - 1
+        } else {
+            0
+        };
+        let rounded = size.saturating_add(round);
+        let fl = max(rounded.ilog2(), min_fl).min(max_fl);
+        let sl = if fl >= SLI {
+            (rounded.min((2usize << max_fl) - 1) - (1usize << fl)) >> (fl - SLI)
+        } else {
+            0
+        };
+
+        (fl, sl)
+    }
+
+    unsafe fn init(
+        mem: &Range<PhysicalAddress>,
+        min_alloc_size: usize,
+        max_alloc_size: usize,
+    ) -> Option<&'static mut Self> {
+        let min_block_size = max(min_alloc_size, size_of::<BlockHeader>()).next_power_of_two();
+        let max_alloc_size = max_alloc_size.next_power_of_two();
+
+        assert!(max_alloc_size > min_block_size);
+
+        let min_fl = min_block_size.ilog2();
+        let max_fl = max_alloc_size.ilog2();
+        let num_fl = (max_fl - min_fl + 1) as usize;
+        let end = PhysicalAddress::new(mem.end.align_down(ALIGN));
+        let mut alloc_start = mem.start;
+
+        let this = Self::claim_memory::<Self>(&mut alloc_start, end, 1)?;
+        let sl_bitmap = Self::claim_memory::<usize>(&mut alloc_start, end, num_fl)?;
+        let free_lists =
+            Self::claim_memory::<FreeList<'static>>(&mut alloc_start, end, num_fl * SL_COUNT)?;
+
+        for i in 0..num_fl {
+            sl_bitmap.add(i).write(0);
+        }
+        for i in 0..num_fl * SL_COUNT {
+            free_lists.add(i).write(FreeList::default());
+        }
+
+        alloc_start = PhysicalAddress::new(alloc_start.align_up(size_of::<BlockHeader>()));
+
+        this.write(Self {
+            start: alloc_start,
+            end,
+            min_fl,
+            max_fl,
+            min_block_size,
+            state: Mutex::new(State {
+                min_fl,
+                fl_bitmap: 0,
+                sl_bitmap: slice_mut(sl_bitmap, num_fl),
+                free_lists: slice_mut(free_lists, num_fl * SL_COUNT),
+            }),
+        });
+
+        let this = &mut *this;
+
+        let first_size = end.as_raw_ptr() - alloc_start.as_raw_ptr() - size_of::<BlockHeader>();
+        let first = BlockHeader::init(alloc_start.as_raw_ptr() as *mut BlockHeader, 0, first_size);
+
+        let mut state = this.state.lock();
+        let idx = this.idx_of(first);
+        state.insert(idx, first);
+        drop(state);
+
+        Some(this)
+    }
+
+    fn claim_memory<T: Sized>(
+        ptr: &mut PhysicalAddress,
+        end: PhysicalAddress,
+        n: usize,
+    ) -> Option<*mut T> {
+        let layout = core::alloc::Layout::new::<T>().repeat(n).unwrap().0;
+        let mem = ptr.align_up(layout.align());
+
+        if mem + layout.size() >= end.as_raw_ptr() {
+            return None;
+        }
+
+        *ptr = PhysicalAddress::new(mem + layout.size());
+
+        Some(mem as *mut T)
+    }
+
+    #[cfg(test)]
+    unsafe fn get_free_area_information(&self) -> Vec<(usize, usize)> {
+        let state = self.state.lock();
+        let mut info = Vec::new();
+
+        for fl in self.min_fl..=self.max_fl {
+            for sl in 0..SL_COUNT {
+                let idx = self.idx(fl, sl);
+                let count = state.free_lists[idx].iter().count();
+                if count != 0 {
+                    info.push((1usize << fl, count));
+                }
+            }
+        }
+
+        info
+    }
+}
+
+unsafe fn slice_mut<'a, T>(ptr: *mut T, n: usize) -> &'a mut [T] {
+    core::slice::from_raw_parts_mut(ptr, n)
+}
+
+impl State {
+    /// Finds the smallest non-empty bucket `(fl, sl)` that only contains
+    /// blocks `>= 2^fl + sl * 2^(fl - SLI)`, i.e. whose blocks are all
+    /// guaranteed large enough for whatever request mapped to `(fl, sl)`.
+    fn find_suitable(&self, fl: u32, sl: usize) -> Option<(u32, usize)> {
+        let sl_map = self.sl_bitmap[(fl - self.min_fl) as usize] & (usize::MAX << sl);
+        if sl_map != 0 {
+            return Some((fl, sl_map.trailing_zeros() as usize));
+        }
+
+        let fl_map = self.fl_bitmap & (usize::MAX << (fl - self.min_fl + 1));
+        if fl_map == 0 {
+            return None;
+        }
+
+        let next_fl = fl_map.trailing_zeros() + self.min_fl;
+        let sl_map = self.sl_bitmap[(next_fl - self.min_fl) as usize];
+        debug_assert_ne!(sl_map, 0);
+
+        Some((next_fl, sl_map.trailing_zeros() as usize))
+    }
+
+    fn remove_block(&mut self, idx: usize) -> &'static BlockHeader {
+        let block = self.free_lists[idx].pop_front().unwrap();
+        self.clear_bit(idx);
+        block
+    }
+
+    fn unlink(&mut self, idx: usize, block: &BlockHeader) {
+        let mut cursor = self.free_lists[idx].cursor_mut_from_ptr(block as *const BlockHeader);
+        cursor.remove().unwrap();
+        self.clear_bit(idx);
+    }
+
+    fn insert(&mut self, idx: usize, block: &'static BlockHeader) {
+        self.free_lists[idx].push_front(block);
+        self.set_bit(idx);
+    }
+
+    fn set_bit(&mut self, idx: usize) {
+        let fl = idx / SL_COUNT;
+        let sl = idx % SL_COUNT;
+        self.sl_bitmap[fl] |= 1 << sl;
+        self.fl_bitmap |= 1 << fl;
+    }
+
+    fn clear_bit(&mut self, idx: usize) {
+        let fl = idx / SL_COUNT;
+        let sl = idx % SL_COUNT;
+        if !self.free_lists[idx].is_empty() {
+            return;
+        }
+        self.sl_bitmap[fl] &= !(1 << sl);
+        if self.sl_bitmap[fl] == 0 {
+            self.fl_bitmap &= !(1 << fl);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::{collections::HashSet, vec::Vec};
+
+    use crate::address::PhysicalAddress;
+
+    use super::TlsfAllocator;
+
+    #[test]
+    fn tlsf_near_exact_fit() {
+        const MIN_ALLOC_SIZE: usize = 32;
+        const MAX_ALLOC_SIZE: usize = 64 * 1024;
+
+        let chunk = std::boxed::Box::new([0xfeu8; MAX_ALLOC_SIZE]);
+        let mem_start = PhysicalAddress::new(chunk.as_ptr() as usize);
+        let mem_end = mem_start + MAX_ALLOC_SIZE;
+        let allocator = unsafe {
+            TlsfAllocator::manage(mem_start..mem_end, MIN_ALLOC_SIZE, MAX_ALLOC_SIZE).unwrap()
+        };
+
+        let mut ptrs = Vec::new();
+        for _ in 0..4 {
+            let ptr = unsafe { allocator.alloc(1500) };
+            assert!(ptr.is_ok());
+            ptrs.push(ptr.unwrap());
+        }
+
+        let mut unique = HashSet::new();
+        for ptr in &ptrs {
+            assert!(unique.insert(ptr.as_raw_ptr()));
+        }
+
+        for ptr in ptrs {
+            assert!(unsafe { allocator.free(ptr) }.is_ok());
+        }
+    }
+
+    #[test]
+    fn tlsf_coalesces_on_free() {
+        const MIN_ALLOC_SIZE: usize = 32;
+        const MAX_ALLOC_SIZE: usize = 64 * 1024;
+
+        let chunk = std::boxed::Box::new([0xfeu8; MAX_ALLOC_SIZE]);
+        let mem_start = PhysicalAddress::new(chunk.as_ptr() as usize);
+        let mem_end = mem_start + MAX_ALLOC_SIZE;
+        let allocator = unsafe {
+            TlsfAllocator::manage(mem_start..mem_end, MIN_ALLOC_SIZE, MAX_ALLOC_SIZE).unwrap()
+        };
+
+        let before = unsafe { allocator.get_free_area_information() };
+
+        let a = unsafe { allocator.alloc(512) }.unwrap();
+        let b = unsafe { allocator.alloc(512) }.unwrap();
+
+        unsafe { allocator.free(a).unwrap() };
+        unsafe { allocator.free(b).unwrap() };
+
+        let after = unsafe { allocator.get_free_area_information() };
+        assert_eq!(before, after);
+    }
+}
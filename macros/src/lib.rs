@@ -16,8 +16,13 @@ pub fn exception_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let exception_handler_block = exception_handler.block;
     let asm_block = format!(
         r"
-        /* 30 general purpose registers + Link Register, ELR_EL1, ESR_EL1, SPSR_EL1 */
-        sub sp, sp, #(8 * 34)
+        /*
+        * 30 general purpose registers + Link Register, ELR_EL1, ESR_EL1, SPSR_EL1,
+        * SP_EL0. SP_EL0 only ever matters for a vector taken from EL0, but it's
+        * saved/restored unconditionally -- reading/writing the banked register
+        * costs nothing extra and keeps every vector's frame the same shape.
+        */
+        sub sp, sp, #(8 * 36)
 
         stp x0, x1, [sp]
         stp x2, x3, [sp, #(16 * 1)]
@@ -36,15 +41,17 @@ pub fn exception_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
         stp x28, x29, [sp, #(16 * 14)]
 
         /*
-        * Add the exception link register (ELR_EL1), saved program status (SPSR_EL1) and exception
-        * syndrome register (ESR_EL1).
+        * Add the exception link register (ELR_EL1), saved program status (SPSR_EL1),
+        * exception syndrome register (ESR_EL1) and SP_EL0.
         */
         mrs	x1,  ELR_EL1
         mrs	x2,  SPSR_EL1
         mrs	x3,  ESR_EL1
+        mrs	x4,  SP_EL0
 
         stp	lr, x1, [sp, #(16 * 15)]
         stp	x2, x3, [sp, #(16 * 16)]
+        str	x4, [sp, #(16 * 17)]
 
         /* x0 is the first argument for the function called through the handler */
         mov	x0,  sp
@@ -52,11 +59,21 @@ pub fn exception_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
         /* Call the handler */
         bl {exception_handler_func_impl}
 
+        /*
+        * Give the scheduler a chance to switch stacks: `__sched_maybe_switch` only
+        * ever moves `sp` to a different task's saved context -- everything the
+        * restore sequence below reads, it rereads fresh from `[sp, ...]`, so no
+        * register is "live" across this call and it's safe from every vector.
+        */
+        bl __sched_maybe_switch
+
         ldr	x19,      [sp, #16 * 16]
         ldp	lr,  x20, [sp, #16 * 15]
+        ldr	x21,      [sp, #16 * 17]
 
         msr	SPSR_EL1, x19
         msr	ELR_EL1,  x20
+        msr	SP_EL0,   x21
 
         ldp x0, x1, [sp]
         ldp x2, x3, [sp, #(16 * 1)]
@@ -75,7 +92,7 @@ pub fn exception_handler(_attr: TokenStream, item: TokenStream) -> TokenStream {
         ldp x28, x29, [sp, #(16 * 14)]
         ldr x30, [sp, #(16 * 15)]
 
-        add sp, sp, #(8 * 34)
+        add sp, sp, #(8 * 36)
         eret",
     );
 
@@ -1,6 +1,8 @@
-extern "C" {
-    fn _exit(code: i32) -> !;
-}
+//! Exit path for a kernel binary running under QEMU, used by the custom test
+//! harness in `lib.rs` to report pass/fail back to the host shell. Goes through
+//! AArch64 semihosting (`SYS_EXIT`) rather than the linker-provided `_exit` stub
+//! this used to call, since a semihosting host can map the reported status
+//! straight onto its own process exit code without any extra asm glue.
 
 #[repr(C)]
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -9,6 +11,27 @@ pub enum ExitCode {
     Failure = -1,
 }
 
+/// `SYS_EXIT`, the semihosting operation number for the exit call. On AArch64
+/// it takes a pointer to a two-word parameter block (reason, subcode) in `x1`.
+const SYS_EXIT: u32 = 0x18;
+
+/// `ADP_Stopped_ApplicationExit`: the semihosting "stopped" reason a debug host
+/// maps onto its own process exit status, used as the first word of the
+/// `SYS_EXIT` parameter block.
+const ADP_STOPPED_APPLICATION_EXIT: u64 = 0x2_0026;
+
+/// Issues a `SYS_EXIT` semihosting call carrying `code`, handing it to the host
+/// as the process exit status. Never returns: the `hlt` traps to the debug host,
+/// which tears the guest down instead of returning control to it.
 pub fn exit(code: ExitCode) -> ! {
-    unsafe { _exit(code as i32) }
+    let parameter_block: [u64; 2] = [ADP_STOPPED_APPLICATION_EXIT, code as i32 as u64];
+
+    unsafe {
+        core::arch::asm!(
+            "hlt #0xF000",
+            in("w0") SYS_EXIT,
+            in("x1") parameter_block.as_ptr(),
+            options(noreturn, nostack),
+        );
+    }
 }